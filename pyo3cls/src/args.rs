@@ -0,0 +1,171 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+use syn;
+
+use utils::Errors;
+
+
+/// Single entry of the `#[args(...)]` method attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    /// `"/"`, all arguments listed before it are positional-only
+    PosOnlySeparator,
+    /// `"*"`, all arguments listed after it are keyword-only
+    VarArgsSeparator,
+    /// `name = "*"`
+    VarArgs(String),
+    /// `name = "**"`
+    KeywordArgs(String),
+    /// `name` or `name = "default value"`
+    Arg(String, Option<String>),
+}
+
+/// Parse content of `#[args(...)]` attribute
+pub fn parse_arguments(items: &[syn::NestedMetaItem], errors: &mut Errors) -> Vec<Argument> {
+    let mut arguments = Vec::new();
+    let mut names = Vec::new();
+    let mut has_varargs = false;
+    let mut has_separator = false;
+    let mut has_kwargs = false;
+    let mut has_pos_only = false;
+
+    for item in items.iter() {
+        if has_kwargs {
+            errors.error(item, "arguments can not follow keyword arguments (**) in #[args]");
+            continue
+        }
+        let arg = match *item {
+            syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref ident)) => {
+                Argument::Arg(ident.as_ref().to_owned(), None)
+            },
+            syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref ident, ref lit)) => {
+                let value = match *lit {
+                    syn::Lit::Str(ref s, _) => s.clone(),
+                    _ => {
+                        errors.error(item, "#[args] value has to be a string");
+                        continue
+                    }
+                };
+                match value.as_ref() {
+                    "*" => {
+                        if has_varargs {
+                            errors.error(item, "var arguments (*) are already defined in #[args]");
+                            continue
+                        }
+                        has_varargs = true;
+                        Argument::VarArgs(ident.as_ref().to_owned())
+                    },
+                    "**" => {
+                        has_kwargs = true;
+                        Argument::KeywordArgs(ident.as_ref().to_owned())
+                    },
+                    _ => {
+                        if let Err(err) = syn::parse_expr(&value) {
+                            errors.error(item, &format!("can not parse default value ({})", err));
+                            continue
+                        }
+                        Argument::Arg(ident.as_ref().to_owned(), Some(value))
+                    },
+                }
+            },
+            syn::NestedMetaItem::Literal(syn::Lit::Str(ref s, _)) => {
+                match s.as_ref() {
+                    "*" => {
+                        if has_separator {
+                            errors.error(
+                                item, "keyword-only separator (*) is already defined in #[args]");
+                            continue
+                        }
+                        has_separator = true;
+                        Argument::VarArgsSeparator
+                    },
+                    "/" => {
+                        if has_pos_only {
+                            errors.error(
+                                item, "positional-only separator (/) is already defined in #[args]");
+                            continue
+                        }
+                        if has_varargs || has_separator {
+                            errors.error(item, "positional-only separator (/) has to be defined \
+                                                before var arguments (*) in #[args]");
+                            continue
+                        }
+                        has_pos_only = true;
+                        Argument::PosOnlySeparator
+                    },
+                    _ => {
+                        errors.error(item, "unsupported #[args] literal");
+                        continue
+                    }
+                }
+            },
+            _ => {
+                errors.error(item, "unsupported #[args] item");
+                continue
+            }
+        };
+
+        match arg {
+            Argument::Arg(ref name, _) | Argument::VarArgs(ref name) |
+            Argument::KeywordArgs(ref name) => {
+                if names.contains(name) {
+                    errors.error(item, "argument is already defined in #[args]");
+                    continue
+                }
+                names.push(name.clone());
+            },
+            _ => (),
+        }
+        arguments.push(arg);
+    }
+
+    arguments
+}
+
+/// Position of an argument relative to the separators of `#[args(...)]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    PosOnly,
+    Normal,
+    KwOnly,
+}
+
+/// Default value expression for argument
+pub fn default_value(arguments: &[Argument], name: &syn::Ident) -> Option<syn::Expr> {
+    for arg in arguments.iter() {
+        if let Argument::Arg(ref n, Some(ref value)) = *arg {
+            if n.as_str() == name.as_ref() {
+                // parse errors are reported by `parse_arguments`
+                return syn::parse_expr(value).ok()
+            }
+        }
+    }
+    None
+}
+
+pub fn is_varargs(arguments: &[Argument], name: &syn::Ident) -> bool {
+    arguments.iter().any(|arg| arg == &Argument::VarArgs(name.as_ref().to_owned()))
+}
+
+pub fn is_kwargs(arguments: &[Argument], name: &syn::Ident) -> bool {
+    arguments.iter().any(|arg| arg == &Argument::KeywordArgs(name.as_ref().to_owned()))
+}
+
+/// Arguments not mentioned in `#[args(...)]` are positional-or-keyword arguments.
+pub fn arg_kind(arguments: &[Argument], name: &syn::Ident) -> ArgKind {
+    let has_pos_only = arguments.iter().any(|arg| *arg == Argument::PosOnlySeparator);
+    let mut kind = if has_pos_only { ArgKind::PosOnly } else { ArgKind::Normal };
+
+    for arg in arguments.iter() {
+        match *arg {
+            Argument::PosOnlySeparator => kind = ArgKind::Normal,
+            Argument::VarArgsSeparator | Argument::VarArgs(_) => kind = ArgKind::KwOnly,
+            Argument::Arg(ref n, _) => if n.as_str() == name.as_ref() {
+                return kind
+            },
+            Argument::KeywordArgs(_) => (),
+        }
+    }
+    ArgKind::Normal
+}
+
@@ -0,0 +1,216 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+//! Definitions of protocol traits supported by `#[proto]`
+
+
+/// Protocol trait, i.e. `PyObjectProtocol`
+pub struct Proto {
+    /// Module of protocol trait and its marker traits
+    pub module: &'static str,
+    /// Trait which provides `py_methods()` for methods without type slots
+    pub py_methods_impl: &'static str,
+    pub methods: &'static [MethodProto],
+}
+
+/// Protocol method, i.e. `__getattr__`
+pub struct MethodProto {
+    pub name: &'static str,
+    /// Marker trait which carries argument and result types of the method
+    pub proto: &'static str,
+    pub args: &'static [Arg],
+    pub ret: Ret,
+    /// Method without type slot is added to the type as python method
+    pub py_method: PyMethod,
+}
+
+/// How protocol method is added to the type
+pub enum PyMethod {
+    /// Method fills type slot
+    Slot,
+    /// Python method, wrapper is generated from method signature
+    Signature,
+    /// Python method, generic wrapper is provided by protocol module, i.e. `complex_method`
+    Wrapper(&'static str),
+}
+
+/// Argument of protocol method, after `py: Python`
+pub enum Arg {
+    /// Argument of type `Self::Name`
+    Type(&'static str),
+    /// Argument of type `Option<Self::Name>`
+    Optional(&'static str),
+    /// Argument of fixed type, i.e. `key: isize` or `op: CompareOp`
+    Fixed,
+}
+
+/// Result type of protocol method
+pub enum Ret {
+    /// `PyResult` of fixed type, i.e. `PyResult<bool>`
+    Plain,
+    /// `PyResult<Self::Success>`, `Success` is converted to python object
+    Success,
+    /// `PyResult<Option<Self::Success>>`
+    OptionalSuccess,
+}
+
+
+macro_rules! method {
+    ($name:expr, $proto:expr, [$($arg:expr),*], $ret:expr) => {
+        MethodProto { name: $name, proto: $proto, args: &[$($arg),*],
+                      ret: $ret, py_method: PyMethod::Slot }
+    };
+    ($name:expr, $proto:expr, [$($arg:expr),*], $ret:expr, py_method) => {
+        MethodProto { name: $name, proto: $proto, args: &[$($arg),*],
+                      ret: $ret, py_method: PyMethod::Signature }
+    };
+    ($name:expr, $proto:expr, [$($arg:expr),*], $ret:expr, wrapper($wrapper:expr)) => {
+        MethodProto { name: $name, proto: $proto, args: &[$($arg),*],
+                      ret: $ret, py_method: PyMethod::Wrapper($wrapper) }
+    };
+}
+
+use self::Arg::{Type, Optional, Fixed};
+use self::Ret::{Plain, Success, OptionalSuccess};
+
+
+pub static OBJECT: Proto = Proto {
+    module: "pyo3::class::basic",
+    py_methods_impl: "",
+    methods: &[
+        method!("__getattr__", "PyObjectGetAttrProtocol", [Type("Name")], Success),
+        method!("__setattr__", "PyObjectSetAttrProtocol", [Type("Name"), Type("Value")], Plain),
+        method!("__delattr__", "PyObjectDelAttrProtocol", [Type("Name")], Plain),
+        method!("__str__", "PyObjectStrProtocol", [], Success),
+        method!("__repr__", "PyObjectReprProtocol", [], Success),
+        method!("__hash__", "PyObjectHashProtocol", [], Plain),
+        method!("__bool__", "PyObjectBoolProtocol", [], Plain),
+        method!("__richcmp__", "PyObjectRichcmpProtocol", [Type("Other"), Fixed], Success),
+    ],
+};
+
+pub static ASYNC: Proto = Proto {
+    module: "pyo3::class::async",
+    py_methods_impl: "",
+    methods: &[
+        method!("__await__", "PyAsyncAwaitProtocol", [], Success),
+        method!("__aiter__", "PyAsyncAiterProtocol", [], Success),
+        method!("__anext__", "PyAsyncAnextProtocol", [], Success),
+    ],
+};
+
+pub static CONTEXT: Proto = Proto {
+    module: "pyo3::class::context",
+    py_methods_impl: "PyContextProtocolImpl",
+    methods: &[
+        method!("__enter__", "PyContextEnterProtocol", [], Success, py_method),
+        method!("__exit__", "PyContextExitProtocol",
+                [Type("ExcType"), Type("ExcValue"), Type("Traceback")], Success, py_method),
+    ],
+};
+
+pub static DESCR: Proto = Proto {
+    module: "pyo3::class::descr",
+    py_methods_impl: "PyDescrProtocolImpl",
+    methods: &[
+        method!("__get__", "PyDescrGetProtocol", [Type("Inst"), Type("Owner")], Success),
+        method!("__set__", "PyDescrSetProtocol", [Type("Inst"), Type("Value")], Plain),
+        method!("__delete__", "PyDescrDeleteProtocol", [Type("Inst")], Plain),
+        method!("__set_name__", "PyDescrSetNameProtocol",
+                [Type("Owner"), Type("Name")], Plain, py_method),
+    ],
+};
+
+pub static ITER: Proto = Proto {
+    module: "pyo3::class::iter",
+    py_methods_impl: "",
+    methods: &[
+        method!("__iter__", "PyIterIterProtocol", [], Success),
+        method!("__next__", "PyIterNextProtocol", [], OptionalSuccess),
+    ],
+};
+
+pub static MAPPING: Proto = Proto {
+    module: "pyo3::class::mapping",
+    py_methods_impl: "",
+    methods: &[
+        method!("__len__", "PyMappingLenProtocol", [], Plain),
+        method!("__getitem__", "PyMappingGetItemProtocol", [Type("Key")], Success),
+        method!("__setitem__", "PyMappingSetItemProtocol",
+                [Type("Key"), Optional("Value")], Plain),
+    ],
+};
+
+pub static SEQUENCE: Proto = Proto {
+    module: "pyo3::class::sequence",
+    py_methods_impl: "",
+    methods: &[
+        method!("__len__", "PySequenceLenProtocol", [], Plain),
+        method!("__getitem__", "PySequenceGetItemProtocol", [Fixed], Success),
+        method!("__setitem__", "PySequenceSetItemProtocol", [Fixed, Optional("Value")], Plain),
+        method!("__contains__", "PySequenceContainsProtocol", [Type("Value")], Plain),
+        method!("__concat__", "PySequenceConcatProtocol", [Type("Other")], Success),
+        method!("__repeat__", "PySequenceRepeatProtocol", [Fixed], Success),
+        method!("__inplace_concat__", "PySequenceInplaceConcatProtocol", [Type("Other")], Plain),
+        method!("__inplace_repeat__", "PySequenceInplaceRepeatProtocol", [Fixed], Plain),
+    ],
+};
+
+pub static NUMBER: Proto = Proto {
+    module: "pyo3::class::number",
+    py_methods_impl: "PyNumberProtocolImpl",
+    methods: &[
+        method!("__add__", "PyNumberAddProtocol", [Type("Other")], Success),
+        method!("__sub__", "PyNumberSubProtocol", [Type("Other")], Success),
+        method!("__mul__", "PyNumberMulProtocol", [Type("Other")], Success),
+        method!("__matmul__", "PyNumberMatmulProtocol", [Type("Other")], Success),
+        method!("__truediv__", "PyNumberTruedivProtocol", [Type("Other")], Success),
+        method!("__floordiv__", "PyNumberFloordivProtocol", [Type("Other")], Success),
+        method!("__mod__", "PyNumberModProtocol", [Type("Other")], Success),
+        method!("__divmod__", "PyNumberDivmodProtocol", [Type("Other")], Success),
+        method!("__pow__", "PyNumberPowProtocol", [Type("Other"), Type("Modulo")], Success),
+        method!("__lshift__", "PyNumberLShiftProtocol", [Type("Other")], Success),
+        method!("__rshift__", "PyNumberRShiftProtocol", [Type("Other")], Success),
+        method!("__and__", "PyNumberAndProtocol", [Type("Other")], Success),
+        method!("__xor__", "PyNumberXorProtocol", [Type("Other")], Success),
+        method!("__or__", "PyNumberOrProtocol", [Type("Other")], Success),
+
+        method!("__radd__", "PyNumberRAddProtocol", [Type("Other")], Success),
+        method!("__rsub__", "PyNumberRSubProtocol", [Type("Other")], Success),
+        method!("__rmul__", "PyNumberRMulProtocol", [Type("Other")], Success),
+        method!("__rmatmul__", "PyNumberRMatmulProtocol", [Type("Other")], Success),
+        method!("__rtruediv__", "PyNumberRTruedivProtocol", [Type("Other")], Success),
+        method!("__rfloordiv__", "PyNumberRFloordivProtocol", [Type("Other")], Success),
+        method!("__rmod__", "PyNumberRModProtocol", [Type("Other")], Success),
+        method!("__rdivmod__", "PyNumberRDivmodProtocol", [Type("Other")], Success),
+        method!("__rpow__", "PyNumberRPowProtocol", [Type("Other")], Success),
+        method!("__rlshift__", "PyNumberRLShiftProtocol", [Type("Other")], Success),
+        method!("__rrshift__", "PyNumberRRShiftProtocol", [Type("Other")], Success),
+        method!("__rand__", "PyNumberRAndProtocol", [Type("Other")], Success),
+        method!("__rxor__", "PyNumberRXorProtocol", [Type("Other")], Success),
+        method!("__ror__", "PyNumberROrProtocol", [Type("Other")], Success),
+
+        method!("__iadd__", "PyNumberIAddProtocol", [Type("Other")], Plain),
+        method!("__isub__", "PyNumberISubProtocol", [Type("Other")], Plain),
+        method!("__imul__", "PyNumberIMulProtocol", [Type("Other")], Plain),
+        method!("__imatmul__", "PyNumberIMatmulProtocol", [Type("Other")], Plain),
+        method!("__itruediv__", "PyNumberITruedivProtocol", [Type("Other")], Plain),
+        method!("__ifloordiv__", "PyNumberIFloordivProtocol", [Type("Other")], Plain),
+        method!("__imod__", "PyNumberIModProtocol", [Type("Other")], Plain),
+        method!("__ipow__", "PyNumberIPowProtocol", [Type("Other"), Type("Modulo")], Plain),
+        method!("__ilshift__", "PyNumberILShiftProtocol", [Type("Other")], Plain),
+        method!("__irshift__", "PyNumberIRShiftProtocol", [Type("Other")], Plain),
+        method!("__iand__", "PyNumberIAndProtocol", [Type("Other")], Plain),
+        method!("__ixor__", "PyNumberIXorProtocol", [Type("Other")], Plain),
+        method!("__ior__", "PyNumberIOrProtocol", [Type("Other")], Plain),
+
+        method!("__neg__", "PyNumberNegProtocol", [], Success),
+        method!("__pos__", "PyNumberPosProtocol", [], Success),
+        method!("__abs__", "PyNumberAbsProtocol", [], Success),
+        method!("__invert__", "PyNumberInvertProtocol", [], Success),
+        method!("__complex__", "PyNumberComplexProtocol", [], Plain, wrapper("complex_method")),
+        method!("__int__", "PyNumberIntProtocol", [], Success),
+        method!("__float__", "PyNumberFloatProtocol", [], Success),
+        method!("__round__", "PyNumberRoundProtocol", [Fixed], Success, py_method),
+        method!("__index__", "PyNumberIndexProtocol", [], Success),
+    ],
+};
@@ -7,17 +7,25 @@ extern crate proc_macro;
 extern crate syn;
 #[macro_use] extern crate quote;
 
+use std::iter;
 use std::str::FromStr;
 use proc_macro::TokenStream;
 
 use quote::{Tokens, ToTokens};
 
 mod py_class;
+mod py_enum;
+mod py_from_object;
+mod py_to_object;
 mod py_impl;
 mod py_proto;
 mod py_method;
+mod args;
+mod defs;
 mod utils;
 
+use utils::Errors;
+
 
 #[proc_macro_attribute]
 pub fn proto(_: TokenStream, input: TokenStream) -> TokenStream {
@@ -25,54 +33,137 @@ pub fn proto(_: TokenStream, input: TokenStream) -> TokenStream {
     let source = input.to_string();
 
     // Parse the string representation into a syntax tree
-    let mut ast = syn::parse_item(&source).unwrap();
+    let mut ast = match syn::parse_item(&source) {
+        Ok(ast) => ast,
+        Err(err) => return parse_error(source, "#[proto]", err),
+    };
 
     // Build the output
-    let expanded = py_proto::build_py_proto(&mut ast);
+    let mut errors = Errors::new();
+    let expanded = py_proto::build_py_proto(&mut ast, &mut errors);
 
     // Return the generated impl as a TokenStream
-    let mut tokens = Tokens::new();
-    ast.to_tokens(&mut tokens);
-    let s = String::from(tokens.as_str()) + expanded.as_str();
-
-    TokenStream::from_str(s.as_str()).unwrap()
+    expand(&ast, expanded, errors)
 }
 
 
 #[proc_macro_attribute]
-pub fn class(_: TokenStream, input: TokenStream) -> TokenStream {
+pub fn class(attr: TokenStream, input: TokenStream) -> TokenStream {
     // Construct a string representation of the type definition
     let source = input.to_string();
 
     // Parse the string representation into a syntax tree
-    let mut ast = syn::parse_derive_input(&source).unwrap();
+    let mut ast = match syn::parse_derive_input(&source) {
+        Ok(ast) => ast,
+        Err(err) => return parse_error(source, "#[class]", err),
+    };
 
     // Build the output
-    let expanded = py_class::build_py_class(&mut ast);
+    let mut errors = Errors::new();
+    let expanded = py_class::build_py_class(&mut ast, attr.to_string(), &mut errors);
 
     // Return the generated impl as a TokenStream
-    let mut tokens = Tokens::new();
-    ast.to_tokens(&mut tokens);
-    let s = String::from(tokens.as_str()) + expanded.as_str();
-
-    TokenStream::from_str(s.as_str()).unwrap()
+    expand(&ast, expanded, errors)
 }
 
 #[proc_macro_attribute]
-pub fn methods(_: TokenStream, input: TokenStream) -> TokenStream {
+pub fn methods(attr: TokenStream, input: TokenStream) -> TokenStream {
+    // Construct a string representation of the type definition
+    let source = input.to_string();
+
+    // Parse the string representation into a syntax tree
+    let mut ast = match syn::parse_item(&source) {
+        Ok(ast) => ast,
+        Err(err) => return parse_error(source, "#[methods]", err),
+    };
+
+    // Build the output
+    let mut errors = Errors::new();
+    let expanded = py_impl::build_py_methods(&mut ast, attr.to_string(), &mut errors);
+
+    // Return the generated impl as a TokenStream
+    expand(&ast, expanded, errors)
+}
+
+#[proc_macro_derive(FromPyObject, attributes(extract))]
+pub fn derive_from_py_object(input: TokenStream) -> TokenStream {
     // Construct a string representation of the type definition
     let source = input.to_string();
 
     // Parse the string representation into a syntax tree
-    let mut ast = syn::parse_item(&source).unwrap();
+    let ast = match syn::parse_derive_input(&source) {
+        Ok(ast) => ast,
+        Err(err) => return parse_error(String::new(), "#[derive(FromPyObject)]", err),
+    };
 
     // Build the output
-    let expanded = py_impl::build_py_methods(&mut ast);
+    let mut errors = Errors::new();
+    let expanded = py_from_object::build_from_py_object(&ast, &mut errors);
 
     // Return the generated impl as a TokenStream
+    expand_derive(expanded, errors)
+}
+
+#[proc_macro_derive(ToPyObject, attributes(to_py_object))]
+pub fn derive_to_py_object(input: TokenStream) -> TokenStream {
+    // Construct a string representation of the type definition
+    let source = input.to_string();
+
+    // Parse the string representation into a syntax tree
+    let ast = match syn::parse_derive_input(&source) {
+        Ok(ast) => ast,
+        Err(err) => return parse_error(String::new(), "#[derive(ToPyObject)]", err),
+    };
+
+    // Build the output
+    let mut errors = Errors::new();
+    let expanded = py_to_object::build_to_py_object(&ast, &mut errors);
+
+    // Return the generated impl as a TokenStream
+    expand_derive(expanded, errors)
+}
+
+/// Emit item followed by generated code, or by compile errors
+/// if any were found. Generated code is dropped in the error case
+/// to avoid errors caused by incomplete expansion.
+fn expand<T: ToTokens>(ast: &T, expanded: Tokens, errors: Errors) -> TokenStream {
     let mut tokens = Tokens::new();
     ast.to_tokens(&mut tokens);
-    let s = String::from(tokens.as_str()) + expanded.as_str();
+    if errors.is_empty() {
+        expanded.to_tokens(&mut tokens);
+    } else {
+        errors.to_tokens(&mut tokens);
+    }
+
+    into_token_stream(tokens)
+}
+
+fn parse_error(source: String, attr: &str, err: String) -> TokenStream {
+    let mut errors = Errors::new();
+    errors.message(format!("{} can not parse item: {}", attr, err));
+
+    let mut tokens = Tokens::new();
+    errors.to_tokens(&mut tokens);
+    TokenStream::from_str(&(source + tokens.as_str()))
+        .unwrap_or_else(|_| into_token_stream(tokens))
+}
+
+/// Emit generated code or compile errors, derive macros do not re-emit the item.
+fn expand_derive(expanded: Tokens, errors: Errors) -> TokenStream {
+    let mut tokens = Tokens::new();
+    if errors.is_empty() {
+        expanded.to_tokens(&mut tokens);
+    } else {
+        errors.to_tokens(&mut tokens);
+    }
+
+    into_token_stream(tokens)
+}
 
-    TokenStream::from_str(s.as_str()).unwrap()
+/// Code that can not be tokenized is reported as compile error instead of panicking.
+fn into_token_stream(tokens: Tokens) -> TokenStream {
+    TokenStream::from_str(tokens.as_str())
+        .or_else(|_| TokenStream::from_str(
+            "compile_error!(\"pyo3: generated code can not be tokenized\");"))
+        .unwrap_or_else(|_| iter::empty::<TokenStream>().collect())
 }
@@ -1,17 +1,35 @@
 // Copyright (c) 2017-present PyO3 Project and Contributors
 
+use std::collections::HashMap;
+
 use syn;
 use quote::{Tokens, ToTokens};
 
+use py_enum;
+use py_method;
+use utils::{self, Errors};
+
 
-pub fn build_py_class(ast: &mut syn::DeriveInput) -> Tokens {
-    let base = syn::Ident::from("pyo3::PyObject");
+pub fn build_py_class(ast: &mut syn::DeriveInput, attr: String, errors: &mut Errors) -> Tokens {
+    let params = parse_attribute(attr, errors);
+    let base = match params.get("base") {
+        Some(base) => Some(base.clone()),
+        None => None,
+    };
 
+    if !ast.generics.ty_params.is_empty() || !ast.generics.lifetimes.is_empty() {
+        errors.error(&ast.generics, &format!("#[class] `{}` can not be generic", ast.ident));
+        return Tokens::new()
+    }
+
+    let doc = utils::get_doc(&ast.attrs);
     let mut tokens = Tokens::new();
 
     match ast.body {
         syn::Body::Struct(syn::VariantData::Struct(ref mut data)) => {
-            impl_storage(&ast.ident, &base, data).to_tokens(&mut tokens);
+            let props = parse_descriptors(data, errors);
+            impl_storage(&ast.ident, base.as_ref(), &params, &doc, data).to_tokens(&mut tokens);
+            impl_descriptors(&ast.ident, &props).to_tokens(&mut tokens);
 
             let tt = quote! {
                 struct Test {
@@ -24,11 +42,15 @@ pub fn build_py_class(ast: &mut syn::DeriveInput) -> Tokens {
                     data.clear();
                     data.extend(fields);
                 }
-                _ => panic!("something is worng"),
+                _ => unreachable!(),
             }
         },
-        _ =>
-            panic!("#[class] can only be used with notmal structs"),
+        syn::Body::Enum(ref variants) =>
+            return py_enum::build_py_enum(&ast.ident, variants, &doc, &params, errors),
+        _ => {
+            errors.error(&ast.ident, "#[class] can only be used with normal structs and enums");
+            return tokens
+        },
     }
 
     impl_to_py_object(&ast.ident).to_tokens(&mut tokens);
@@ -51,12 +73,166 @@ pub fn build_py_class(ast: &mut syn::DeriveInput) -> Tokens {
     }
 }
 
-fn impl_storage(cls: &syn::Ident, base: &syn::Ident, fields: &Vec<syn::Field>) -> Tokens {
-    let names: Vec<syn::Ident> = fields.iter()
+fn impl_storage(cls: &syn::Ident, base: Option<&syn::Ident>,
+                params: &HashMap<&'static str, syn::Ident>, doc: &str,
+                fields: &Vec<syn::Field>) -> Tokens {
+    let names: &Vec<syn::Ident> = &fields.iter()
         .map(|f| f.ident.as_ref().unwrap().clone()).collect();
-    let values: Vec<syn::Ident> = fields.iter()
+    let values: &Vec<syn::Ident> = &fields.iter()
         .map(|f| f.ident.as_ref().unwrap().clone()).collect();
-    //let types: Vec<syn::Ty> = fields.iter().map(|f| f.ty.clone()).collect();
+    // constructor arguments, field attributes and visibility do not apply to them
+    let args: &Vec<Tokens> = &fields.iter().map(|f| {
+        let name = f.ident.as_ref().unwrap();
+        let ty = &f.ty;
+        quote! { #name: #ty }
+    }).collect();
+
+    // classes with explicit base type receive base initializer as first argument
+    // and pass base type to type initialization
+    let (base, base_arg, base_param, base_value, base_type) = match base {
+        Some(base) => (
+            base.clone(),
+            quote! { base: <#base as BaseObject>::Type, },
+            quote! { base, },
+            quote! { base },
+            quote! { Some(&pyo3::class::typeob::base_type_object::<#base>(py)) }),
+        None => (
+            syn::Ident::from("pyo3::PyObject"),
+            quote! {},
+            quote! {},
+            quote! { () },
+            quote! { None }),
+    };
+
+    // python and rust subclasses are allowed only for `#[class(subclass)]`
+    let (flags, base_type_impl) = if params.contains_key("subclass") {
+        (quote! { ffi::Py_TPFLAGS_BASETYPE },
+         quote! { impl pyo3::class::PyBaseType for #cls {} })
+    } else {
+        (quote! { 0 }, Tokens::new())
+    };
+
+    // `__dict__` and weak reference list pointers follow instance data
+    let mut extra_slots = 0usize;
+    let mut offsets = Tokens::new();
+    let mut clear_weakrefs = Tokens::new();
+    let mut clear_dict = Tokens::new();
+    let mut traverse_dict = Tokens::new();
+    if params.contains_key("dict") {
+        let index = extra_slots;
+        extra_slots += 1;
+        quote! {
+            #[inline]
+            fn dict_offset() -> Option<isize> {
+                Some(slot_offset(#index) as isize)
+            }
+        }.to_tokens(&mut offsets);
+        clear_dict = quote! {
+            ffi::Py_CLEAR(&mut *((obj as *mut u8).offset(slot_offset(#index) as isize)
+                                 as *mut *mut ffi::PyObject));
+        };
+        traverse_dict = quote! {
+            visit.call_ptr(*((obj as *mut u8).offset(slot_offset(#index) as isize)
+                             as *mut *mut ffi::PyObject))?;
+        };
+    }
+    if params.contains_key("weakref") {
+        let index = extra_slots;
+        extra_slots += 1;
+        quote! {
+            #[inline]
+            fn weakref_offset() -> Option<isize> {
+                Some(slot_offset(#index) as isize)
+            }
+        }.to_tokens(&mut offsets);
+        clear_weakrefs = quote! {
+            if !(*((obj as *mut u8).offset(slot_offset(#index) as isize)
+                   as *mut *mut ffi::PyObject)).is_null() {
+                ffi::PyObject_ClearWeakRefs(obj);
+            }
+        };
+    }
+
+    // `#[class(freelist = N)]` recycles memory of instances
+    let (base_alloc, base_dealloc, freelist) = match params.get("freelist") {
+        Some(capacity) => (
+            quote! { pyo3::class::freelist::alloc::<#cls>(py, ty, base) },
+            quote! { pyo3::class::freelist::dealloc::<#cls>(py, obj) },
+            quote! {
+                impl pyo3::class::freelist::PyObjectWithFreeList for #cls {
+                    #[inline]
+                    fn get_free_list(_py: Python)
+                                     -> &'static mut pyo3::class::freelist::FreeList<*mut ffi::PyObject>
+                    {
+                        static mut FREELIST: *mut pyo3::class::freelist::FreeList<*mut ffi::PyObject> =
+                            0 as *mut pyo3::class::freelist::FreeList<*mut ffi::PyObject>;
+                        unsafe {
+                            if FREELIST.is_null() {
+                                FREELIST = Box::into_raw(Box::new(
+                                    pyo3::class::freelist::FreeList::with_capacity(#capacity)));
+                            }
+                            &mut *FREELIST
+                        }
+                    }
+                }
+            }),
+        None => (
+            quote! { <#base as BaseObject>::alloc(py, ty, base) },
+            quote! { <#base as BaseObject>::dealloc(py, obj) },
+            quote! {}),
+    };
+
+    // `#[class(gc)]` traverses and clears every field holding python references
+    let gc = if params.contains_key("gc") {
+        quote! {
+            impl pyo3::class::gc::PyGCProtocol for #cls {
+                fn __traverse__(&self, py: Python, visit: pyo3::class::gc::PyVisit)
+                                -> Result<(), pyo3::class::gc::PyTraverseError> {
+                    // instance data is not visited while it is mutably borrowed,
+                    // buffer exports do not move python references
+                    let obj = self._unsafe_inner.as_ptr();
+                    unsafe { #traverse_dict }
+                    let cell = unsafe { cell(obj) };
+                    if !cell.is_borrowed_mut() {
+                        let data = unsafe { &*cell.as_ptr() };
+                        #(pyo3::class::gc::PyTraverseField::traverse_field(&data.#names, visit)?;)*
+                    }
+                    Ok(())
+                }
+
+                fn __clear__(&self, py: Python) {
+                    let obj = self._unsafe_inner.as_ptr();
+                    unsafe { #clear_dict }
+                    let cell = unsafe { cell(obj) };
+                    if let Ok(mut data) = cell.try_borrow_mut(py) {
+                        #(pyo3::class::gc::PyTraverseField::clear_field(&mut data.#values, py);)*
+                    }
+                }
+            }
+
+            impl pyo3::class::gc::PyGCProtocolImpl for #cls {
+                fn methods() -> &'static [&'static str] {
+                    static METHODS: &'static [&'static str] = &["__traverse__", "__clear__"];
+                    METHODS
+                }
+            }
+        }
+    } else {
+        Tokens::new()
+    };
+
+    // `#[class(pickle)]` reduces instances with `PyPickleProtocol` implementation
+    let pickle = if params.contains_key("pickle") {
+        quote! {
+            impl pyo3::class::pickle::PyPickleProtocolImpl for #cls {
+                fn methods() -> Vec<pyo3::class::PyMethodDefType> {
+                    pyo3::class::pickle::pickle_methods::<#cls>()
+                }
+            }
+        }
+    } else {
+        Tokens::new()
+    };
 
     let mut accessors = Tokens::new();
     for field in fields.iter() {
@@ -64,21 +240,19 @@ fn impl_storage(cls: &syn::Ident, base: &syn::Ident, fields: &Vec<syn::Field>) -
         let name_mut = syn::Ident::from(format!("{}_mut", name.as_ref()));
         let ty = &field.ty;
 
+        // accessors borrow instance data, so handles of the same object
+        // can not hold aliasing references
         let accessor = quote!{
             impl #cls {
-                fn #name<'a>(&'a self, py: Python<'a>) -> &'a #ty {
-                    unsafe {
-                        let ptr = (self._unsafe_inner.as_ptr() as *const u8)
-                            .offset(base_offset() as isize) as *const Storage;
-                        &(*ptr).#name
-                    }
+                fn #name<'a>(&'a self, py: Python<'a>)
+                             -> PyResult<pyo3::class::cell::PyRef<'a, #ty>> {
+                    let data = unsafe { cell(self._unsafe_inner.as_ptr()).try_borrow(py)? };
+                    Ok(pyo3::class::cell::PyRef::map(data, |data| &data.#name))
                 }
-                fn #name_mut<'a>(&'a self, py: Python<'a>) -> &'a mut #ty {
-                    unsafe {
-                        let ptr = (self._unsafe_inner.as_ptr() as *const u8)
-                            .offset(base_offset() as isize) as *mut Storage;
-                        &mut (*ptr).#name
-                    }
+                fn #name_mut<'a>(&'a self, py: Python<'a>)
+                                 -> PyResult<pyo3::class::cell::PyRefMut<'a, #ty>> {
+                    let data = unsafe { cell(self._unsafe_inner.as_ptr()).try_borrow_mut(py)? };
+                    Ok(pyo3::class::cell::PyRefMut::map(data, |data| &mut data.#name))
                 }
             }
         };
@@ -91,14 +265,53 @@ fn impl_storage(cls: &syn::Ident, base: &syn::Ident, fields: &Vec<syn::Field>) -
         }
 
         impl #cls {
-            fn create_instance(py: Python, #(#fields),*) -> PyResult<#cls> {
+            fn create_instance(py: Python, #base_arg #(#args),*) -> PyResult<#cls> {
+                #cls::create_instance_of(py, &py.get_type::<#cls>(), #base_param #(#names),*)
+            }
+
+            /// Creates instance of `cls`, which is the class or its python subclass,
+            /// i.e. in `#[new]` method of `#[class(subclass)]`.
+            fn create_instance_of(py: Python, cls: &PyType, #base_arg #(#args),*)
+                                  -> PyResult<#cls> {
                 let obj = try!(unsafe {
                     <#cls as BaseObject>::alloc(
-                        py, &py.get_type::<#cls>(),
-                        Storage { #(#names: #values),*})});
+                        py, cls, (#base_value, Storage { #(#names: #values),*}))});
 
                 return Ok(#cls { _unsafe_inner: obj });
             }
+
+            /// Builds instance data, can be used as base initializer of a subclass
+            fn init_storage(#base_arg #(#args),*) -> <#cls as BaseObject>::Type {
+                (#base_value, Storage { #(#names: #values),*})
+            }
+
+            /// Immutably borrows instance data,
+            /// raises `RuntimeError` if it is mutably borrowed.
+            fn borrow<'a>(&'a self, py: Python<'a>)
+                          -> PyResult<pyo3::class::cell::PyRef<'a, Storage>> {
+                unsafe { cell(self._unsafe_inner.as_ptr()).try_borrow(py) }
+            }
+
+            /// Mutably borrows instance data, raises `RuntimeError` if it is borrowed.
+            fn borrow_mut<'a>(&'a self, py: Python<'a>)
+                              -> PyResult<pyo3::class::cell::PyRefMut<'a, Storage>> {
+                unsafe { cell(self._unsafe_inner.as_ptr()).try_borrow_mut(py) }
+            }
+        }
+
+        impl pyo3::class::cell::PyCellProtocolImpl for #cls {
+            unsafe fn borrow_data<'a>(py: Python, obj: *mut ffi::PyObject, mutable: bool)
+                                      -> PyResult<Option<pyo3::class::cell::BorrowGuard<'a>>> {
+                cell(obj).borrow_guard(py, mutable).map(Some)
+            }
+
+            unsafe fn export_data(obj: *mut ffi::PyObject, export: bool, writable: bool) {
+                if export {
+                    cell(obj).add_export(writable)
+                } else {
+                    cell(obj).release_export(writable)
+                }
+            }
         }
 
         #accessors
@@ -114,70 +327,380 @@ fn impl_storage(cls: &syn::Ident, base: &syn::Ident, fields: &Vec<syn::Field>) -
 
             fn add_to_module(py: Python, module: &PyModule) -> PyResult<()> {
                 let ty = unsafe { #cls::initialized(py, module.name(py).ok()) };
-                module.add(py, stringify!(#cls), ty)
-            }
-
-            #[inline]
-            unsafe fn type_obj() -> &'static mut ffi::PyTypeObject {
-                static mut TYPE_OBJECT: ffi::PyTypeObject = ffi::PyTypeObject_INIT;
-                &mut TYPE_OBJECT
+                pyo3::class::typeob::add_type_to_module(py, module, stringify!(#cls), ty)
             }
 
             unsafe fn initialized(py: Python, module_name: Option<&str>) -> PyType {
-                let mut ty = #cls::type_obj();
+                // heap type object, created on first use and never released
+                static mut TYPE_OBJECT: *mut ffi::PyTypeObject = 0 as *mut ffi::PyTypeObject;
 
-                if (ty.tp_flags & ffi::Py_TPFLAGS_READY) != 0 {
-                    PyType::from_type_ptr(py, ty)
-                } else {
+                if TYPE_OBJECT.is_null() {
                     // automatically initialize the class on-demand
-                    pyo3::class::typeob::initialize_type::<#cls>(
-                        py, module_name, ty).expect(
+                    let ty = pyo3::class::typeob::initialize_type::<#cls>(
+                        py, module_name, stringify!(#cls), #base_type, #doc, #flags).expect(
                         concat!("An error occurred while initializing class ",
                                 stringify!(#cls)));
-                    PyType::from_type_ptr(py, ty)
+                    TYPE_OBJECT = ty.into_object().steal_ptr() as *mut ffi::PyTypeObject;
                 }
+                PyType::from_type_ptr(py, TYPE_OBJECT)
             }
         }
 
         #[inline]
         fn base_offset() -> usize {
-            let align = std::mem::align_of::<Storage>();
+            let align = std::mem::align_of::<pyo3::class::cell::PyCell<Storage>>();
             let bs = <#base as BaseObject>::size();
 
             // round base_size up to next multiple of align
             (bs + align - 1) / align * align
         }
 
+        /// Instance data of the object, `obj` has to be instance of the class or its subclass
+        #[inline]
+        unsafe fn cell<'a>(obj: *mut ffi::PyObject) -> &'a pyo3::class::cell::PyCell<Storage> {
+            &*((obj as *const u8).offset(base_offset() as isize)
+               as *const pyo3::class::cell::PyCell<Storage>)
+        }
+
+        /// Offset of object pointer slot `index` after instance data
+        #[inline]
+        #[allow(dead_code)]
+        fn slot_offset(index: usize) -> usize {
+            let align = std::mem::align_of::<*mut ffi::PyObject>();
+            let offset = base_offset() + std::mem::size_of::<pyo3::class::cell::PyCell<Storage>>();
+
+            (offset + align - 1) / align * align + index * std::mem::size_of::<*mut ffi::PyObject>()
+        }
+
+        #freelist
+
+        #gc
+
+        #pickle
+
+        #base_type_impl
+
         impl BaseObject for #cls {
-            type Type = Storage;
+            type Type = (<#base as BaseObject>::Type, Storage);
 
             #[inline]
             fn size() -> usize {
-                base_offset() + std::mem::size_of::<Self::Type>()
+                slot_offset(#extra_slots)
             }
 
+            #offsets
+
             unsafe fn alloc(py: Python, ty: &PyType, value: Self::Type) -> PyResult<PyObject>
             {
-                let obj = try!(<#base as BaseObject>::alloc(py, ty, ()));
+                let (base, value) = value;
+                let obj = try!(#base_alloc);
 
                 let ptr = (obj.as_ptr() as *mut u8)
-                    .offset(base_offset() as isize) as *mut Self::Type;
-                std::ptr::write(ptr, value);
+                    .offset(base_offset() as isize) as *mut pyo3::class::cell::PyCell<Storage>;
+                std::ptr::write(ptr, pyo3::class::cell::PyCell::new(value));
 
                 Ok(obj)
             }
 
             unsafe fn dealloc(py: Python, obj: *mut ffi::PyObject) {
-                let ptr = (obj as *mut u8).offset(base_offset() as isize) as *mut Self::Type;
+                // collector must not visit the object while its data is dropped
+                if ffi::PyType_IS_GC(ffi::Py_TYPE(obj)) != 0 {
+                    ffi::PyObject_GC_UnTrack(obj as *mut pyo3::c_void);
+                }
+                #clear_weakrefs
+
+                let ptr = (obj as *mut u8)
+                    .offset(base_offset() as isize) as *mut pyo3::class::cell::PyCell<Storage>;
                 std::ptr::drop_in_place(ptr);
 
-                <#base as BaseObject>::dealloc(py, obj)
+                #clear_dict
+
+                #base_dealloc
             }
         }
     }
 }
 
-fn impl_to_py_object(cls: &syn::Ident) -> Tokens {
+/// Python property generated for struct field by `#[prop(...)]` attribute
+struct FieldProp {
+    name: syn::Ident,
+    ty: syn::Ty,
+    py_name: String,
+    doc: String,
+    get: bool,
+    set: bool,
+}
+
+/// Parse and remove `#[prop(get, set, name = "...")]` field attributes
+fn parse_descriptors(fields: &mut Vec<syn::Field>, errors: &mut Errors) -> Vec<FieldProp> {
+    let mut props: Vec<FieldProp> = Vec::new();
+
+    for field in fields.iter_mut() {
+        let mut new_attrs = Vec::new();
+        let mut prop: Option<FieldProp> = None;
+
+        for attr in field.attrs.iter() {
+            match attr.value {
+                syn::MetaItem::List(ref name, ref items) if name.as_ref() == "prop" => {
+                    if prop.is_some() {
+                        errors.error(attr, "#[prop] attribute can not be used multiple times");
+                        continue
+                    }
+                    let ident = field.ident.as_ref().unwrap();
+                    let mut desc = FieldProp {
+                        name: ident.clone(),
+                        ty: field.ty.clone(),
+                        py_name: ident.as_ref().to_owned(),
+                        doc: utils::get_doc(&field.attrs),
+                        get: false,
+                        set: false,
+                    };
+                    for item in items.iter() {
+                        match *item {
+                            syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w))
+                                if w.as_ref() == "get" => desc.get = true,
+                            syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w))
+                                if w.as_ref() == "set" => desc.set = true,
+                            syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(
+                                ref n, syn::Lit::Str(ref value, _))) if n.as_ref() == "name" =>
+                                desc.py_name = value.clone(),
+                            _ => errors.error(item, "unsupported #[prop] parameter"),
+                        }
+                    }
+                    if !desc.get && !desc.set {
+                        errors.error(attr, "#[prop] requires `get` or `set` parameter");
+                    }
+                    prop = Some(desc);
+                },
+                syn::MetaItem::Word(ref name) if name.as_ref() == "prop" => {
+                    errors.error(attr, "#[prop] requires `get` or `set` parameter");
+                },
+                _ => new_attrs.push(attr.clone()),
+            }
+        }
+        field.attrs = new_attrs;
+
+        if let Some(prop) = prop {
+            if props.iter().any(|p| p.py_name == prop.py_name &&
+                                ((p.get && prop.get) || (p.set && prop.set))) {
+                errors.error(&prop.name, &format!(
+                    "property `{}` is defined multiple times", prop.py_name));
+            } else {
+                props.push(prop);
+            }
+        }
+    }
+
+    props
+}
+
+fn impl_descriptors(cls: &syn::Ident, props: &Vec<FieldProp>) -> Tokens {
+    if props.is_empty() {
+        return Tokens::new()
+    }
+
+    let mut defs = Vec::new();
+    let mut names = Vec::new();
+    for prop in props.iter() {
+        let name = &prop.name;
+        let ty = &prop.ty;
+        let py_name = &prop.py_name;
+        let doc = &prop.doc;
+
+        if prop.get {
+            names.push(("getter", py_name.clone()));
+            defs.push(quote! {
+                pyo3::class::PyMethodDefType::Getter({
+                    unsafe extern "C" fn wrap(slf: *mut ffi::PyObject,
+                                              _: *mut pyo3::c_void) -> *mut ffi::PyObject
+                    {
+                        const LOCATION: &'static str = concat!(
+                            stringify!(#cls), ".getter_", stringify!(#name), "()");
+                        pyo3::callback::handle_callback(
+                            LOCATION, pyo3::callback::PyObjectCallbackConverter, |py|
+                            {
+                                let data = cell(slf).try_borrow(py)?;
+                                Ok(pyo3::ToPyObject::to_py_object(&data.#name, py))
+                            })
+                    }
+
+                    pyo3::class::PyGetterDef {
+                        name: #py_name,
+                        meth: wrap,
+                        doc: #doc,
+                    }
+                })
+            });
+        }
+        if prop.set {
+            names.push(("setter", py_name.clone()));
+            defs.push(quote! {
+                pyo3::class::PyMethodDefType::Setter({
+                    unsafe extern "C" fn wrap(slf: *mut ffi::PyObject,
+                                              value: *mut ffi::PyObject,
+                                              _: *mut pyo3::c_void) -> pyo3::c_int
+                    {
+                        const LOCATION: &'static str = concat!(
+                            stringify!(#cls), ".setter_", stringify!(#name), "()");
+                        pyo3::callback::handle_callback(
+                            LOCATION, pyo3::callback::UnitCallbackConverter, |py|
+                            {
+                                if value.is_null() {
+                                    return Err(pyo3::PyErr::new::<pyo3::exc::TypeError, _>(
+                                        py, concat!("can't delete attribute ", #py_name)))
+                                }
+                                let value = PyObject::from_borrowed_ptr(py, value);
+                                let ret = match <#ty as pyo3::FromPyObject>::extract(py, &value) {
+                                    Ok(value) => cell(slf).try_borrow_mut(py).map(|mut data| {
+                                        data.#name = value;
+                                    }),
+                                    Err(e) => Err(e),
+                                };
+                                pyo3::PyDrop::release_ref(value, py);
+                                ret
+                            })
+                    }
+
+                    pyo3::class::PySetterDef {
+                        name: #py_name,
+                        meth: wrap,
+                        doc: #doc,
+                    }
+                })
+            });
+        }
+    }
+
+    let guards = py_method::impl_property_guards(cls, &names);
+
+    quote! {
+        impl pyo3::class::methods::PyPropMethodsProtocolImpl for #cls {
+            fn py_methods() -> &'static [pyo3::class::PyMethodDefType] {
+                static METHODS: &'static [pyo3::class::PyMethodDefType] = &[
+                    #(#defs),*
+                ];
+                METHODS
+            }
+        }
+
+        #guards
+    }
+}
+
+/// Parse `#[class(...)]` parameters, i.e. `#[class(base = pyo3::PyDict, subclass)]`
+fn parse_attribute(attr: String, errors: &mut Errors) -> HashMap<&'static str, syn::Ident> {
+    let mut params = HashMap::new();
+
+    // attribute arguments may or may not include surrounding parens
+    let attr = attr.trim();
+    let attr = if attr.is_empty() || attr.starts_with('(') {
+        attr.to_string()
+    } else {
+        format!("({})", attr)
+    };
+
+    let tts = match syn::parse_token_trees(&attr) {
+        Ok(tts) => tts,
+        Err(err) => {
+            errors.message(format!("can not parse #[class] attribute: {}: `{}`", err, attr));
+            return params
+        }
+    };
+    let mut elem = Vec::new();
+    let mut elems = Vec::new();
+
+    for tt in tts.iter() {
+        match tt {
+            &syn::TokenTree::Delimited(ref delimited) => {
+                for tt in delimited.tts.iter() {
+                    match tt {
+                        &syn::TokenTree::Token(syn::Token::Comma) => {
+                            let el = elem;
+                            elem = Vec::new();
+                            elems.push(el);
+                        },
+                        _ => elem.push(tt.clone())
+                    }
+                }
+            },
+            &syn::TokenTree::Token(_) => {
+                errors.error(tt, "wrong format of #[class] attribute");
+                return params
+            },
+        }
+    }
+    if !elem.is_empty() {
+        elems.push(elem);
+    }
+
+    for elem in elems {
+        let mut item = Tokens::new();
+        for tt in elem.iter() {
+            tt.to_tokens(&mut item);
+        }
+
+        let key = match elem.first() {
+            Some(&syn::TokenTree::Token(syn::Token::Ident(ref ident))) =>
+                ident.as_ref().to_string(),
+            _ => {
+                errors.error(&item, "unsupported #[class] parameter");
+                continue
+            }
+        };
+
+        // flag parameters, i.e. `#[class(subclass, weakref)]`
+        if elem.len() == 1 {
+            match key.as_ref() {
+                "subclass" => { let _ = params.insert("subclass", syn::Ident::from("subclass")); },
+                "weakref" => { let _ = params.insert("weakref", syn::Ident::from("weakref")); },
+                "dict" => { let _ = params.insert("dict", syn::Ident::from("dict")); },
+                "gc" => { let _ = params.insert("gc", syn::Ident::from("gc")); },
+                "pickle" => { let _ = params.insert("pickle", syn::Ident::from("pickle")); },
+                "base" | "freelist" => errors.error(&item, "#[class] parameter requires value"),
+                _ => errors.error(&item, "unsupported #[class] parameter"),
+            }
+            continue
+        }
+
+        if elem.len() < 3 || elem[1] != syn::TokenTree::Token(syn::Token::Eq) {
+            errors.error(&item, "#[class] parameter requires value");
+            continue
+        }
+
+        let mut value = Tokens::new();
+        for tt in elem[2..].iter() {
+            tt.to_tokens(&mut value);
+        }
+
+        match key.as_ref() {
+            "base" => {
+                let _ = params.insert("base", syn::Ident::from(value.as_str()));
+            },
+            "freelist" => {
+                match value.as_str().parse::<usize>() {
+                    Ok(_) => {
+                        let _ = params.insert("freelist", syn::Ident::from(value.as_str()));
+                    },
+                    Err(_) => errors.error(
+                        &item, "#[class] freelist parameter requires integer capacity"),
+                }
+            },
+            _ => errors.error(&item, "unsupported #[class] parameter"),
+        }
+    }
+
+    // free list recycles plain python objects only
+    if params.contains_key("freelist") && params.contains_key("base") {
+        errors.message("#[class(freelist)] can not be used together with `base`");
+    }
+    if params.contains_key("freelist") && params.contains_key("gc") {
+        errors.message("#[class(freelist)] can not be used together with `gc`");
+    }
+
+    params
+}
+
+pub fn impl_to_py_object(cls: &syn::Ident) -> Tokens {
     quote! {
         /// Identity conversion: allows using existing `PyObject` instances where
         /// `T: ToPyObject` is expected.
@@ -222,7 +745,7 @@ fn impl_from_py_object(cls: &syn::Ident) -> Tokens {
     }
 }
 
-fn impl_python_object(cls: &syn::Ident) -> Tokens {
+pub fn impl_python_object(cls: &syn::Ident) -> Tokens {
     quote! {
         impl pyo3::PythonObject for #cls {
             #[inline]
@@ -252,7 +775,7 @@ fn impl_python_object(cls: &syn::Ident) -> Tokens {
     }
 }
 
-fn impl_checked_downcast(cls: &syn::Ident) -> Tokens {
+pub fn impl_checked_downcast(cls: &syn::Ident) -> Tokens {
     quote! {
         impl pyo3::PythonObjectWithCheckedDowncast for #cls {
             #[inline]
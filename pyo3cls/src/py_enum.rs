@@ -0,0 +1,497 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+use std::collections::HashMap;
+
+use syn;
+use quote::{Tokens, ToTokens};
+
+use py_class;
+use utils::{self, Errors};
+
+
+/// `#[class]` on enum, python object keeps the enum value as instance data.
+///
+/// Enums with unit variants only get class attribute with instance of each variant,
+/// `__eq__`, `__hash__`, `__int__` and `__repr__`. Variants of enums with data
+/// become subclasses of the enum type with getters for variant fields,
+/// such enums have to implement `Clone` for conversion.
+pub fn build_py_enum(cls: &syn::Ident, variants: &Vec<syn::Variant>, doc: &str,
+                     params: &HashMap<&'static str, syn::Ident>, errors: &mut Errors) -> Tokens {
+    if !params.is_empty() {
+        errors.error(cls, "#[class] parameters are not supported for enums");
+    }
+    if variants.is_empty() {
+        errors.error(cls, "#[class] can not be used with empty enums");
+        return Tokens::new()
+    }
+
+    let obj = syn::Ident::from(format!("{}Object", cls));
+    let unit = variants.iter().all(|v| v.data == syn::VariantData::Unit);
+
+    let mut tokens = Tokens::new();
+    let mut attrs = Vec::new();
+    if unit {
+        for variant in variants.iter() {
+            let name = &variant.ident;
+            attrs.push(quote! {
+                (stringify!(#name), pyo3::ToPyObject::into_py_object(#cls::#name, py))
+            });
+        }
+        impl_unit_protocols(cls, &obj, variants).to_tokens(&mut tokens);
+    } else {
+        for variant in variants.iter() {
+            let name = &variant.ident;
+            let vobj = syn::Ident::from(format!("{}{}Object", cls, name));
+            attrs.push(quote! {
+                (stringify!(#name),
+                 <#vobj as pyo3::class::PyTypeObject>::initialized(py, module_name).into_object())
+            });
+            impl_variant_type(cls, &obj, variant).to_tokens(&mut tokens);
+        }
+        impl_data_repr(cls, &obj, variants).to_tokens(&mut tokens);
+    }
+    impl_enum_object(cls, &obj, doc, !unit, &attrs).to_tokens(&mut tokens);
+    impl_conversions(cls, &obj, variants, unit).to_tokens(&mut tokens);
+
+    let dummy_const = syn::Ident::new(format!("_IMPL_PYO3_CLS_{}", cls));
+    quote! {
+        #[feature(specialization)]
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications,
+                dead_code, unreachable_patterns)]
+        const #dummy_const: () = {
+            extern crate pyo3;
+            use std;
+            use pyo3::class::BaseObject;
+            use pyo3::{ffi, Python, PyObject, PyType, PyResult, PyModule};
+
+            #tokens
+        };
+    }
+}
+
+/// Python type of the enum, its instance data is the enum value
+fn impl_enum_object(cls: &syn::Ident, obj: &syn::Ident, doc: &str,
+                    subclass: bool, attrs: &Vec<Tokens>) -> Tokens {
+    // variant types of enum with data are subclasses of the enum type
+    let flags = if subclass { quote! { ffi::Py_TPFLAGS_BASETYPE } } else { quote! { 0 } };
+
+    let mut tokens = Tokens::new();
+    impl_handle(obj).to_tokens(&mut tokens);
+
+    quote! {
+        #tokens
+
+        impl #cls {
+            /// Python type object of the enum
+            fn type_object(py: Python) -> PyType {
+                py.get_type::<#obj>()
+            }
+        }
+
+        impl pyo3::class::PyTypeObject for #obj {
+
+            fn add_to_module(py: Python, module: &PyModule) -> PyResult<()> {
+                let ty = unsafe { #obj::initialized(py, module.name(py).ok()) };
+                pyo3::class::typeob::add_type_to_module(py, module, stringify!(#cls), ty)
+            }
+
+            unsafe fn initialized(py: Python, module_name: Option<&str>) -> PyType {
+                // heap type object, created on first use and never released
+                static mut TYPE_OBJECT: *mut ffi::PyTypeObject = 0 as *mut ffi::PyTypeObject;
+
+                if TYPE_OBJECT.is_null() {
+                    let ty = pyo3::class::typeob::initialize_type::<#obj>(
+                        py, module_name, stringify!(#cls), None, #doc, #flags).expect(
+                        concat!("An error occurred while initializing class ",
+                                stringify!(#cls)));
+                    TYPE_OBJECT = ty.into_object().steal_ptr() as *mut ffi::PyTypeObject;
+
+                    // variant attributes need the type object, set them once it exists
+                    let ty = PyType::from_type_ptr(py, TYPE_OBJECT);
+                    let attrs: Vec<(&str, PyObject)> = vec![#(#attrs),*];
+                    for (name, value) in attrs {
+                        pyo3::ObjectProtocol::setattr(ty.as_object(), py, name, value).expect(
+                            concat!("An error occurred while initializing class ",
+                                    stringify!(#cls)));
+                    }
+                }
+                PyType::from_type_ptr(py, TYPE_OBJECT)
+            }
+        }
+
+        #[inline]
+        fn base_offset() -> usize {
+            let align = std::mem::align_of::<pyo3::class::cell::PyCell<#cls>>();
+            let bs = <PyObject as BaseObject>::size();
+
+            // round base_size up to next multiple of align
+            (bs + align - 1) / align * align
+        }
+
+        /// Enum value of the object, `obj` has to be instance of the enum type
+        #[inline]
+        unsafe fn cell<'a>(obj: *mut ffi::PyObject) -> &'a pyo3::class::cell::PyCell<#cls> {
+            &*((obj as *const u8).offset(base_offset() as isize)
+               as *const pyo3::class::cell::PyCell<#cls>)
+        }
+
+        impl BaseObject for #obj {
+            type Type = #cls;
+
+            #[inline]
+            fn size() -> usize {
+                base_offset() + std::mem::size_of::<pyo3::class::cell::PyCell<#cls>>()
+            }
+
+            unsafe fn alloc(py: Python, ty: &PyType, value: #cls) -> PyResult<PyObject> {
+                let obj = try!(<PyObject as BaseObject>::alloc(py, ty, ()));
+
+                let ptr = (obj.as_ptr() as *mut u8)
+                    .offset(base_offset() as isize) as *mut pyo3::class::cell::PyCell<#cls>;
+                std::ptr::write(ptr, pyo3::class::cell::PyCell::new(value));
+
+                Ok(obj)
+            }
+
+            unsafe fn dealloc(py: Python, obj: *mut ffi::PyObject) {
+                let ptr = (obj as *mut u8)
+                    .offset(base_offset() as isize) as *mut pyo3::class::cell::PyCell<#cls>;
+                std::ptr::drop_in_place(ptr);
+
+                <PyObject as BaseObject>::dealloc(py, obj)
+            }
+        }
+    }
+}
+
+/// Handle struct and python object traits of enum or variant type
+fn impl_handle(obj: &syn::Ident) -> Tokens {
+    let mut tokens = Tokens::new();
+    py_class::impl_to_py_object(obj).to_tokens(&mut tokens);
+    py_class::impl_python_object(obj).to_tokens(&mut tokens);
+    py_class::impl_checked_downcast(obj).to_tokens(&mut tokens);
+
+    quote! {
+        struct #obj {
+            _unsafe_inner: PyObject
+        }
+
+        impl pyo3::PythonObjectWithTypeObject for #obj {
+            #[inline]
+            fn type_object(py: Python) -> PyType {
+                unsafe { <#obj as pyo3::class::PyTypeObject>::initialized(py, None) }
+            }
+        }
+
+        #tokens
+    }
+}
+
+/// `ToPyObject` creates new object, `FromPyObject` copies enum value of the object
+fn impl_conversions(cls: &syn::Ident, obj: &syn::Ident,
+                    variants: &Vec<syn::Variant>, unit: bool) -> Tokens {
+    let (copy_self, copy_value, ty) = if unit {
+        let copy = &unit_arms(cls, variants, |name| quote! { #cls::#name });
+        (quote! { match *self { #(#copy),* } },
+         quote! { match *value { #(#copy),* } },
+         quote! { py.get_type::<#obj>() })
+    } else {
+        let mut types = Vec::new();
+        for variant in variants.iter() {
+            let pattern = variant_pattern(cls, variant);
+            let vobj = syn::Ident::from(format!("{}{}Object", cls, variant.ident));
+            types.push(quote! { #pattern => py.get_type::<#vobj>() });
+        }
+        (quote! { std::clone::Clone::clone(self) },
+         quote! { std::clone::Clone::clone(&*value) },
+         quote! { match self { #(#types),* } })
+    };
+
+    quote! {
+        impl pyo3::ToPyObject for #cls {
+            #[inline]
+            fn to_py_object(&self, py: Python) -> PyObject {
+                pyo3::ToPyObject::into_py_object(#copy_self, py)
+            }
+
+            fn into_py_object(self, py: Python) -> PyObject {
+                let ty = #ty;
+                unsafe { <#obj as BaseObject>::alloc(py, &ty, self) }.expect(
+                    concat!("An error occurred while creating ", stringify!(#cls), " object"))
+            }
+        }
+
+        impl <'source> pyo3::FromPyObject<'source> for #cls {
+            fn extract(py: Python, obj: &'source PyObject) -> PyResult<#cls> {
+                let obj = obj.cast_as::<#obj>(py)?;
+                let value = unsafe { cell(obj._unsafe_inner.as_ptr()) }.try_borrow(py)?;
+                Ok(#copy_value)
+            }
+        }
+    }
+}
+
+/// Comparison, hash, `int()` and `repr()` of enum with unit variants,
+/// values are compared by discriminant.
+fn impl_unit_protocols(cls: &syn::Ident, obj: &syn::Ident, variants: &Vec<syn::Variant>) -> Tokens {
+    let discriminants = unit_arms(cls, variants, |name| quote! { #cls::#name as isize });
+    let reprs = unit_arms(
+        cls, variants, |name| quote! { concat!(stringify!(#cls), ".", stringify!(#name)) });
+
+    quote! {
+        /// Discriminant of enum value of the object
+        fn discriminant(obj: &#obj, py: Python) -> PyResult<isize> {
+            let value = unsafe { cell(obj._unsafe_inner.as_ptr()) }.try_borrow(py)?;
+            Ok(match *value { #(#discriminants),* })
+        }
+
+        impl pyo3::class::basic::PyObjectProtocol for #obj {
+            fn __repr__(&self, py: Python) -> PyResult<&'static str> {
+                let value = unsafe { cell(self._unsafe_inner.as_ptr()) }.try_borrow(py)?;
+                Ok(match *value { #(#reprs),* })
+            }
+
+            fn __hash__(&self, py: Python) -> PyResult<usize> {
+                // same as hash of python int, -1 is error value of `tp_hash`
+                match discriminant(self, py)? {
+                    -1 => Ok(-2isize as usize),
+                    value => Ok(value as usize),
+                }
+            }
+
+            fn __richcmp__(&self, py: Python, other: PyObject, op: pyo3::CompareOp)
+                           -> PyResult<PyObject> {
+                let other = match other.cast_as::<#obj>(py) {
+                    Ok(other) => discriminant(other, py)?,
+                    Err(_) => return Ok(py.NotImplemented()),
+                };
+                let value = discriminant(self, py)?;
+                match op {
+                    pyo3::CompareOp::Eq => Ok(pyo3::ToPyObject::to_py_object(&(value == other), py)),
+                    pyo3::CompareOp::Ne => Ok(pyo3::ToPyObject::to_py_object(&(value != other), py)),
+                    _ => Ok(py.NotImplemented()),
+                }
+            }
+        }
+
+        impl pyo3::class::basic::PyObjectReprProtocol for #obj {
+            type Success = &'static str;
+            type Result = PyResult<&'static str>;
+        }
+
+        impl pyo3::class::basic::PyObjectHashProtocol for #obj {
+            type Result = PyResult<usize>;
+        }
+
+        impl pyo3::class::basic::PyObjectRichcmpProtocol for #obj {
+            type Other = PyObject;
+            type Success = PyObject;
+            type Result = PyResult<PyObject>;
+        }
+
+        impl pyo3::class::number::PyNumberProtocol for #obj {
+            fn __int__(&self, py: Python) -> PyResult<isize> {
+                discriminant(self, py)
+            }
+        }
+
+        impl pyo3::class::number::PyNumberIntProtocol for #obj {
+            type Success = isize;
+            type Result = PyResult<isize>;
+        }
+    }
+}
+
+/// `repr()` of enum with data, i.e. `Shape.Circle(radius=1.0)`
+fn impl_data_repr(cls: &syn::Ident, obj: &syn::Ident, variants: &Vec<syn::Variant>) -> Tokens {
+    let mut arms = Vec::new();
+    for variant in variants.iter() {
+        let name = &variant.ident;
+        let prefix = quote! { concat!(stringify!(#cls), ".", stringify!(#name)) };
+        let arm = match variant.data {
+            syn::VariantData::Unit => quote! {
+                #cls::#name => #prefix.to_owned()
+            },
+            syn::VariantData::Tuple(ref fields) => {
+                let bindings: &Vec<syn::Ident> = &(0..fields.len())
+                    .map(|i| syn::Ident::from(format!("_{}", i))).collect();
+                let bindings2 = bindings;
+                quote! {
+                    #cls::#name(#(ref #bindings),*) => {
+                        let fields: Vec<String> = vec![#(field_repr(py, #bindings2)?),*];
+                        format!("{}({})", #prefix, fields.join(", "))
+                    }
+                }
+            },
+            syn::VariantData::Struct(ref fields) => {
+                let bindings: &Vec<&syn::Ident> = &fields.iter()
+                    .map(|f| f.ident.as_ref().unwrap()).collect();
+                let bindings2 = bindings;
+                let bindings3 = bindings;
+                quote! {
+                    #cls::#name { #(ref #bindings),* } => {
+                        let fields: Vec<String> = vec![
+                            #(format!("{}={}", stringify!(#bindings2), field_repr(py, #bindings3)?)),*];
+                        format!("{}({})", #prefix, fields.join(", "))
+                    }
+                }
+            },
+        };
+        arms.push(arm);
+    }
+
+    quote! {
+        fn field_repr<T: pyo3::ToPyObject>(py: Python, value: &T) -> PyResult<String> {
+            let value = value.to_py_object(py);
+            let repr = pyo3::ObjectProtocol::repr(&value, py)?;
+            Ok(repr.to_string_lossy(py).into_owned())
+        }
+
+        impl pyo3::class::basic::PyObjectProtocol for #obj {
+            fn __repr__(&self, py: Python) -> PyResult<String> {
+                let value = unsafe { cell(self._unsafe_inner.as_ptr()) }.try_borrow(py)?;
+                Ok(match *value {
+                    #(#arms),*
+                })
+            }
+        }
+
+        impl pyo3::class::basic::PyObjectReprProtocol for #obj {
+            type Success = String;
+            type Result = PyResult<String>;
+        }
+    }
+}
+
+/// Subclass of the enum type for a variant of enum with data,
+/// with getters for variant fields.
+fn impl_variant_type(cls: &syn::Ident, obj: &syn::Ident, variant: &syn::Variant) -> Tokens {
+    let name = &variant.ident;
+    let vobj = syn::Ident::from(format!("{}{}Object", cls, name));
+    let doc = utils::get_doc(&variant.attrs);
+
+    let mut getters = Vec::new();
+    for (i, field) in variant.data.fields().iter().enumerate() {
+        let (py_name, pattern) = match field.ident {
+            Some(ref ident) => (
+                ident.as_ref().to_owned(),
+                quote! { #cls::#name { #ident: ref field, .. } }),
+            None => {
+                let skip: &Vec<Tokens> = &(0..i).map(|_| quote! { _ }).collect();
+                (format!("_{}", i),
+                 quote! { #cls::#name(#(#skip,)* ref field, ..) })
+            }
+        };
+        let field_doc = utils::get_doc(&field.attrs);
+
+        getters.push(quote! {
+            pyo3::class::PyMethodDefType::Getter({
+                unsafe extern "C" fn wrap(slf: *mut ffi::PyObject,
+                                          _: *mut pyo3::c_void) -> *mut ffi::PyObject
+                {
+                    const LOCATION: &'static str = concat!(
+                        stringify!(#cls), ".", stringify!(#name), ".getter_", #py_name, "()");
+                    pyo3::callback::handle_callback(
+                        LOCATION, pyo3::callback::PyObjectCallbackConverter, |py|
+                        {
+                            let data = cell(slf).try_borrow(py)?;
+                            match *data {
+                                #pattern => Ok(pyo3::ToPyObject::to_py_object(field, py)),
+                                _ => unreachable!(),
+                            }
+                        })
+                }
+
+                pyo3::class::PyGetterDef {
+                    name: #py_name,
+                    meth: wrap,
+                    doc: #field_doc,
+                }
+            })
+        });
+    }
+
+    let props = if getters.is_empty() {
+        Tokens::new()
+    } else {
+        quote! {
+            impl pyo3::class::methods::PyPropMethodsProtocolImpl for #vobj {
+                fn py_methods() -> &'static [pyo3::class::PyMethodDefType] {
+                    static METHODS: &'static [pyo3::class::PyMethodDefType] = &[
+                        #(#getters),*
+                    ];
+                    METHODS
+                }
+            }
+        }
+    };
+
+    let mut tokens = Tokens::new();
+    impl_handle(&vobj).to_tokens(&mut tokens);
+
+    quote! {
+        #tokens
+
+        impl pyo3::class::PyTypeObject for #vobj {
+
+            fn add_to_module(py: Python, module: &PyModule) -> PyResult<()> {
+                let ty = unsafe { #vobj::initialized(py, module.name(py).ok()) };
+                pyo3::class::typeob::add_type_to_module(py, module, stringify!(#name), ty)
+            }
+
+            unsafe fn initialized(py: Python, module_name: Option<&str>) -> PyType {
+                static mut TYPE_OBJECT: *mut ffi::PyTypeObject = 0 as *mut ffi::PyTypeObject;
+
+                if TYPE_OBJECT.is_null() {
+                    let base = <#obj as pyo3::class::PyTypeObject>::initialized(py, module_name);
+                    let ty = pyo3::class::typeob::initialize_type::<#vobj>(
+                        py, module_name, stringify!(#name), Some(&base), #doc, 0).expect(
+                        concat!("An error occurred while initializing class ",
+                                stringify!(#cls), ".", stringify!(#name)));
+                    TYPE_OBJECT = ty.into_object().steal_ptr() as *mut ffi::PyTypeObject;
+                }
+                PyType::from_type_ptr(py, TYPE_OBJECT)
+            }
+        }
+
+        impl BaseObject for #vobj {
+            type Type = #cls;
+
+            #[inline]
+            fn size() -> usize {
+                <#obj as BaseObject>::size()
+            }
+
+            unsafe fn alloc(py: Python, ty: &PyType, value: #cls) -> PyResult<PyObject> {
+                <#obj as BaseObject>::alloc(py, ty, value)
+            }
+
+            unsafe fn dealloc(py: Python, obj: *mut ffi::PyObject) {
+                <#obj as BaseObject>::dealloc(py, obj)
+            }
+        }
+
+        #props
+    }
+}
+
+/// Match arms `Enum::Variant => value` of enum with unit variants
+fn unit_arms<F>(cls: &syn::Ident, variants: &Vec<syn::Variant>, value: F) -> Vec<Tokens>
+    where F: Fn(&syn::Ident) -> Tokens
+{
+    variants.iter().map(|variant| {
+        let name = &variant.ident;
+        let value = value(name);
+        quote! { #cls::#name => #value }
+    }).collect()
+}
+
+/// Pattern matching any value of the variant
+fn variant_pattern(cls: &syn::Ident, variant: &syn::Variant) -> Tokens {
+    let name = &variant.ident;
+    match variant.data {
+        syn::VariantData::Unit => quote! { #cls::#name },
+        syn::VariantData::Tuple(_) => quote! { #cls::#name(..) },
+        syn::VariantData::Struct(_) => quote! { #cls::#name { .. } },
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+use syn;
+use quote::Tokens;
+
+use utils::{self, Errors};
+
+
+/// Where named field is extracted from.
+enum Source {
+    Attr(String),
+    Item(String),
+}
+
+/// `#[derive(FromPyObject)]`
+///
+/// Named fields are extracted from attributes or, with `#[extract(item)]`, from mapping items.
+/// Tuple structs are extracted from sequences of the same length, except for single field
+/// tuple structs which are extracted as the field itself. Enum variants are tried in order,
+/// the first one which could be extracted wins.
+pub fn build_from_py_object(ast: &syn::DeriveInput, errors: &mut Errors) -> Tokens {
+    let cls = &ast.ident;
+    if !ast.generics.ty_params.is_empty() || !ast.generics.lifetimes.is_empty() {
+        errors.error(&ast.generics,
+                     &format!("#[derive(FromPyObject)] `{}` can not be generic", cls));
+        return Tokens::new()
+    }
+
+    let body = match ast.body {
+        syn::Body::Struct(syn::VariantData::Unit) => {
+            errors.error(cls, "#[derive(FromPyObject)] can not be used with unit structs");
+            return Tokens::new()
+        },
+        syn::Body::Struct(ref data) => {
+            let value = extract_fields(quote!{#cls}, data, errors);
+            quote! { Ok(#value) }
+        },
+        syn::Body::Enum(ref variants) => {
+            if variants.is_empty() {
+                errors.error(cls, "#[derive(FromPyObject)] can not be used with empty enums");
+                return Tokens::new()
+            }
+            let mut arms = Vec::new();
+            for variant in variants.iter() {
+                let name = &variant.ident;
+                if variant.data == syn::VariantData::Unit {
+                    errors.error(name, "#[derive(FromPyObject)] can not be used with unit variants");
+                    continue
+                }
+                let value = extract_fields(quote!{#cls::#name}, &variant.data, errors);
+                arms.push(quote! {
+                    match (|| -> PyResult<#cls> { Ok(#value) })() {
+                        Ok(value) => return Ok(value),
+                        // only mismatch falls through to the next variant
+                        Err(err) => if pyo3::derive::is_mismatch(py, &err) {
+                            failures.push((stringify!(#name), err))
+                        } else {
+                            return Err(err)
+                        },
+                    }
+                });
+            }
+            quote! {
+                let mut failures = Vec::new();
+                #(#arms)*
+                Err(pyo3::derive::extract_variants_error(py, obj, path, expected, failures))
+            }
+        },
+    };
+
+    let dummy_const = syn::Ident::new(format!("_IMPL_PYO3_FROM_PY_OBJECT_{}", cls));
+    quote! {
+        #[feature(specialization)]
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications, unused_variables)]
+        const #dummy_const: () = {
+            extern crate pyo3;
+            use pyo3::{Python, PyObject, PyResult};
+
+            impl<'source> pyo3::FromPyObject<'source> for #cls {
+                fn extract(py: Python, obj: &'source PyObject) -> PyResult<#cls> {
+                    pyo3::derive::FromPyObjectPath::extract_path(
+                        py, obj, stringify!(#cls), stringify!(#cls))
+                }
+            }
+
+            impl pyo3::derive::FromPyObjectPath for #cls {
+                fn extract_path(py: Python, obj: &PyObject, path: &str, expected: &str)
+                                -> PyResult<#cls> {
+                    #body
+                }
+            }
+        };
+    }
+}
+
+/// Expression which constructs struct or variant `ctor`, propagates extraction errors.
+fn extract_fields(ctor: Tokens, data: &syn::VariantData, errors: &mut Errors) -> Tokens {
+    match *data {
+        syn::VariantData::Struct(ref fields) => {
+            let mut names = Vec::new();
+            let mut values = Vec::new();
+            for field in fields.iter() {
+                let name = field.ident.as_ref().unwrap();
+                let expected = type_name(&field.ty);
+                let value = match parse_source(field, errors) {
+                    Source::Attr(attr) => quote! {
+                        pyo3::derive::extract_attr(py, obj, path, #attr, #expected)?
+                    },
+                    Source::Item(key) => quote! {
+                        pyo3::derive::extract_item(py, obj, path, #key, #expected)?
+                    },
+                };
+                names.push(name);
+                values.push(value);
+            }
+            quote! { #ctor { #(#names: #values),* } }
+        },
+        syn::VariantData::Tuple(ref fields) => {
+            for field in fields.iter() {
+                if field.attrs.iter().any(is_extract_attr) {
+                    errors.error(field, "#[extract] can only be used with named fields");
+                }
+            }
+            if fields.len() == 1 {
+                let expected = type_name(&fields[0].ty);
+                return quote! {
+                    #ctor(pyo3::derive::FromPyObjectPath::extract_path(py, obj, path, #expected)?)
+                }
+            }
+            let len = fields.len();
+            let mut values = Vec::new();
+            for (index, field) in fields.iter().enumerate() {
+                let expected = type_name(&field.ty);
+                values.push(quote! {
+                    pyo3::derive::extract_seq_item(py, seq, path, #index, #expected)?
+                });
+            }
+            quote! {
+                {
+                    let seq = pyo3::derive::extract_sequence(py, obj, path, #len)?;
+                    #ctor(#(#values),*)
+                }
+            }
+        },
+        syn::VariantData::Unit => unreachable!(),
+    }
+}
+
+fn is_extract_attr(attr: &syn::Attribute) -> bool {
+    attr.value.name() == "extract"
+}
+
+/// Parse `#[extract(attr = "...")]`, `#[extract(item)]` and `#[extract(item = "...")]`
+fn parse_source(field: &syn::Field, errors: &mut Errors) -> Source {
+    let name = field.ident.as_ref().unwrap().as_ref().to_owned();
+    let mut source = None;
+
+    for attr in field.attrs.iter().filter(|attr| is_extract_attr(attr)) {
+        if source.is_some() {
+            errors.error(attr, "#[extract] attribute can not be used multiple times");
+            continue
+        }
+        let items = match attr.value {
+            syn::MetaItem::List(_, ref items) if items.len() == 1 => items,
+            _ => {
+                errors.error(attr, "#[extract] requires single `attr` or `item` parameter");
+                continue
+            },
+        };
+        source = match items[0] {
+            syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w))
+                if w.as_ref() == "item" => Some(Source::Item(name.clone())),
+            syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(
+                ref n, syn::Lit::Str(ref value, _))) if n.as_ref() == "item" =>
+                Some(Source::Item(value.clone())),
+            syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(
+                ref n, syn::Lit::Str(ref value, _))) if n.as_ref() == "attr" =>
+                Some(Source::Attr(value.clone())),
+            _ => {
+                errors.error(&items[0], "unsupported #[extract] parameter");
+                continue
+            },
+        };
+    }
+
+    source.unwrap_or(Source::Attr(name))
+}
+
+/// Python name of the type, used in error messages.
+fn type_name(ty: &syn::Ty) -> String {
+    match *ty {
+        syn::Ty::Path(_, ref path) => {
+            let segment = path.segments.last().unwrap();
+            let name = match segment.ident.as_ref() {
+                "i8" | "i16" | "i32" | "i64" | "isize" |
+                "u8" | "u16" | "u32" | "u64" | "usize" => "int",
+                "f32" | "f64" => "float",
+                "bool" => "bool",
+                "String" | "str" => "str",
+                "Vec" => "list",
+                "HashMap" | "BTreeMap" => "dict",
+                "HashSet" | "BTreeSet" => "set",
+                "PyObject" => "object",
+                "Option" => {
+                    if let syn::PathParameters::AngleBracketed(ref params) = segment.parameters {
+                        if let Some(inner) = params.types.first() {
+                            return format!("{} or None", type_name(inner))
+                        }
+                    }
+                    "Option"
+                },
+                name => name,
+            };
+            name.to_owned()
+        },
+        syn::Ty::Rptr(_, ref ty) => type_name(&ty.ty),
+        syn::Ty::Tup(_) => "tuple".to_owned(),
+        syn::Ty::Slice(_) | syn::Ty::Array(..) => "list".to_owned(),
+        _ => utils::for_err_msg(ty),
+    }
+}
@@ -1,40 +1,84 @@
 // Copyright (c) 2017-present PyO3 Project and Contributors
 
 use syn;
-use quote::Tokens;
+use quote::{Tokens, ToTokens};
 
 use py_method;
+use utils::Errors;
 
 
-pub fn build_py_methods(ast: &mut syn::Item) -> Tokens {
+/// Maximum number of `#[methods]` blocks per class, has to match number of
+/// blocks in `pyo3::class::methods`.
+const MAX_METHODS_BLOCKS: usize = 16;
+
+
+pub fn build_py_methods(ast: &mut syn::Item, attr: String, errors: &mut Errors) -> Tokens {
+    let block = parse_attribute(attr, errors);
+
     match ast.node {
         syn::ItemKind::Impl(_, _, _, ref path, ref ty, ref mut impl_items) => {
-            if let &Some(_) = path {
-                panic!("#[methods] can not be used only with trait impl block");
+            if let &Some(ref path) = path {
+                errors.error(path, "#[methods] can not be used with trait impl block");
+                Tokens::new()
             } else {
-                impl_methods(ty, impl_items)
+                impl_methods(ty, impl_items, block, errors)
             }
         },
-        _ => panic!("#[methods] can only be used with Impl blocks"),
+        _ => {
+            errors.error(&ast.ident, "#[methods] can only be used with Impl blocks");
+            Tokens::new()
+        },
     }
 }
 
-fn impl_methods(ty: &Box<syn::Ty>, impls: &mut Vec<syn::ImplItem>) -> Tokens {
+fn impl_methods(ty: &Box<syn::Ty>, impls: &mut Vec<syn::ImplItem>,
+                block: usize, errors: &mut Errors) -> Tokens {
 
     // get method names in impl block
     let mut methods = Vec::new();
+    let mut props = Vec::new();
     for iimpl in impls.iter_mut() {
         match iimpl.node {
             syn::ImplItemKind::Method(ref mut sig, ref mut block) => {
+                if let Some(prop) = py_method::property_name(&iimpl.ident, &iimpl.attrs) {
+                    if props.contains(&prop) {
+                        errors.error(&iimpl.ident, &format!(
+                            "{} of property `{}` is defined multiple times", prop.0, prop.1));
+                    } else {
+                        props.push(prop);
+                    }
+                }
                 methods.push(py_method::gen_py_method(
-                    ty, &iimpl.ident, sig, block, &mut iimpl.attrs));
+                    ty, &iimpl.ident, sig, block, &mut iimpl.attrs, errors));
+            },
+            syn::ImplItemKind::Const(_, _) => {
+                // associated consts marked with #[classattr] become class attributes
+                let len = iimpl.attrs.len();
+                iimpl.attrs.retain(|attr| attr.value != syn::MetaItem::Word("classattr".into()));
+                if iimpl.attrs.len() != len {
+                    let name = &iimpl.ident;
+                    methods.push(py_method::impl_py_class_attribute_def(
+                        name, &quote! { #ty::#name }));
+                }
             },
             _ => (),
         }
     }
 
+    let cls_name = match **ty {
+        syn::Ty::Path(_, ref path) => path.segments.last().unwrap().ident.as_ref().to_owned(),
+        _ => {
+            errors.error(ty, "#[methods] can only be used with class types");
+            return Tokens::new()
+        }
+    };
+
+    // every block of the class specializes its own index,
+    // two blocks with the same index are conflicting implementations
+    let index = syn::Ident::new(format!("Block{}", block));
+    let guards = py_method::impl_property_guards(ty, &props);
     let tokens = quote! {
-        impl pyo3::class::methods::PyMethodsProtocolImpl for #ty {
+        impl pyo3::class::methods::PyMethodsProtocolImpl<pyo3::class::methods::#index> for #ty {
             fn py_methods() -> &'static [pyo3::class::PyMethodDefType] {
                 static METHODS: &'static [pyo3::class::PyMethodDefType] = &[
                     #(#methods),*
@@ -42,9 +86,11 @@ fn impl_methods(ty: &Box<syn::Ty>, impls: &mut Vec<syn::ImplItem>) -> Tokens {
                 METHODS
             }
         }
+
+        #guards
     };
 
-    let dummy_const = syn::Ident::new("_IMPL_PYO3_METHODS");
+    let dummy_const = syn::Ident::new(format!("_IMPL_PYO3_METHODS_{}_{}", cls_name, block));
     quote! {
         #[feature(specialization)]
         #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
@@ -56,3 +102,49 @@ fn impl_methods(ty: &Box<syn::Ty>, impls: &mut Vec<syn::ImplItem>) -> Tokens {
         };
     }
 }
+
+/// Parse `#[methods(block = N)]` parameter, block index defaults to 0
+fn parse_attribute(attr: String, errors: &mut Errors) -> usize {
+    // attribute arguments may or may not include surrounding parens
+    let attr = attr.trim();
+    let attr = if attr.starts_with('(') && attr.ends_with(')') {
+        attr[1..attr.len()-1].trim()
+    } else {
+        attr
+    };
+    if attr.is_empty() {
+        return 0
+    }
+
+    let tts = match syn::parse_token_trees(attr) {
+        Ok(tts) => tts,
+        Err(err) => {
+            errors.message(format!("can not parse #[methods] attribute: {}: `{}`", err, attr));
+            return 0
+        }
+    };
+    let mut item = Tokens::new();
+    for tt in tts.iter() {
+        tt.to_tokens(&mut item);
+    }
+
+    if tts.len() == 3 {
+        match (&tts[0], &tts[1], &tts[2]) {
+            (&syn::TokenTree::Token(syn::Token::Ident(ref key)),
+             &syn::TokenTree::Token(syn::Token::Eq),
+             &syn::TokenTree::Token(syn::Token::Literal(syn::Lit::Int(value, _))))
+                if key.as_ref() == "block" =>
+            {
+                if value >= MAX_METHODS_BLOCKS as u64 {
+                    errors.error(&item, &format!(
+                        "#[methods] block index has to be less than {}", MAX_METHODS_BLOCKS));
+                    return 0
+                }
+                return value as usize
+            },
+            _ => (),
+        }
+    }
+    errors.error(&item, "unsupported #[methods] parameter");
+    0
+}
@@ -1,8 +1,9 @@
 // Copyright (c) 2017-present PyO3 Project and Contributors
 
 use syn;
-use quote::Tokens;
-use utils::for_err_msg;
+use quote::{Tokens, ToTokens};
+use utils::{self, Errors};
+use args::{self, Argument, ArgKind};
 
 
 #[derive(Debug)]
@@ -11,6 +12,10 @@ struct Arg<'a> {
     pub mode: &'a syn::BindingMode,
     pub ty: &'a syn::Ty,
     pub optional: Option<&'a syn::Ty>,
+    pub default: Option<syn::Expr>,
+    pub kind: ArgKind,
+    pub varargs: bool,
+    pub kwargs: bool,
 }
 
 #[derive(PartialEq, Debug)]
@@ -18,74 +23,141 @@ enum FnType {
     Getter(Option<String>),
     Setter(Option<String>),
     Fn,
+    FnNew,
+    FnCall,
+    FnClass,
+    FnStatic,
+    ClassAttr,
 }
 
 
 pub fn gen_py_method<'a>(cls: &Box<syn::Ty>, name: &syn::Ident,
                          sig: &mut syn::MethodSig, _block: &mut syn::Block,
-                         meth_attrs: &mut Vec<syn::Attribute>) -> Tokens
+                         meth_attrs: &mut Vec<syn::Attribute>, errors: &mut Errors) -> Tokens
 {
-    check_generic(name, sig);
+    check_generic(name, sig, errors);
 
-    let fn_type = parse_attributes(meth_attrs);
+    let (fn_type, spec) = parse_attributes(meth_attrs, errors);
+    let doc = utils::get_doc(meth_attrs);
 
     //let mut has_self = false;
     let mut py = false;
+    // `&mut self` methods borrow instance data mutably
+    let mut mutable = false;
+    // __new__ and class methods receive type object as first argument
+    let mut cls_arg = fn_type == FnType::FnNew || fn_type == FnType::FnClass;
     let mut arguments: Vec<Arg> = Vec::new();
 
     for input in sig.decl.inputs.iter() {
         match input {
-            &syn::FnArg::SelfRef(_, _) => {
+            &syn::FnArg::SelfRef(_, _) | &syn::FnArg::SelfValue(_) => {
                 //has_self = true;
+                if let &syn::FnArg::SelfRef(_, syn::Mutability::Mutable) = input {
+                    mutable = true;
+                }
+                if fn_type == FnType::ClassAttr {
+                    errors.error(input, "#[classattr] can not have self argument");
+                }
             },
-            &syn::FnArg::SelfValue(_) => {
-                //has_self = true;
-            }
             &syn::FnArg::Captured(ref pat, ref ty) => {
                 let (mode, ident) = match pat {
                     &syn::Pat::Ident(ref mode, ref ident, _) =>
                         (mode, ident),
-                    _ =>
-                        panic!("unsupported argument: {:?}", pat),
+                    _ => {
+                        errors.error(pat, "unsupported argument pattern");
+                        continue
+                    }
                 };
                 // TODO add check for first py: Python arg
-                if py {
-                    let opt = check_arg_ty_and_optional(name, ty);
-                    arguments.push(Arg{name: ident, mode: mode, ty: ty, optional: opt});
+                if cls_arg {
+                    cls_arg = false;
+                } else if py {
+                    let opt = check_arg_ty_and_optional(ty, errors);
+                    arguments.push(Arg{name: ident, mode: mode, ty: ty, optional: opt,
+                                       default: args::default_value(&spec, ident),
+                                       kind: args::arg_kind(&spec, ident),
+                                       varargs: args::is_varargs(&spec, ident),
+                                       kwargs: args::is_kwargs(&spec, ident)});
                 } else {
                     py = true;
                 }
             }
             &syn::FnArg::Ignored(_) =>
-                panic!("ignored argument: {:?}", name),
+                errors.error(input, "ignored argument is not supported"),
         }
     }
 
+    check_arg_spec(&spec, &arguments, errors);
+    if fn_type == FnType::ClassAttr && !arguments.is_empty() {
+        errors.error(name, "#[classattr] can only have `py: Python` argument");
+    }
+
+    // regular methods expose their signature through `__text_signature__`
+    let doc = match fn_type {
+        FnType::Fn | FnType::FnClass | FnType::FnStatic =>
+            text_signature(name, &fn_type, &arguments) + &doc,
+        _ => doc,
+    };
+
     match fn_type {
         FnType::Fn =>
-            impl_py_method_def(name, &impl_wrap(cls, name, arguments)),
+            impl_py_method_def(name, &doc, &impl_wrap(cls, name, mutable, arguments)),
+        FnType::FnNew =>
+            impl_py_method_def_new(name, &doc, &impl_wrap_new(cls, name, arguments)),
+        FnType::FnCall =>
+            impl_py_method_def_call(name, &doc, &impl_wrap(cls, name, mutable, arguments)),
+        FnType::FnClass =>
+            impl_py_method_def_class(name, &doc, &impl_wrap_class(cls, name, arguments)),
+        FnType::FnStatic =>
+            impl_py_method_def_static(name, &doc, &impl_wrap_static(cls, name, arguments)),
         FnType::Getter(getter) =>
-            impl_py_getter_def(name, getter, &impl_wrap_getter(cls, name, arguments)),
+            impl_py_getter_def(
+                name, &doc, getter, &impl_wrap_getter(cls, name, mutable, arguments)),
         FnType::Setter(setter) =>
-            impl_py_setter_def(name, setter, &impl_wrap_setter(cls, name, arguments)),
+            impl_py_setter_def(
+                name, &doc, setter, &impl_wrap_setter(cls, name, mutable, arguments)),
+        FnType::ClassAttr => {
+            let value = if py { quote! { #cls::#name(py) } } else { quote! { #cls::#name() } };
+            impl_py_class_attribute_def(name, &value)
+        },
     }
 }
 
-fn parse_attributes(attrs: &mut Vec<syn::Attribute>) -> FnType {
+fn parse_attributes(attrs: &mut Vec<syn::Attribute>, errors: &mut Errors)
+                    -> (FnType, Vec<Argument>)
+{
     let mut new_attrs = Vec::new();
+    let mut spec = Vec::new();
     let mut res: Option<FnType> = None;
 
     for attr in attrs.iter() {
         match attr.value {
             syn::MetaItem::Word(ref name) => {
                 match name.as_ref() {
+                    "new" | "call" | "classmethod" | "staticmethod" | "classattr" => {
+                        if res != None {
+                            errors.error(
+                                attr, "attribute can not be combined with other attributes");
+                            continue
+                        }
+                        res = Some(match name.as_ref() {
+                            "new" => FnType::FnNew,
+                            "call" => FnType::FnCall,
+                            "classmethod" => FnType::FnClass,
+                            "classattr" => FnType::ClassAttr,
+                            _ => FnType::FnStatic,
+                        })
+                    },
                     "setter" | "getter" => {
                         if attr.style == syn::AttrStyle::Inner {
-                            panic!("Inner style attribute is not
-                                    supported for setter and getter");
+                            errors.error(
+                                attr, "inner style attribute is not supported for setter and getter");
+                            continue
                         }
                         if res != None {
-                            panic!("setter/getter attribute can not be used mutiple times");
+                            errors.error(
+                                attr, "setter/getter attribute can not be used mutiple times");
+                            continue
                         }
                         if name.as_ref() == "setter" {
                             res = Some(FnType::Setter(None))
@@ -102,42 +174,40 @@ fn parse_attributes(attrs: &mut Vec<syn::Attribute>) -> FnType {
                 match name.as_ref() {
                     "setter" | "getter" => {
                         if attr.style == syn::AttrStyle::Inner {
-                            panic!("Inner style attribute is not
-                                    supported for setter and getter");
+                            errors.error(
+                                attr, "inner style attribute is not supported for setter and getter");
+                            continue
                         }
                         if res != None {
-                            panic!("setter/getter attribute can not be used mutiple times");
+                            errors.error(
+                                attr, "setter/getter attribute can not be used mutiple times");
+                            continue
                         }
                         if meta.len() != 1 {
-                            panic!("setter/getter requires one value");
+                            errors.error(attr, "setter/getter requires one value");
+                            continue
                         }
-                        match *meta.first().unwrap() {
-                            syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w)) => {
-                                if name.as_ref() == "setter" {
-                                    res = Some(FnType::Setter(Some(w.to_string())))
-                                } else {
-                                    res = Some(FnType::Getter(Some(w.to_string())))
-                                }
-                            },
-                            syn::NestedMetaItem::Literal(ref lit) => {
-                                match *lit {
-                                    syn::Lit::Str(ref s, syn::StrStyle::Cooked) => {
-                                        if name.as_ref() == "setter" {
-                                            res = Some(FnType::Setter(Some(s.clone())))
-                                        } else {
-                                            res = Some(FnType::Getter(Some(s.clone())))
-                                        }
-                                    },
-                                    _ => {
-                                        panic!("setter/getter attribute requires str value");
-                                    },
-                                }
-                            }
+                        let value = match meta[0] {
+                            syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w)) =>
+                                w.to_string(),
+                            syn::NestedMetaItem::Literal(
+                                syn::Lit::Str(ref s, syn::StrStyle::Cooked)) =>
+                                s.clone(),
                             _ => {
-                                println!("cannot parse {:?} attribute: {:?}", name, meta);
+                                errors.error(
+                                    attr, "setter/getter attribute requires name or str value");
+                                continue
                             },
+                        };
+                        if name.as_ref() == "setter" {
+                            res = Some(FnType::Setter(Some(value)))
+                        } else {
+                            res = Some(FnType::Getter(Some(value)))
                         }
                     },
+                    "args" => {
+                        spec.extend(args::parse_arguments(meta.as_slice(), errors))
+                    },
                     _ => {
                         new_attrs.push(attr.clone())
                     }
@@ -152,43 +222,126 @@ fn parse_attributes(attrs: &mut Vec<syn::Attribute>) -> FnType {
     attrs.extend(new_attrs);
 
     match res {
-        Some(tp) => tp,
-        None => FnType::Fn,
+        Some(tp) => (tp, spec),
+        None => (FnType::Fn, spec),
+    }
+}
+
+fn check_arg_spec(spec: &Vec<Argument>, arguments: &Vec<Arg>, errors: &mut Errors) {
+    for item in spec.iter() {
+        let n = match *item {
+            Argument::Arg(ref n, _) | Argument::VarArgs(ref n) | Argument::KeywordArgs(ref n) => n,
+            _ => continue,
+        };
+        if !arguments.iter().any(|arg| arg.name.as_ref() == n.as_str()) {
+            errors.error(&syn::Ident::from(n.as_str()), "#[args] refers to unknown argument");
+        }
+    }
+}
+
+/// Build docstring prefix, i.e. `name($self, a, b=None)\n--\n\n`,
+/// that python parses into `__text_signature__`
+fn text_signature(name: &syn::Ident, fn_type: &FnType, arguments: &Vec<Arg>) -> String {
+    let mut params = Vec::new();
+    match *fn_type {
+        FnType::Fn => params.push("$self".to_owned()),
+        FnType::FnClass => params.push("$cls".to_owned()),
+        _ => (),
+    }
+
+    let param = |arg: &Arg| {
+        match arg.default {
+            Some(ref default) => format!("{}={}", arg.name, py_default_value(default)),
+            None => if arg.optional.is_some() {
+                format!("{}=None", arg.name)
+            } else {
+                arg.name.to_string()
+            },
+        }
+    };
+    let regular = |kind: ArgKind| {
+        arguments.iter()
+            .filter(move |arg| !arg.varargs && !arg.kwargs && arg.kind == kind)
+            .map(&param)
+            .collect::<Vec<String>>()
+    };
+
+    let pos_only = regular(ArgKind::PosOnly);
+    if !pos_only.is_empty() {
+        params.extend(pos_only);
+        params.push("/".to_owned());
+    }
+    params.extend(regular(ArgKind::Normal));
+
+    let kw_only = regular(ArgKind::KwOnly);
+    if let Some(arg) = arguments.iter().find(|arg| arg.varargs) {
+        params.push(format!("*{}", arg.name));
+    } else if !kw_only.is_empty() {
+        params.push("*".to_owned());
     }
+    params.extend(kw_only);
+
+    if let Some(arg) = arguments.iter().find(|arg| arg.kwargs) {
+        params.push(format!("**{}", arg.name));
+    }
+
+    format!("{}({})\n--\n\n", name, params.join(", "))
 }
 
-fn check_generic(name: &syn::Ident, sig: &syn::MethodSig) {
+/// Python representation of the default value, python only understands simple
+/// literals, so anything else is shown as `...`
+fn py_default_value(expr: &syn::Expr) -> String {
+    match expr.node {
+        syn::ExprKind::Lit(ref lit) => match *lit {
+            syn::Lit::Str(ref s, _) => format!("{:?}", s),
+            syn::Lit::Int(i, _) => i.to_string(),
+            syn::Lit::Float(ref f, _) => f.clone(),
+            syn::Lit::Bool(true) => "True".to_owned(),
+            syn::Lit::Bool(false) => "False".to_owned(),
+            _ => "...".to_owned(),
+        },
+        syn::ExprKind::Unary(syn::UnOp::Neg, ref expr) => {
+            match py_default_value(expr).as_str() {
+                "..." => "...".to_owned(),
+                value => format!("-{}", value),
+            }
+        },
+        syn::ExprKind::Path(None, ref path) if path.segments.len() == 1 &&
+            path.segments[0].ident.as_ref() == "None" => "None".to_owned(),
+        syn::ExprKind::Call(ref func, ref args) if args.len() == 1 => {
+            match func.node {
+                syn::ExprKind::Path(None, ref path) if path.segments.len() == 1 &&
+                    path.segments[0].ident.as_ref() == "Some" => py_default_value(&args[0]),
+                _ => "...".to_owned(),
+            }
+        },
+        _ => "...".to_owned(),
+    }
+}
+
+fn check_generic(name: &syn::Ident, sig: &syn::MethodSig, errors: &mut Errors) {
     if !sig.generics.ty_params.is_empty() {
-        panic!("python method can not be generic: {:?}", name);
+        errors.error(name, "python method can not be generic");
     }
 }
 
-fn check_arg_ty_and_optional<'a>(name: &'a syn::Ident, ty: &'a syn::Ty) -> Option<&'a syn::Ty> {
+fn check_arg_ty_and_optional<'a>(ty: &'a syn::Ty, errors: &mut Errors) -> Option<&'a syn::Ty> {
     match ty {
         &syn::Ty::Path(ref qs, ref path) => {
-            if let &Some(ref qs) = qs {
-                panic!("explicit Self type in a 'qualified path' is not supported: {:?} - {:?}",
-                       name, qs);
+            if let &Some(_) = qs {
+                errors.error(ty, "explicit Self type in a 'qualified path' is not supported");
+                return None
             }
 
             if let Some(segment) = path.segments.last() {
                 match segment.ident.as_ref() {
                     "Option" => {
                         match segment.parameters {
-                            syn::PathParameters::AngleBracketed(ref params) => {
-                                if params.types.len() != 1 {
-                                    panic!("argument type is not supported by python method: {:?} ({:?}) {:?}",
-                                           for_err_msg(name),
-                                           for_err_msg(ty),
-                                           for_err_msg(path));
-                                }
-                                Some(&params.types[0])
-                            },
+                            syn::PathParameters::AngleBracketed(ref params)
+                                if params.types.len() == 1 => Some(&params.types[0]),
                             _ => {
-                                panic!("argument type is not supported by python method: {:?} ({:?}) {:?}",
-                                       for_err_msg(name),
-                                       for_err_msg(ty),
-                                       for_err_msg(path));
+                                errors.error(ty, "argument type is not supported by python method");
+                                None
                             }
                         }
                     },
@@ -198,19 +351,14 @@ fn check_arg_ty_and_optional<'a>(name: &'a syn::Ident, ty: &'a syn::Ty) -> Optio
                 None
             }
         },
-        _ => {
-            None
-            //panic!("argument type is not supported by python method: {:?} ({:?})",
-            //for_err_msg(name),
-            //for_err_msg(ty));
-        },
+        _ => None,
     }
 }
 
 /// Generate functiona wrapper (PyCFunction, PyCFunctionWithKeywords)
-fn impl_wrap(cls: &Box<syn::Ty>, name: &syn::Ident, args: Vec<Arg>) -> Tokens {
-    let cb = impl_call(cls, name, &args);
-    let body = impl_arg_params(args, cb);
+fn impl_wrap(cls: &Box<syn::Ty>, name: &syn::Ident, mutable: bool, args: Vec<Arg>) -> Tokens {
+    let cb = impl_call(cls, name, mutable, &args);
+    let body = impl_arg_params(cls, name, args, cb);
 
     quote! {
         unsafe extern "C" fn wrap
@@ -238,9 +386,119 @@ fn impl_wrap(cls: &Box<syn::Ty>, name: &syn::Ident, args: Vec<Arg>) -> Tokens {
     }
 }
 
+/// Generate function wrapper for ffi::newfunc (tp_new slot)
+fn impl_wrap_new(cls: &Box<syn::Ty>, name: &syn::Ident, args: Vec<Arg>) -> Tokens {
+    let cb = impl_call_class(cls, name, &args);
+    let body = impl_arg_params(cls, name, args, cb);
+
+    quote! {
+        unsafe extern "C" fn wrap
+            (cls: *mut pyo3::ffi::PyTypeObject,
+             args: *mut pyo3::ffi::PyObject,
+             kwargs: *mut pyo3::ffi::PyObject) -> *mut pyo3::ffi::PyObject
+        {
+            const LOCATION: &'static str = concat!(
+                stringify!(#cls), ".", stringify!(#name), "()");
+            pyo3::callback::handle_callback(
+                LOCATION, pyo3::callback::PyObjectCallbackConverter, |py|
+                {
+                    let cls = pyo3::PyType::from_type_ptr(py, cls);
+                    let args: pyo3::PyTuple =
+                        pyo3::PyObject::from_borrowed_ptr(py, args).unchecked_cast_into();
+                    let kwargs: Option<pyo3::PyDict> = pyo3::argparse::get_kwargs(py, kwargs);
+
+                    let ret = {
+                        #body
+                    };
+                    pyo3::PyDrop::release_ref(cls, py);
+                    pyo3::PyDrop::release_ref(args, py);
+                    pyo3::PyDrop::release_ref(kwargs, py);
+                    ret
+                })
+        }
+    }
+}
+/// Generate class method wrapper (PyCFunction, PyCFunctionWithKeywords)
+fn impl_wrap_class(cls: &Box<syn::Ty>, name: &syn::Ident, args: Vec<Arg>) -> Tokens {
+    let cb = impl_call_class(cls, name, &args);
+    let body = impl_arg_params(cls, name, args, cb);
+
+    quote! {
+        unsafe extern "C" fn wrap
+            (cls: *mut pyo3::ffi::PyObject,
+             args: *mut pyo3::ffi::PyObject,
+             kwargs: *mut pyo3::ffi::PyObject) -> *mut pyo3::ffi::PyObject
+        {
+            const LOCATION: &'static str = concat!(
+                stringify!(#cls), ".", stringify!(#name), "()");
+            pyo3::callback::handle_callback(
+                LOCATION, pyo3::callback::PyObjectCallbackConverter, |py|
+                {
+                    let cls = pyo3::PyObject::from_borrowed_ptr(py, cls)
+                        .unchecked_cast_into::<pyo3::PyType>();
+                    let args: pyo3::PyTuple =
+                        pyo3::PyObject::from_borrowed_ptr(py, args).unchecked_cast_into();
+                    let kwargs: Option<pyo3::PyDict> = pyo3::argparse::get_kwargs(py, kwargs);
+
+                    let ret = {
+                        #body
+                    };
+                    pyo3::PyDrop::release_ref(cls, py);
+                    pyo3::PyDrop::release_ref(args, py);
+                    pyo3::PyDrop::release_ref(kwargs, py);
+                    ret
+                })
+        }
+    }
+}
+
+/// Generate static method wrapper (PyCFunction, PyCFunctionWithKeywords)
+fn impl_wrap_static(cls: &Box<syn::Ty>, name: &syn::Ident, args: Vec<Arg>) -> Tokens {
+    let names: Vec<&syn::Ident> = args.iter().map(|item| item.name).collect();
+    let cb = quote! {{
+        #cls::#name(py, #(#names),*)
+    }};
+    let body = impl_arg_params(cls, name, args, cb);
+
+    quote! {
+        unsafe extern "C" fn wrap
+            (_slf: *mut pyo3::ffi::PyObject,
+             args: *mut pyo3::ffi::PyObject,
+             kwargs: *mut pyo3::ffi::PyObject) -> *mut pyo3::ffi::PyObject
+        {
+            const LOCATION: &'static str = concat!(
+                stringify!(#cls), ".", stringify!(#name), "()");
+            pyo3::callback::handle_callback(
+                LOCATION, pyo3::callback::PyObjectCallbackConverter, |py|
+                {
+                    let args: pyo3::PyTuple =
+                        pyo3::PyObject::from_borrowed_ptr(py, args).unchecked_cast_into();
+                    let kwargs: Option<pyo3::PyDict> = pyo3::argparse::get_kwargs(py, kwargs);
+
+                    let ret = {
+                        #body
+                    };
+                    pyo3::PyDrop::release_ref(args, py);
+                    pyo3::PyDrop::release_ref(kwargs, py);
+                    ret
+                })
+        }
+    }
+}
 
 /// Generate functiona wrapper (PyCFunction, PyCFunctionWithKeywords)
-fn impl_wrap_getter(cls: &Box<syn::Ty>, name: &syn::Ident, _args: Vec<Arg>) -> Tokens {
+fn impl_wrap_getter(cls: &Box<syn::Ty>, name: &syn::Ident, mutable: bool, _args: Vec<Arg>)
+                    -> Tokens
+{
+    let slf = self_binding(mutable);
+    let call = impl_borrow_self(cls, mutable, quote! {{
+        let #slf = pyo3::PyObject::from_borrowed_ptr(
+            py, slf).unchecked_cast_into::<#cls>();
+        let ret = slf.#name(py);
+        pyo3::PyDrop::release_ref(slf, py);
+        ret
+    }});
+
     quote! {
         unsafe extern "C" fn wrap (slf: *mut pyo3::ffi::PyObject,
                                    _: *mut pyo3::c_void)
@@ -251,18 +509,25 @@ fn impl_wrap_getter(cls: &Box<syn::Ty>, name: &syn::Ident, _args: Vec<Arg>) -> T
             pyo3::callback::handle_callback(
                 LOCATION, pyo3::callback::PyObjectCallbackConverter, |py|
                 {
-                    let slf = pyo3::PyObject::from_borrowed_ptr(
-                        py, slf).unchecked_cast_into::<#cls>();
-                    let ret = slf.#name(py);
-                    pyo3::PyDrop::release_ref(slf, py);
-                    ret
+                    #call
                 })
         }
     }
 }
 
 /// Generate functiona wrapper (PyCFunction, PyCFunctionWithKeywords)
-fn impl_wrap_setter(cls: &Box<syn::Ty>, name: &syn::Ident, _args: Vec<Arg>) -> Tokens {
+fn impl_wrap_setter(cls: &Box<syn::Ty>, name: &syn::Ident, mutable: bool, _args: Vec<Arg>)
+                    -> Tokens
+{
+    let slf = self_binding(mutable);
+    let call = impl_borrow_self(cls, mutable, quote! {{
+        let #slf = pyo3::PyObject::from_borrowed_ptr(py, slf)
+            .unchecked_cast_into::<#cls>();
+        let ret = slf.#name(py, &value);
+        pyo3::PyDrop::release_ref(slf, py);
+        ret
+    }});
+
     quote! {
         unsafe extern "C" fn wrap(slf: *mut pyo3::ffi::PyObject,
                                   value: *mut pyo3::ffi::PyObject,
@@ -273,11 +538,8 @@ fn impl_wrap_setter(cls: &Box<syn::Ty>, name: &syn::Ident, _args: Vec<Arg>) -> T
             pyo3::callback::handle_callback(
                 LOCATION, pyo3::callback::UnitCallbackConverter, |py|
                 {
-                    let slf = pyo3::PyObject::from_borrowed_ptr(py, slf)
-                        .unchecked_cast_into::<#cls>();
                     let value = pyo3::PyObject::from_borrowed_ptr(py, value);
-                    let ret = slf.#name(py, &value);
-                    pyo3::PyDrop::release_ref(slf, py);
+                    let ret = #call;
                     pyo3::PyDrop::release_ref(value, py);
                     ret.map(|o| ())
                 })
@@ -286,36 +548,66 @@ fn impl_wrap_setter(cls: &Box<syn::Ty>, name: &syn::Ident, _args: Vec<Arg>) -> T
 }
 
 
-fn impl_call(cls: &Box<syn::Ty>, fname: &syn::Ident, args: &Vec<Arg>) -> Tokens {
+fn impl_call(cls: &Box<syn::Ty>, fname: &syn::Ident, mutable: bool, args: &Vec<Arg>) -> Tokens {
+    let names: Vec<&syn::Ident> = args.iter().map(|item| item.name).collect();
+    let slf = self_binding(mutable);
+    impl_borrow_self(cls, mutable, quote! {{
+        let #slf = pyo3::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<#cls>();
+        let ret = slf.#fname(py, #(#names),*);
+        pyo3::PyDrop::release_ref(slf, py);
+        ret
+    }})
+}
+
+/// Borrow instance data for the duration of `call`, conflicting borrow
+/// (i.e. reentrant call of `&mut self` method) raises `RuntimeError`
+fn impl_borrow_self(cls: &Box<syn::Ty>, mutable: bool, call: Tokens) -> Tokens {
+    let mutable = bool_ident(mutable);
+    quote! {
+        match <#cls as pyo3::class::cell::PyCellProtocolImpl>::borrow_data(py, slf, #mutable) {
+            Ok(_borrow) => #call,
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn self_binding(mutable: bool) -> Tokens {
+    if mutable { quote! { mut slf } } else { quote! { slf } }
+}
+
+fn impl_call_class(cls: &Box<syn::Ty>, fname: &syn::Ident, args: &Vec<Arg>) -> Tokens {
     let names: Vec<&syn::Ident> = args.iter().map(|item| item.name).collect();
     quote! {
         {
-            let slf = pyo3::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<#cls>();
-            let ret = slf.#fname(py, #(#names),*);
-            pyo3::PyDrop::release_ref(slf, py);
-            ret
+            #cls::#fname(&cls, py, #(#names),*)
         }
     }
 }
 
-fn impl_arg_params(mut args: Vec<Arg>, body: Tokens) -> Tokens {
+fn impl_arg_params(cls: &Box<syn::Ty>, fname: &syn::Ident, mut args: Vec<Arg>, body: Tokens)
+                   -> Tokens
+{
     let mut params = Vec::new();
 
     for arg in args.iter() {
+        if arg.varargs || arg.kwargs {
+            continue
+        }
         let name = arg.name.as_ref();
-        let opt = if let Some(_) = arg.optional {
-            syn::Ident::from("true")
-        } else {
-            syn::Ident::from("false")
-        };
+        let opt = bool_ident(arg.optional.is_some() || arg.default.is_some());
+        let kw_only = bool_ident(arg.kind == ArgKind::KwOnly);
+        let pos_only = bool_ident(arg.kind == ArgKind::PosOnly);
         params.push(
             quote! {
-                pyo3::argparse::ParamDescription{name: #name, is_optional: #opt,}
+                pyo3::argparse::ParamDescription{
+                    name: #name, is_optional: #opt, kw_only: #kw_only, pos_only: #pos_only,}
             }
         );
     }
     let placeholders: Vec<syn::Ident> = params.iter().map(
         |_| syn::Ident::from("None")).collect();
+    let accept_args = args.iter().any(|arg| arg.varargs);
+    let accept_kwargs = args.iter().any(|arg| arg.kwargs);
 
     // generate extrat args
     args.reverse();
@@ -324,6 +616,30 @@ fn impl_arg_params(mut args: Vec<Arg>, body: Tokens) -> Tokens {
         body = impl_arg_param(&arg, &body);
     }
 
+    // collect extra positional and keyword arguments
+    if accept_kwargs {
+        body = quote! {
+            match pyo3::argparse::varkwargs(py, PARAMS, kwargs.as_ref()) {
+                Ok(_varkwargs) => {
+                    let ret = #body;
+                    pyo3::PyDrop::release_ref(_varkwargs, py);
+                    ret
+                },
+                Err(err) => Err(err)
+            }
+        };
+    }
+    if accept_args {
+        body = quote! {{
+            let _varargs = pyo3::argparse::varargs(py, PARAMS, &args);
+            let ret = #body;
+            pyo3::PyDrop::release_ref(_varargs, py);
+            ret
+        }};
+    }
+    let accept_args = bool_ident(accept_args);
+    let accept_kwargs = bool_ident(accept_kwargs);
+
     // create array of arguments, and then parse
     quote! {
         const PARAMS: &'static [pyo3::argparse::ParamDescription<'static>] = &[
@@ -332,7 +648,8 @@ fn impl_arg_params(mut args: Vec<Arg>, body: Tokens) -> Tokens {
 
         let mut output = [#(#placeholders),*];
         match pyo3::argparse::parse_args(
-            py, Some(LOCATION), PARAMS, &args, kwargs.as_ref(), &mut output) {
+            py, Some(concat!(stringify!(#cls), ".", stringify!(#fname))),
+            PARAMS, &args, kwargs.as_ref(), #accept_args, #accept_kwargs, &mut output) {
             Ok(_) => {
                 let mut _iter = output.iter();
 
@@ -347,20 +664,32 @@ fn impl_arg_param(arg: &Arg, body: &Tokens) -> Tokens {
     let ty = arg.ty;
     let name = arg.name;
 
+    if arg.varargs {
+        return quote! {{
+            let #name: #ty = &_varargs;
+            #body
+        }}
+    }
+    if arg.kwargs {
+        return quote! {{
+            let #name: #ty = _varkwargs.as_ref();
+            #body
+        }}
+    }
+
     // First unwrap() asserts the iterated sequence is long enough (which should be guaranteed);
     // second unwrap() asserts the parameter was not missing (which fn
     // parse_args already checked for).
 
-    if let Some(ref opt_ty) = arg.optional {
+    if arg.default.is_some() || arg.optional.is_some() {
+        let default = match arg.default {
+            Some(ref default) => quote! { #default },
+            None => quote! { None },
+        };
         quote! {
             match match _iter.next().unwrap().as_ref() {
-                Some(obj) => {
-                    match <#opt_ty as pyo3::FromPyObject>::extract(py, obj) {
-                        Ok(obj) => Ok(Some(obj)),
-                        Err(e) => Err(e),
-                    }
-                },
-                None => Ok(None)
+                Some(obj) => <#ty as pyo3::FromPyObject>::extract(py, obj),
+                None => Ok(#default)
             } {
                 Ok(#name) => #body,
                 Err(e) => Err(e)
@@ -380,7 +709,15 @@ fn impl_arg_param(arg: &Arg, body: &Tokens) -> Tokens {
     }
 }
 
-fn impl_py_method_def(name: &syn::Ident, wrapper: &Tokens) -> Tokens {
+fn bool_ident(value: bool) -> syn::Ident {
+    if value {
+        syn::Ident::from("true")
+    } else {
+        syn::Ident::from("false")
+    }
+}
+
+fn impl_py_method_def(name: &syn::Ident, doc: &str, wrapper: &Tokens) -> Tokens {
     quote! {
         pyo3::class::PyMethodDefType::Method({
             #wrapper
@@ -389,23 +726,94 @@ fn impl_py_method_def(name: &syn::Ident, wrapper: &Tokens) -> Tokens {
                 ml_name: stringify!(#name),
                 ml_meth: pyo3::class::PyMethodType::PyCFunctionWithKeywords(wrap),
                 ml_flags: pyo3::ffi::METH_VARARGS | pyo3::ffi::METH_KEYWORDS,
-                ml_doc: "",
+                ml_doc: #doc,
             }
         })
     }
 }
 
-fn impl_py_setter_def(name: &syn::Ident, setter: Option<String>, wrapper: &Tokens) -> Tokens {
-    let n = if let Some(ref name) = setter {
-        name.to_string()
-    } else {
-        let n = String::from(name.as_ref());
-        if n.starts_with("set_") {
-            n[4..].to_string()
-        } else {
-            n
-        }
-    };
+fn impl_py_method_def_new(name: &syn::Ident, doc: &str, wrapper: &Tokens) -> Tokens {
+    quote! {
+        pyo3::class::PyMethodDefType::New({
+            #wrapper
+
+            pyo3::class::PyMethodDef {
+                ml_name: stringify!(#name),
+                ml_meth: pyo3::class::PyMethodType::PyNewFunc(wrap),
+                ml_flags: pyo3::ffi::METH_VARARGS | pyo3::ffi::METH_KEYWORDS,
+                ml_doc: #doc,
+            }
+        })
+    }
+}
+
+fn impl_py_method_def_call(name: &syn::Ident, doc: &str, wrapper: &Tokens) -> Tokens {
+    quote! {
+        pyo3::class::PyMethodDefType::Call({
+            #wrapper
+
+            pyo3::class::PyMethodDef {
+                ml_name: stringify!(#name),
+                ml_meth: pyo3::class::PyMethodType::PyCFunctionWithKeywords(wrap),
+                ml_flags: pyo3::ffi::METH_VARARGS | pyo3::ffi::METH_KEYWORDS,
+                ml_doc: #doc,
+            }
+        })
+    }
+}
+
+fn impl_py_method_def_class(name: &syn::Ident, doc: &str, wrapper: &Tokens) -> Tokens {
+    quote! {
+        pyo3::class::PyMethodDefType::Class({
+            #wrapper
+
+            pyo3::class::PyMethodDef {
+                ml_name: stringify!(#name),
+                ml_meth: pyo3::class::PyMethodType::PyCFunctionWithKeywords(wrap),
+                ml_flags: pyo3::ffi::METH_VARARGS | pyo3::ffi::METH_KEYWORDS |
+                    pyo3::ffi::METH_CLASS,
+                ml_doc: #doc,
+            }
+        })
+    }
+}
+
+fn impl_py_method_def_static(name: &syn::Ident, doc: &str, wrapper: &Tokens) -> Tokens {
+    quote! {
+        pyo3::class::PyMethodDefType::Static({
+            #wrapper
+
+            pyo3::class::PyMethodDef {
+                ml_name: stringify!(#name),
+                ml_meth: pyo3::class::PyMethodType::PyCFunctionWithKeywords(wrap),
+                ml_flags: pyo3::ffi::METH_VARARGS | pyo3::ffi::METH_KEYWORDS |
+                    pyo3::ffi::METH_STATIC,
+                ml_doc: #doc,
+            }
+        })
+    }
+}
+
+/// Class attribute, `value` is evaluated once during type initialization.
+pub fn impl_py_class_attribute_def(name: &syn::Ident, value: &Tokens) -> Tokens {
+    quote! {
+        pyo3::class::PyMethodDefType::ClassAttribute({
+            fn wrap(py: pyo3::Python) -> pyo3::PyObject {
+                pyo3::ToPyObject::to_py_object(&#value, py)
+            }
+
+            pyo3::class::PyClassAttributeDef {
+                name: stringify!(#name),
+                meth: wrap,
+            }
+        })
+    }
+}
+
+fn impl_py_setter_def(name: &syn::Ident, doc: &str, setter: Option<String>, wrapper: &Tokens)
+                      -> Tokens
+{
+    let n = setter_name(name, setter);
 
     quote! {
         pyo3::class::PyMethodDefType::Setter({
@@ -414,23 +822,16 @@ fn impl_py_setter_def(name: &syn::Ident, setter: Option<String>, wrapper: &Token
             pyo3::class::PySetterDef {
                 name: #n,
                 meth: wrap,
-                doc: "",
+                doc: #doc,
             }
         })
     }
 }
 
-fn impl_py_getter_def(name: &syn::Ident, getter: Option<String>, wrapper: &Tokens) -> Tokens {
-    let n = if let Some(ref name) = getter {
-        name.to_string()
-    } else {
-        let n = String::from(name.as_ref());
-        if n.starts_with("get_") {
-            n[4..].to_string()
-        } else {
-            n
-        }
-    };
+fn impl_py_getter_def(name: &syn::Ident, doc: &str, getter: Option<String>, wrapper: &Tokens)
+                      -> Tokens
+{
+    let n = getter_name(name, getter);
 
     quote! {
         pyo3::class::PyMethodDefType::Getter({
@@ -439,8 +840,74 @@ fn impl_py_getter_def(name: &syn::Ident, getter: Option<String>, wrapper: &Token
             pyo3::class::PyGetterDef {
                 name: #n,
                 meth: wrap,
-                doc: "",
+                doc: #doc,
             }
         })
     }
 }
+
+fn setter_name(name: &syn::Ident, setter: Option<String>) -> String {
+    if let Some(name) = setter {
+        name
+    } else {
+        let n = String::from(name.as_ref());
+        if n.starts_with("set_") {
+            n[4..].to_string()
+        } else {
+            n
+        }
+    }
+}
+
+fn getter_name(name: &syn::Ident, getter: Option<String>) -> String {
+    if let Some(name) = getter {
+        name
+    } else {
+        let n = String::from(name.as_ref());
+        if n.starts_with("get_") {
+            n[4..].to_string()
+        } else {
+            n
+        }
+    }
+}
+
+/// Kind (`"getter"` or `"setter"`) and python name of the property defined by method,
+/// attribute errors are reported by `gen_py_method`.
+pub fn property_name(name: &syn::Ident, meth_attrs: &Vec<syn::Attribute>)
+                     -> Option<(&'static str, String)>
+{
+    match parse_attributes(&mut meth_attrs.clone(), &mut Errors::new()).0 {
+        FnType::Getter(getter) => Some(("getter", getter_name(name, getter))),
+        FnType::Setter(setter) => Some(("setter", setter_name(name, setter))),
+        _ => None,
+    }
+}
+
+/// Hidden associated constant for every property getter and setter of the class.
+/// Properties of different `#[methods]` blocks and `#[prop]` fields are merged by name,
+/// so rustc reports duplicate definitions if the same property is defined twice.
+pub fn impl_property_guards<T: ToTokens>(cls: &T, props: &Vec<(&'static str, String)>) -> Tokens {
+    let guards: Vec<syn::Ident> = props.iter()
+        // only names that are valid in rust identifier can be checked
+        .filter(|&&(_, ref name)| name.chars().all(|c| match c {
+            'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => true,
+            _ => false,
+        }))
+        .map(|&(kind, ref name)| syn::Ident::new(format!("__pyo3_{}_{}", kind, name)))
+        .collect();
+
+    if guards.is_empty() {
+        return Tokens::new()
+    }
+
+    quote! {
+        #[allow(dead_code, non_upper_case_globals)]
+        impl #cls {
+            #(
+                #[doc(hidden)]
+                const #guards: () = ();
+            )*
+        }
+    }
+}
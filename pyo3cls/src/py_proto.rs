@@ -1,189 +1,117 @@
 // Copyright (c) 2017-present PyO3 Project and Contributors
 
 use syn;
-use quote::{Tokens, ToTokens};
+use quote::Tokens;
 
+use defs;
 use py_method;
+use utils::Errors;
 
 
-struct Methods {
-    methods: &'static [&'static str],
-    non_pyobj_result: &'static [&'static str],
-    no_adjust: bool,
-}
-
-static DEFAULT_METHODS: Methods = Methods {
-    methods: &[],
-    non_pyobj_result: &[],
-    no_adjust: true,
-};
-
-static BUFFER_METHODS: Methods = Methods {
-    methods: &[],
-    non_pyobj_result: &["bf_getbuffer", "bf_releasebuffer"],
-    no_adjust: false,
-};
-
-static GC_METHODS: Methods = Methods {
-    methods: &[],
-    non_pyobj_result: &["__traverse__", "__clear__"],
-    no_adjust: false,
-};
-
-static CONTEXT_METHODS: Methods = Methods {
-    methods: &["__enter__", "__exit__"],
-    non_pyobj_result: &[],
-    no_adjust: false,
-};
-
-static DESCR_METHODS: Methods = Methods {
-    methods: &["__delete__", "__set_name__"],
-    non_pyobj_result: &[],
-    no_adjust: true,
-};
-
-static MAPPING_METHODS: Methods = Methods {
-    methods: &[],
-    non_pyobj_result: &["__setitem__", "__len__"],
-    no_adjust: false,
-};
-
-static NUM_METHODS: Methods = Methods {
-    methods: &[
-        "__radd__", "__rsub__", "__rmul__", "__rmatmul__", "__rtruediv__",
-        "__rfloordiv__", "__rmod__", "__rdivmod__", "__rpow__", "__rlshift__",
-        "__rrshift__", "__rand__", "__rxor__", "__ror__", "__complex__",
-        "__round__"
-    ],
-    non_pyobj_result: &[],
-    no_adjust: true,
-};
-
-
-enum ImplType {
-    Object,
-    Async,
-    Buffer,
-    Context,
-    Descr,
-    GC,
-    Mapping,
-    Sequence,
-    Number,
-}
-
-pub fn build_py_proto(ast: &mut syn::Item) -> Tokens {
+pub fn build_py_proto(ast: &mut syn::Item, errors: &mut Errors) -> Tokens {
     match ast.node {
         syn::ItemKind::Impl(_, _, _, ref path, ref ty, ref mut impl_items) => {
             if let &Some(ref path) = path {
-                match process_path(path) {
-                    ImplType::Object =>
-                        impl_protocol("pyo3::class::async::PyObjectProtocolImpl",
-                                      path.clone(), ty, impl_items, &DEFAULT_METHODS),
-                    ImplType::Async =>
-                        impl_protocol("pyo3::class::async::PyAsyncProtocolImpl",
-                                      path.clone(), ty, impl_items, &DEFAULT_METHODS),
-                    ImplType::Buffer =>
-                        impl_protocol("pyo3::class::buffer::PyBufferProtocolImpl",
-                                      path.clone(), ty, impl_items, &BUFFER_METHODS),
-                    ImplType::Context =>
-                        impl_protocol("pyo3::class::context::PyContextProtocolImpl",
-                                      path.clone(), ty, impl_items, &CONTEXT_METHODS),
-                    ImplType::Descr =>
-                        impl_protocol("pyo3::class::descr::PyDescrProtocolImpl",
-                                      path.clone(), ty, impl_items, &DESCR_METHODS),
-                    ImplType::GC =>
-                        impl_protocol("pyo3::class::gc::PyGCProtocolImpl",
-                                      path.clone(), ty, impl_items, &GC_METHODS),
-                    ImplType::Mapping =>
-                        impl_protocol("pyo3::class::mapping::PyMappingProtocolImpl",
-                                      path.clone(), ty, impl_items, &MAPPING_METHODS),
-                    ImplType::Sequence =>
-                        impl_protocol("pyo3::class::mapping::PySequenceProtocolImpl",
-                                      path.clone(), ty, impl_items, &DEFAULT_METHODS),
-                    ImplType::Number =>
-                        impl_protocol("pyo3::class::number::PyNumberProtocolImpl",
-                                      path.clone(), ty, impl_items, &NUM_METHODS),
-                }
+                let name = match path.segments.last() {
+                    Some(segment) => segment.ident.as_ref().to_owned(),
+                    None => String::new(),
+                };
+                let tokens = match name.as_ref() {
+                    "PyObjectProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::OBJECT, errors),
+                    "PyAsyncProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::ASYNC, errors),
+                    "PyContextProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::CONTEXT, errors),
+                    "PyDescrProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::DESCR, errors),
+                    "PyIterProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::ITER, errors),
+                    "PyMappingProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::MAPPING, errors),
+                    "PySequenceProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::SEQUENCE, errors),
+                    "PyNumberProtocol" =>
+                        impl_proto_impl(&name, ty, impl_items, &defs::NUMBER, errors),
+                    // buffer slots are filled for any implementation of the trait
+                    "PyBufferProtocol" => Tokens::new(),
+                    // pickle methods are added by `#[class(pickle)]`
+                    "PyPickleProtocol" => Tokens::new(),
+                    "PyGCProtocol" =>
+                        impl_methods_impl("pyo3::class::gc::PyGCProtocolImpl",
+                                          ty, impl_items),
+                    _ => {
+                        errors.error(path, "#[proto] can not be used with this block");
+                        return Tokens::new()
+                    }
+                };
+                dummy_const(&name, ty, tokens)
             } else {
-                panic!("#[proto] can only be used with protocol trait implementations")
+                errors.error(ty, "#[proto] can only be used with protocol trait implementations");
+                Tokens::new()
             }
         },
-        _ => panic!("#[proto] can only be used with Impl blocks"),
+        _ => {
+            errors.error(&ast.ident, "#[proto] can only be used with Impl blocks");
+            Tokens::new()
+        },
     }
 }
 
-fn process_path(path: &syn::Path) -> ImplType {
-    if let Some(segment) = path.segments.last() {
-            match segment.ident.as_ref() {
-                "PyObjectProtocol" => ImplType::Object,
-                "PyAsyncProtocol" => ImplType::Async,
-                "PyBufferProtocol" => ImplType::Buffer,
-                "PyContextProtocol" => ImplType::Context,
-                "PyDescrProtocol" => ImplType::Descr,
-                "PyGCProtocol" => ImplType::GC,
-                "PyMappingProtocol" => ImplType::Mapping,
-                "PySequenceProtocol" => ImplType::Sequence,
-                "PyNumberProtocol" => ImplType::Number,
-                _ => panic!("#[proto] can not be used with this block"),
-            }
-    } else {
-        panic!("#[proto] can not be used with this block");
+fn dummy_const(name: &str, ty: &Box<syn::Ty>, tokens: Tokens) -> Tokens {
+    let dummy_const = match **ty {
+        syn::Ty::Path(_, ref path) => syn::Ident::new(
+            format!("_IMPL_PYO3_{}_{}", name, path.segments.last().unwrap().ident)),
+        _ => syn::Ident::new(format!("_IMPL_PYO3_{}", name)),
+    };
+    quote! {
+        #[feature(specialization)]
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+        const #dummy_const: () = {
+            extern crate pyo3;
+            use pyo3::ffi;
+
+            #tokens
+        };
     }
 }
 
-fn impl_protocol(name: &'static str,
-                 path: syn::Path, ty: &Box<syn::Ty>,
-                 impls: &mut Vec<syn::ImplItem>, methods: &Methods) -> Tokens {
+/// Implement marker trait of each protocol method, argument and result types
+/// of the marker trait are taken from method signature. Methods without type slot
+/// are added to the type as python methods.
+fn impl_proto_impl(name: &str, ty: &Box<syn::Ty>, impls: &mut Vec<syn::ImplItem>,
+                   proto: &defs::Proto, errors: &mut Errors) -> Tokens {
+    let mut tokens = Tokens::new();
     let mut py_methods = Vec::new();
 
-    // get method names in impl block
-    let mut meth = Vec::new();
     for iimpl in impls.iter_mut() {
-        match iimpl.node {
-            syn::ImplItemKind::Method(ref mut sig, ref mut block) => {
-                if methods.methods.contains(&iimpl.ident.as_ref()) {
-                    py_methods.push(py_method::gen_py_method(
-                        ty, &iimpl.ident, sig, block, &mut iimpl.attrs));
-                } else {
-                    meth.push(String::from(iimpl.ident.as_ref()));
+        // unknown methods are reported by rustc
+        let meth = match proto.methods.iter().find(|m| m.name == iimpl.ident.as_ref()) {
+            Some(meth) => meth,
+            None => continue,
+        };
+        if let syn::ImplItemKind::Method(ref mut sig, ref mut block) = iimpl.node {
+            tokens.append_all(&[impl_method_proto(ty, proto, meth, sig, errors)]);
 
-                    // adjust return type
-                    if !methods.non_pyobj_result.contains(&iimpl.ident.as_ref()) &&
-                        !methods.no_adjust {
-                        impl_adjust_result(sig, block);
-                    }
-                }
-            },
-            _ => (),
+            match meth.py_method {
+                defs::PyMethod::Slot => (),
+                defs::PyMethod::Signature =>
+                    py_methods.push(py_method::gen_py_method(
+                        ty, &iimpl.ident, sig, block, &mut iimpl.attrs, errors)),
+                defs::PyMethod::Wrapper(wrapper) =>
+                    py_methods.push(impl_wrapper_def(ty, proto, meth, wrapper)),
+            }
         }
     }
 
-    // set trait name
-    let mut path = path;
-    {
-        let mut last = path.segments.last_mut().unwrap();
-        last.ident = syn::Ident::from(name);
-    }
-
-    let i = syn::Ident::from(name);
-    let tokens = if py_methods.is_empty() {
-        quote! {
-            impl #i for #ty {
-                fn methods() -> &'static [&'static str] {
-                    static METHODS: &'static [&'static str] = &[#(#meth),*];
-                    METHODS
-                }
-            }
-        }
-    } else {
-        quote! {
-            impl #i for #ty {
-                fn methods() -> &'static [&'static str] {
-                    static METHODS: &'static [&'static str] = &[#(#meth,),*];
-                    METHODS
-                }
+    if !py_methods.is_empty() {
+        let trait_path = syn::Ident::from(format!("{}::{}", proto.module, name));
+        let methods_impl = syn::Ident::from(
+            format!("{}::{}", proto.module, proto.py_methods_impl));
+        tokens.append_all(&[quote! {
+            use #trait_path;
 
+            impl #methods_impl for #ty {
                 fn py_methods() -> &'static [pyo3::class::PyMethodDefType] {
                     static METHODS: &'static [pyo3::class::PyMethodDefType] = &[
                         #(#py_methods),*
@@ -191,84 +119,148 @@ fn impl_protocol(name: &'static str,
                     METHODS
                 }
             }
+        }]);
+    }
+
+    tokens
+}
+
+fn impl_method_proto(ty: &Box<syn::Ty>, proto: &defs::Proto, meth: &defs::MethodProto,
+                     sig: &syn::MethodSig, errors: &mut Errors) -> Tokens {
+    // arguments after `&self` and `py: Python`
+    let args: Vec<&syn::Ty> = sig.decl.inputs.iter()
+        .filter_map(|arg| match *arg {
+            syn::FnArg::Captured(_, ref ty) => Some(ty),
+            _ => None,
+        })
+        .skip(1)
+        .collect();
+    // signature of the method, reported with errors that are not related to single argument
+    let signature = {
+        let name = syn::Ident::from(meth.name);
+        let inputs = &sig.decl.inputs;
+        quote! { fn #name(#(#inputs),*) }
+    };
+    if args.len() != meth.args.len() {
+        errors.error(&signature, &format!(
+            "#[proto] `{}` requires {} argument(s) after `py: Python`, got {}",
+            meth.name, meth.args.len(), args.len()));
+        return Tokens::new()
+    }
+
+    let mut types = Vec::new();
+    for (arg, ty) in meth.args.iter().zip(args.into_iter()) {
+        let (name, ty) = match *arg {
+            defs::Arg::Type(name) => (name, ty),
+            defs::Arg::Optional(name) => match option_inner(ty) {
+                Some(inner) => (name, inner),
+                None => {
+                    errors.error(ty, &format!(
+                        "#[proto] argument of `{}` has to be `Option<_>`", meth.name));
+                    continue
+                },
+            },
+            defs::Arg::Fixed => continue,
+        };
+        if let syn::Ty::Rptr(..) = *ty {
+            errors.error(ty, &format!(
+                "#[proto] argument of `{}` can not be a reference, use owned type", meth.name));
+            continue
         }
+        let name = syn::Ident::from(name);
+        types.push(quote! { type #name = #ty; });
+    }
+
+    let result = match sig.decl.output {
+        syn::FunctionRetTy::Ty(ref ty) => ty,
+        syn::FunctionRetTy::Default => {
+            errors.error(&signature, &format!(
+                "#[proto] `{}` has to return `PyResult<_>`", meth.name));
+            return Tokens::new()
+        },
     };
-    let name = name.split("::").last().unwrap();
-    let dummy_const = syn::Ident::new(format!("_IMPL_PYO3_{}", name));
+    let success = match meth.ret {
+        defs::Ret::Plain => None,
+        defs::Ret::Success => Some((result_inner(result), "PyResult<_>")),
+        defs::Ret::OptionalSuccess =>
+            Some((result_inner(result).and_then(option_inner), "PyResult<Option<_>>")),
+    };
+    if let Some((success, expected)) = success {
+        match success {
+            Some(success) => types.push(quote! { type Success = #success; }),
+            None => {
+                errors.error(result, &format!(
+                    "#[proto] `{}` has to return `{}`", meth.name, expected));
+                return Tokens::new()
+            },
+        }
+    }
+
+    let marker = syn::Ident::from(format!("{}::{}", proto.module, meth.proto));
     quote! {
-        #[feature(specialization)]
-        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
-        const #dummy_const: () = {
-            extern crate pyo3;
-            use pyo3::ffi;
+        impl #marker for #ty {
+            #(#types)*
+            type Result = #result;
+        }
+    }
+}
 
-            #tokens
-        };
+/// Python method, which uses generic wrapper of the protocol module
+fn impl_wrapper_def(ty: &Box<syn::Ty>, proto: &defs::Proto, meth: &defs::MethodProto,
+                    wrapper: &str) -> Tokens {
+    let name = meth.name;
+    let wrapper = syn::Ident::from(format!("{}::{}", proto.module, wrapper));
+    quote! {
+        pyo3::class::PyMethodDefType::Method(
+            pyo3::class::PyMethodDef {
+                ml_name: #name,
+                ml_meth: pyo3::class::PyMethodType::PyCFunction(#wrapper::<#ty>),
+                ml_flags: pyo3::ffi::METH_NOARGS,
+                ml_doc: "",
+            })
     }
 }
 
-fn impl_adjust_result(sig: &mut syn::MethodSig, block: &mut syn::Block) {
-    match sig.decl.output {
-        syn::FunctionRetTy::Ty(ref mut ty) => match *ty {
-            syn::Ty::Path(_, ref mut path) => {
-                // check if function returns PyResult
-                if let Some(segment) = path.segments.last_mut() {
-                    match segment.ident.as_ref() {
-                        // check result type
-                        "PyResult" => match segment.parameters {
-                            syn::PathParameters::AngleBracketed(ref mut data) => {
-                                if rewrite_pyobject(&mut data.types) {
-                                    let expr = {
-                                        let s = block as &ToTokens;
-                                        quote! {
-                                            match #s {
-                                                Ok(res) => Ok(res.to_py_object(py)),
-                                                Err(err) => Err(err)
-                                            }
-                                        }
-                                    };
-                                    let expr = syn::parse_expr(&expr.as_str()).unwrap();
-                                    let expr = syn::Stmt::Expr(Box::new(expr));
-                                    block.stmts = vec![expr];
-                                }
-                            },
-                            _ => (),
-                        },
-                        _ => (),
-                    }
+/// `T` of `Option<T>`
+fn option_inner(ty: &syn::Ty) -> Option<&syn::Ty> {
+    type_param(ty, &["Option"])
+}
+
+/// `T` of `PyResult<T>` or `Result<T, E>`
+fn result_inner(ty: &syn::Ty) -> Option<&syn::Ty> {
+    type_param(ty, &["PyResult", "Result"])
+}
+
+fn type_param<'a>(ty: &'a syn::Ty, names: &[&str]) -> Option<&'a syn::Ty> {
+    if let syn::Ty::Path(None, ref path) = *ty {
+        if let Some(segment) = path.segments.last() {
+            if names.contains(&segment.ident.as_ref()) {
+                if let syn::PathParameters::AngleBracketed(ref params) = segment.parameters {
+                    return params.types.first()
                 }
             }
-            _ => (),
-        },
-        syn::FunctionRetTy::Default => (),
+        }
     }
+    None
 }
 
-fn rewrite_pyobject(path: &mut Vec<syn::Ty>) -> bool {
-    if path.len() != 1 {
-        false
-    } else {
-        if let &mut syn::Ty::Path(_, ref mut path) = path.first_mut().unwrap() {
-            if let Some(segment) = path.segments.last_mut() {
-                if segment.ident.as_ref() == "PyObject" {
-                    return false
-                } else {
-                    segment.ident = syn::Ident::from("PyObject");
-                    return true
-                }
+/// Protocols without marker traits list implemented methods.
+fn impl_methods_impl(name: &'static str, ty: &Box<syn::Ty>,
+                     impls: &mut Vec<syn::ImplItem>) -> Tokens {
+    let meth: Vec<String> = impls.iter()
+        .filter_map(|iimpl| match iimpl.node {
+            syn::ImplItemKind::Method(..) => Some(String::from(iimpl.ident.as_ref())),
+            _ => None,
+        })
+        .collect();
+
+    let i = syn::Ident::from(name);
+    quote! {
+        impl #i for #ty {
+            fn methods() -> &'static [&'static str] {
+                static METHODS: &'static [&'static str] = &[#(#meth),*];
+                METHODS
             }
         }
-        let ty = syn::Ty::Path(
-            None, syn::Path{
-                global: false,
-                segments: vec![
-                    syn::PathSegment {
-                        ident: syn::Ident::from("PyObject"),
-                        parameters: syn::PathParameters::AngleBracketed(
-                            syn::AngleBracketedParameterData {
-                                lifetimes: vec![], types: vec![], bindings: vec![] }) }]});
-        let _ = path.pop();
-        let _ = path.push(ty);
-        true
     }
 }
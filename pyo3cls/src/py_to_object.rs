@@ -0,0 +1,126 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+use syn;
+use quote::Tokens;
+
+use utils::{self, Errors};
+
+
+/// Python representation of derived struct.
+#[derive(PartialEq)]
+enum Repr {
+    Dict,
+    NamedTuple,
+    Namespace,
+}
+
+/// `#[derive(ToPyObject)]`
+///
+/// Struct with named fields is converted to `dict` by default,
+/// `#[to_py_object(namedtuple)]` converts it to struct sequence type (named tuple)
+/// and `#[to_py_object(namespace)]` to `types.SimpleNamespace`.
+/// `to_py_object` can not report errors, it panics if python raises one
+/// (i.e. `MemoryError`) after printing the exception.
+pub fn build_to_py_object(ast: &syn::DeriveInput, errors: &mut Errors) -> Tokens {
+    let cls = &ast.ident;
+    if !ast.generics.ty_params.is_empty() || !ast.generics.lifetimes.is_empty() {
+        errors.error(&ast.generics,
+                     &format!("#[derive(ToPyObject)] `{}` can not be generic", cls));
+        return Tokens::new()
+    }
+    let fields = match ast.body {
+        syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+        _ => {
+            errors.error(cls, "#[derive(ToPyObject)] can only be used with structs with named fields");
+            return Tokens::new()
+        },
+    };
+    let repr = parse_repr(&ast.attrs, errors);
+
+    let names: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+    let body = match repr {
+        Repr::Dict | Repr::Namespace => {
+            let names2 = names.clone();
+            let dict = quote! {
+                let dict = PyDict::new(py);
+                #(pyo3::derive::set_dict_item(py, &dict, stringify!(#names), &self.#names2);)*
+            };
+            if repr == Repr::Dict {
+                quote! {
+                    #dict
+                    dict.into_object()
+                }
+            } else {
+                quote! {
+                    #dict
+                    pyo3::derive::namespace(py, dict)
+                }
+            }
+        },
+        Repr::NamedTuple => {
+            let name = format!("{}\0", cls);
+            let doc = format!("{}\0", utils::get_doc(&ast.attrs));
+            let fields: Vec<_> = names.iter().map(|name| format!("{}\0", name)).collect();
+            quote! {
+                static mut TYPE_OBJECT: *mut ffi::PyTypeObject = 0 as *mut ffi::PyTypeObject;
+                unsafe {
+                    let ty = pyo3::derive::struct_sequence_type(
+                        &mut TYPE_OBJECT, #name, #doc, &[#(#fields),*]);
+                    pyo3::derive::struct_sequence(
+                        py, ty, vec![#(pyo3::ToPyObject::to_py_object(&self.#names, py)),*])
+                }
+            }
+        },
+    };
+
+    let dummy_const = syn::Ident::new(format!("_IMPL_PYO3_TO_PY_OBJECT_{}", cls));
+    quote! {
+        #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+        const #dummy_const: () = {
+            extern crate pyo3;
+            use pyo3::{ffi, Python, PyObject, PyDict, PythonObject};
+
+            impl pyo3::ToPyObject for #cls {
+                fn to_py_object(&self, py: Python) -> PyObject {
+                    #body
+                }
+            }
+        };
+    }
+}
+
+/// Parse `#[to_py_object(dict)]`, `#[to_py_object(namedtuple)]` and `#[to_py_object(namespace)]`
+fn parse_repr(attrs: &Vec<syn::Attribute>, errors: &mut Errors) -> Repr {
+    let mut repr = None;
+
+    for attr in attrs.iter().filter(|attr| attr.value.name() == "to_py_object") {
+        if repr.is_some() {
+            errors.error(attr, "#[to_py_object] attribute can not be used multiple times");
+            continue
+        }
+        repr = match attr.value {
+            syn::MetaItem::List(_, ref items) if items.len() == 1 => match items[0] {
+                syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref w)) => match w.as_ref() {
+                    "dict" => Some(Repr::Dict),
+                    "namedtuple" => Some(Repr::NamedTuple),
+                    "namespace" => Some(Repr::Namespace),
+                    _ => {
+                        errors.error(&items[0], "unsupported #[to_py_object] parameter");
+                        continue
+                    },
+                },
+                _ => {
+                    errors.error(&items[0], "unsupported #[to_py_object] parameter");
+                    continue
+                },
+            },
+            _ => {
+                errors.error(
+                    attr, "#[to_py_object] requires `dict`, `namedtuple` or `namespace` parameter");
+                continue
+            },
+        };
+    }
+
+    repr.unwrap_or(Repr::Dict)
+}
@@ -1,3 +1,4 @@
+use syn;
 use quote::{Tokens, ToTokens};
 
 
@@ -7,3 +8,67 @@ pub fn for_err_msg(i: &ToTokens) -> String {
     i.to_tokens(&mut tokens);
     tokens.as_str().to_string()
 }
+
+
+/// Errors found during macro expansion.
+///
+/// Expansion continues after an error, so every problem of an item is reported
+/// at once. Each error becomes a `compile_error!` invocation. Items are parsed
+/// from their string form, which carries no spans, so messages quote the offending code.
+pub struct Errors {
+    messages: Vec<String>,
+}
+
+impl Errors {
+    pub fn new() -> Errors {
+        Errors { messages: Vec::new() }
+    }
+
+    /// Report error related to syntax element, the offending code is included in the message.
+    pub fn error(&mut self, item: &ToTokens, msg: &str) {
+        self.messages.push(format!("{}: `{}`", msg, for_err_msg(item)));
+    }
+
+    /// Report error that is not related to specific syntax element.
+    pub fn message<T: Into<String>>(&mut self, msg: T) {
+        self.messages.push(msg.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl ToTokens for Errors {
+    fn to_tokens(&self, tokens: &mut Tokens) {
+        for msg in self.messages.iter() {
+            let msg = msg.as_str();
+            quote! { compile_error!(#msg); }.to_tokens(tokens);
+        }
+    }
+}
+
+
+/// Collect doc comments of an item, used as python docstring.
+pub fn get_doc(attrs: &Vec<syn::Attribute>) -> String {
+    let mut doc = Vec::new();
+
+    for attr in attrs.iter() {
+        if let syn::MetaItem::NameValue(ref name, syn::Lit::Str(ref value, _)) = attr.value {
+            if name.as_ref() != "doc" {
+                continue
+            }
+            // sugared doc comments keep comment markers
+            let line = if value.starts_with("///") {
+                &value[3..]
+            } else if value.starts_with("/**") && value.ends_with("*/") {
+                value[3..value.len()-2].trim()
+            } else {
+                value.as_str()
+            };
+            doc.push(if line.starts_with(' ') { &line[1..] } else { line });
+        }
+    }
+
+    doc.join("\n")
+}
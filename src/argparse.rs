@@ -30,7 +30,11 @@ pub struct ParamDescription<'a> {
     /// The name of the parameter.
     pub name: &'a str,
     /// Whether the parameter is optional.
-    pub is_optional: bool
+    pub is_optional: bool,
+    /// Whether the parameter can only be passed as keyword argument.
+    pub kw_only: bool,
+    /// Whether the parameter can only be passed as positional argument.
+    pub pos_only: bool,
 }
 
 /// Parse argument list
@@ -39,68 +43,180 @@ pub struct ParamDescription<'a> {
 /// * params: Declared parameters of the function
 /// * args:   Positional arguments
 /// * kwargs: Keyword arguments
+/// * accept_args: Whether extra positional arguments are accepted (`*args`)
+/// * accept_kwargs: Whether extra keyword arguments are accepted (`**kwargs`)
 /// * output: Output array that receives the arguments.
 ///           Must have same length as `params` and must be initialized to `None`.
+///
+/// Extra positional and keyword arguments are not stored in `output`,
+/// use `varargs()` and `varkwargs()` to collect them.
 pub fn parse_args(py: Python,
                   fname: Option<&str>, params: &[ParamDescription],
                   args: &PyTuple, kwargs: Option<&PyDict>,
+                  accept_args: bool, accept_kwargs: bool,
                   output: &mut[Option<PyObject>]) -> PyResult<()>
 {
     assert!(params.len() == output.len());
 
     let nargs = args.len(py);
     let nkeywords = kwargs.map_or(0, |d| d.len(py));
-    if nargs + nkeywords > params.len() {
-        return Err(err::PyErr::new::<exc::TypeError, _>(py,
-            format!("{}{} takes at most {} argument{} ({} given)",
-                    fname.unwrap_or("function"),
-                    if fname.is_some() { "()" } else { "" },
-                    params.len(),
-                    if params.len() == 1 { "s" } else { "" },
-                    nargs + nkeywords
-                )));
+
+    // check number of positional arguments
+    let max_positional = params.iter().filter(|p| !p.kw_only).count();
+    if !accept_args && nargs > max_positional {
+        let min_positional = params.iter().filter(|p| !p.kw_only && !p.is_optional).count();
+        let expected = if min_positional == max_positional {
+            format!("{} positional argument{}",
+                    max_positional, if max_positional == 1 { "" } else { "s" })
+        } else {
+            format!("from {} to {} positional arguments", min_positional, max_positional)
+        };
+        return Err(type_error(py, format!(
+            "{} takes {} but {} {} given",
+            fn_name(fname), expected, nargs, if nargs == 1 { "was" } else { "were" })));
     }
+
     let mut used_keywords = 0;
+    let mut missing_positional = Vec::new();
+    let mut missing_kw_only = Vec::new();
+    let mut pos = 0;
+
     // Iterate through the parameters and assign values to output:
-    for (i, (p, out)) in params.iter().zip(output).enumerate() {
-        match kwargs.and_then(|d| d.get_item(py, p.name)) {
+    for (p, out) in params.iter().zip(output) {
+        // index of the parameter in the positional arguments
+        let index = if p.kw_only {
+            None
+        } else {
+            pos += 1;
+            Some(pos - 1)
+        };
+        let kwarg = if p.pos_only {
+            None
+        } else {
+            kwargs.and_then(|d| d.get_item(py, p.name))
+        };
+        match kwarg {
             Some(kwarg) => {
+                if let Some(i) = index {
+                    if i < nargs {
+                        return Err(type_error(py, format!(
+                            "{} got multiple values for argument '{}'", fn_name(fname), p.name)));
+                    }
+                }
                 *out = Some(kwarg);
                 used_keywords += 1;
-                if i < nargs {
-                    return Err(err::PyErr::new::<exc::TypeError, _>(py,
-                        format!("Argument given by name ('{}') and position ({})",
-                                p.name, i+1)));
-                }
             },
             None => {
-                if i < nargs {
-                    *out = Some(args.get_item(py, i));
-                } else {
-                    *out = None;
-                    if !p.is_optional {
-                        return Err(err::PyErr::new::<exc::TypeError, _>(py,
-                            format!("Required argument ('{}') (pos {}) not found",
-                                    p.name, i+1)));
+                match index {
+                    Some(i) if i < nargs => *out = Some(args.get_item(py, i)),
+                    _ => {
+                        *out = None;
+                        if !p.is_optional {
+                            if p.kw_only {
+                                missing_kw_only.push(p.name);
+                            } else {
+                                missing_positional.push(p.name);
+                            }
+                        }
                     }
                 }
             }
         }
     }
     if used_keywords != nkeywords {
-        // check for extraneous keyword arguments
+        // check for positional-only and extraneous keyword arguments,
+        // like python does before it reports missing arguments
+        let mut pos_only = Vec::new();
         for (key, _value) in kwargs.unwrap().items(py) {
             let key = try!(try!(key.cast_as::<PyString>(py)).to_string(py));
-            if !params.iter().any(|p| p.name == key) {
-                return Err(err::PyErr::new::<exc::TypeError, _>(py,
-                    format!("'{}' is an invalid keyword argument for this function",
-                            key)));
+            match params.iter().find(|p| p.name == key) {
+                Some(p) if p.pos_only => {
+                    if !accept_kwargs {
+                        pos_only.push(format!("'{}'", key));
+                    }
+                },
+                Some(_) => (),
+                None => if !accept_kwargs {
+                    return Err(type_error(py, format!(
+                        "{} got an unexpected keyword argument '{}'", fn_name(fname), key)));
+                },
             }
         }
+        if !pos_only.is_empty() {
+            return Err(type_error(py, format!(
+                "{} got some positional-only arguments passed as keyword arguments: {}",
+                fn_name(fname), pos_only.join(", "))));
+        }
+    }
+
+    if !missing_positional.is_empty() {
+        return Err(missing_args_error(py, fname, "positional", &missing_positional));
+    }
+    if !missing_kw_only.is_empty() {
+        return Err(missing_args_error(py, fname, "keyword-only", &missing_kw_only));
     }
     Ok(())
 }
 
+/// Collect positional arguments that are not consumed by `params`.
+pub fn varargs(py: Python, params: &[ParamDescription], args: &PyTuple) -> PyTuple {
+    let npositional = params.iter().filter(|p| !p.kw_only).count();
+    let args = args.as_slice(py);
+    if args.len() > npositional {
+        PyTuple::new(py, &args[npositional..])
+    } else {
+        PyTuple::empty(py)
+    }
+}
+
+/// Collect keyword arguments that are not consumed by `params`.
+///
+/// Keywords that match a positional-only parameter are returned as well.
+pub fn varkwargs(py: Python, params: &[ParamDescription],
+                 kwargs: Option<&PyDict>) -> PyResult<Option<PyDict>>
+{
+    match kwargs {
+        Some(kwargs) => {
+            let dict = PyDict::new(py);
+            for (key, value) in kwargs.items(py) {
+                let consumed = {
+                    let name = try!(try!(key.cast_as::<PyString>(py)).to_string(py));
+                    params.iter().any(|p| p.name == name && !p.pos_only)
+                };
+                if !consumed {
+                    try!(dict.set_item(py, key, value));
+                }
+            }
+            if dict.len(py) == 0 { Ok(None) } else { Ok(Some(dict)) }
+        },
+        None => Ok(None),
+    }
+}
+
+fn fn_name(fname: Option<&str>) -> String {
+    match fname {
+        Some(fname) => format!("{}()", fname),
+        None => "function".to_owned(),
+    }
+}
+
+fn type_error(py: Python, msg: String) -> err::PyErr {
+    err::PyErr::new::<exc::TypeError, _>(py, msg)
+}
+
+fn missing_args_error(py: Python, fname: Option<&str>, kind: &str, names: &[&str]) -> err::PyErr {
+    let names: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    let list = match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() =>
+            format!("{}{} and {}",
+                    rest.join(", "), if rest.len() > 1 { "," } else { "" }, last),
+        _ => names.join(""),
+    };
+    type_error(py, format!(
+        "{} missing {} required {} argument{}: {}",
+        fn_name(fname), names.len(), kind, if names.len() == 1 { "" } else { "s" }, list))
+}
+
 /// This macro is used to parse a parameter list into a set of variables.
 ///
 /// Syntax: `py_argparse!(py, fname, args, kwargs, (parameter-list) { body })`
@@ -317,7 +433,8 @@ macro_rules! py_argparse_impl {
         ];
         let py: $crate::Python = $py;
         let mut output = [$( py_replace_expr!($pname None) ),*];
-        match $crate::argparse::parse_args(py, $fname, PARAMS, $args, $kwargs, &mut output) {
+        match $crate::argparse::parse_args(
+            py, $fname, PARAMS, $args, $kwargs, false, false, &mut output) {
             Ok(()) => {
                 // Experimental slice pattern syntax would be really nice here (#23121)
                 //let [$(ref $pname),*] = output;
@@ -365,14 +482,18 @@ macro_rules! py_argparse_param_description {
     { $pname:ident : $ptype:ty = [ {} {} $rtype:tt ] } => (
         $crate::argparse::ParamDescription {
             name: stringify!($pname),
-            is_optional: false
+            is_optional: false,
+            kw_only: false,
+            pos_only: false,
         }
     );
     // optional parameters
     { $pname:ident : $ptype:ty = [ {} {$default:expr} {$($rtype:tt)*} ] } => (
         $crate::argparse::ParamDescription {
             name: stringify!($pname),
-            is_optional: true
+            is_optional: true,
+            kw_only: false,
+            pos_only: false,
         }
     );
 }
@@ -445,8 +566,101 @@ pub fn with_extracted_or_default<P: ?Sized, R, F>(py: Python, obj: Option<&PyObj
 #[cfg(test)]
 mod test {
     use python::{Python, PythonObject};
-    use objects::PyTuple;
+    use objects::{PyTuple, PyDict};
     use conversion::{ToPyObject, ToPyTuple};
+    use err::PyErr;
+    use super::{parse_args, varargs, varkwargs, ParamDescription};
+
+    const PARAMS: &'static [ParamDescription<'static>] = &[
+        ParamDescription{name: "a", is_optional: false, kw_only: false, pos_only: true},
+        ParamDescription{name: "b", is_optional: false, kw_only: false, pos_only: false},
+        ParamDescription{name: "c", is_optional: true, kw_only: false, pos_only: false},
+        ParamDescription{name: "d", is_optional: false, kw_only: true, pos_only: false},
+    ];
+
+    fn error_message(py: Python, err: PyErr) -> String {
+        err.pvalue.unwrap().extract::<String>(py).unwrap()
+    }
+
+    fn parse(py: Python, args: &PyTuple, kwargs: Option<&PyDict>,
+             accept_args: bool, accept_kwargs: bool) -> Result<usize, String> {
+        let mut output = [None, None, None, None];
+        match parse_args(py, Some("f"), PARAMS, args, kwargs,
+                         accept_args, accept_kwargs, &mut output) {
+            Ok(()) => Ok(output.iter().filter(|o| o.is_some()).count()),
+            Err(err) => Err(error_message(py, err)),
+        }
+    }
+
+    #[test]
+    pub fn test_parse_args() {
+        let gil_guard = Python::acquire_gil();
+        let py = gil_guard.python();
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "d", 4).unwrap();
+
+        assert_eq!(parse(py, &(1, 2).to_py_tuple(py), Some(&kwargs), false, false), Ok(3));
+        assert_eq!(parse(py, &(1, 2, 3).to_py_tuple(py), Some(&kwargs), false, false), Ok(4));
+        kwargs.set_item(py, "b", 2).unwrap();
+        assert_eq!(parse(py, &(1,).to_py_tuple(py), Some(&kwargs), false, false), Ok(3));
+    }
+
+    #[test]
+    pub fn test_parse_args_errors() {
+        let gil_guard = Python::acquire_gil();
+        let py = gil_guard.python();
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "d", 4).unwrap();
+
+        assert_eq!(parse(py, &(1, 2, 3, 4).to_py_tuple(py), Some(&kwargs), false, false),
+                   Err("f() takes from 2 to 3 positional arguments but 4 were given".to_owned()));
+        assert_eq!(parse(py, &PyTuple::empty(py), Some(&kwargs), false, false),
+                   Err("f() missing 2 required positional arguments: 'a' and 'b'".to_owned()));
+        assert_eq!(parse(py, &(1, 2).to_py_tuple(py), None, false, false),
+                   Err("f() missing 1 required keyword-only argument: 'd'".to_owned()));
+
+        kwargs.set_item(py, "b", 2).unwrap();
+        assert_eq!(parse(py, &(1, 2).to_py_tuple(py), Some(&kwargs), false, false),
+                   Err("f() got multiple values for argument 'b'".to_owned()));
+
+        kwargs.set_item(py, "e", 5).unwrap();
+        assert_eq!(parse(py, &(1,).to_py_tuple(py), Some(&kwargs), false, false),
+                   Err("f() got an unexpected keyword argument 'e'".to_owned()));
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "a", 1).unwrap();
+        kwargs.set_item(py, "b", 2).unwrap();
+        kwargs.set_item(py, "d", 4).unwrap();
+        assert_eq!(parse(py, &PyTuple::empty(py), Some(&kwargs), false, false),
+                   Err("f() got some positional-only arguments passed as keyword arguments: 'a'"
+                       .to_owned()));
+        assert_eq!(parse(py, &(1,).to_py_tuple(py), Some(&kwargs), false, false),
+                   Err("f() got some positional-only arguments passed as keyword arguments: 'a'"
+                       .to_owned()));
+    }
+
+    #[test]
+    pub fn test_varargs() {
+        let gil_guard = Python::acquire_gil();
+        let py = gil_guard.python();
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "a", 0).unwrap();
+        kwargs.set_item(py, "d", 4).unwrap();
+        kwargs.set_item(py, "e", 5).unwrap();
+
+        let args = (1, 2, 3, 4, 5).to_py_tuple(py);
+        assert_eq!(parse(py, &args, Some(&kwargs), true, true), Ok(4));
+
+        let rest = varargs(py, PARAMS, &args);
+        assert_eq!(rest.len(py), 2);
+        assert_eq!(rest.get_item(py, 0).extract::<i32>(py).unwrap(), 4);
+
+        let rest = varkwargs(py, PARAMS, Some(&kwargs)).unwrap().unwrap();
+        assert_eq!(rest.len(py), 2);
+        assert!(rest.contains(py, "a").unwrap());
+        assert!(rest.contains(py, "e").unwrap());
+        assert!(varkwargs(py, PARAMS, None).unwrap().is_none());
+    }
 
     #[test]
     pub fn test_parse() {
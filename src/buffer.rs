@@ -127,6 +127,10 @@ pub unsafe trait Element {
     /// Gets whether the element specified in the format string is potentially compatible.
     /// Alignment and size are checked separately from this function.
     fn is_compatible_format(format: &CStr) -> bool;
+
+    /// Native struct format of the element, used when rust memory is exported
+    /// through the buffer protocol.
+    fn format() -> &'static CStr;
 }
 
 fn validate(b: &ffi::Py_buffer) {
@@ -539,7 +543,7 @@ impl <T: Copy> ReadOnlyCell<T> {
 }
 
 macro_rules! impl_element(
-    ($t:ty, $f:ident) => {
+    ($t:ty, $f:ident, $format:expr) => {
         unsafe impl Element for $t {
             fn is_compatible_format(format: &CStr) -> bool {
                 let slice = format.to_bytes();
@@ -548,22 +552,26 @@ macro_rules! impl_element(
                 }
                 ElementType::from_format(format) == ElementType::$f { bytes: mem::size_of::<$t>() }
             }
+
+            fn format() -> &'static CStr {
+                unsafe { CStr::from_bytes_with_nul_unchecked(concat!($format, "\0").as_bytes()) }
+            }
         }
     }
 );
 
-impl_element!(u8, UnsignedInteger);
-impl_element!(u16, UnsignedInteger);
-impl_element!(u32, UnsignedInteger);
-impl_element!(u64, UnsignedInteger);
-impl_element!(usize, UnsignedInteger);
-impl_element!(i8, SignedInteger);
-impl_element!(i16, SignedInteger);
-impl_element!(i32, SignedInteger);
-impl_element!(i64, SignedInteger);
-impl_element!(isize, SignedInteger);
-impl_element!(f32, Float);
-impl_element!(f64, Float);
+impl_element!(u8, UnsignedInteger, "B");
+impl_element!(u16, UnsignedInteger, "H");
+impl_element!(u32, UnsignedInteger, "I");
+impl_element!(u64, UnsignedInteger, "Q");
+impl_element!(usize, UnsignedInteger, "N");
+impl_element!(i8, SignedInteger, "b");
+impl_element!(i16, SignedInteger, "h");
+impl_element!(i32, SignedInteger, "i");
+impl_element!(i64, SignedInteger, "q");
+impl_element!(isize, SignedInteger, "n");
+impl_element!(f32, Float, "f");
+impl_element!(f64, Float, "d");
 
 #[cfg(test)]
 mod test {
@@ -572,7 +580,7 @@ mod test {
     use conversion::ToPyObject;
     use objects::{PySequence, PyList, PyTuple, PyIterator};
     use objectprotocol::ObjectProtocol;
-    use super::PyBuffer;
+    use super::{PyBuffer, Element};
 
     #[test]
     fn test_compatible_size() {
@@ -580,6 +588,19 @@ mod test {
         assert_eq!(std::mem::size_of::<::Py_ssize_t>(), std::mem::size_of::<usize>());
     }
 
+    #[test]
+    fn test_element_format() {
+        fn compatible<T: Element>() -> bool {
+            T::is_compatible_format(T::format())
+        }
+        assert!(compatible::<u8>() && compatible::<u16>() && compatible::<u32>());
+        assert!(compatible::<u64>() && compatible::<usize>());
+        assert!(compatible::<i8>() && compatible::<i16>() && compatible::<i32>());
+        assert!(compatible::<i64>() && compatible::<isize>());
+        assert!(compatible::<f32>() && compatible::<f64>());
+        assert!(!u32::is_compatible_format(i32::format()));
+    }
+
     #[test]
     fn test_bytes_buffer() {
         let gil = Python::acquire_gil();
@@ -133,10 +133,9 @@ impl <T> CallbackConverter<Option<T>>
     fn convert(val: Option<T>, py: Python) -> *mut ffi::PyObject {
         match val {
             Some(val) => val.into_py_object(py).into_object().steal_ptr(),
-            None => unsafe {
-                ffi::PyErr_SetNone(ffi::PyExc_StopIteration);
-                ptr::null_mut()
-            }
+            // `tp_iternext` signals exhaustion by NULL without exception set,
+            // so `StopIteration` is never created
+            None => ptr::null_mut(),
         }
     }
 
@@ -12,9 +12,10 @@ use ffi;
 use err::{PyErr, PyResult};
 use python::{Python, PythonObject, PyDrop};
 use objects::{exc, PyObject};
-use conversion::ToPyObject;
-use callback::{handle_callback, PyObjectCallbackConverter, HashConverter, UnitCallbackConverter};
-use class::{NO_METHODS, NO_PY_METHODS};
+use conversion::{ToPyObject, FromPyObject};
+use callback::{handle_callback, PyObjectCallbackConverter, HashConverter, BoolConverter,
+               UnitCallbackConverter};
+use class::typeob::TypeSlots;
 
 // __new__
 // __init__
@@ -24,13 +25,21 @@ use class::{NO_METHODS, NO_PY_METHODS};
 
 
 /// Basic customization
-pub trait PyObjectProtocol {
+#[allow(unused_variables)]
+pub trait PyObjectProtocol: PythonObject {
 
-    fn __getattr__(&self, py: Python, name: &PyObject) -> PyResult<PyObject>;
+    /// Called when the default attribute access fails with `AttributeError`.
+    fn __getattr__(&self, py: Python, name: Self::Name) -> Self::Result
+        where Self: PyObjectGetAttrProtocol
+    { unimplemented!() }
 
-    fn __setattr__(&self, py: Python, name: &PyObject, value: &PyObject) -> PyResult<()>;
+    fn __setattr__(&self, py: Python, name: Self::Name, value: Self::Value) -> Self::Result
+        where Self: PyObjectSetAttrProtocol
+    { unimplemented!() }
 
-    fn __delattr__(&self, py: Python, name: &PyObject) -> PyResult<()>;
+    fn __delattr__(&self, py: Python, name: Self::Name) -> Self::Result
+        where Self: PyObjectDelAttrProtocol
+    { unimplemented!() }
 
     // __instancecheck__
     // __subclasscheck__
@@ -38,112 +47,288 @@ pub trait PyObjectProtocol {
     // __next__
     // __dir__
 
-    fn __str__(&self, py: Python) -> PyResult<PyObject>;
+    fn __str__(&self, py: Python) -> Self::Result
+        where Self: PyObjectStrProtocol
+    { unimplemented!() }
 
-    fn __repr__(&self, py: Python) -> PyResult<PyObject>;
+    fn __repr__(&self, py: Python) -> Self::Result
+        where Self: PyObjectReprProtocol
+    { unimplemented!() }
 
-    fn __hash__(&self, py: Python) -> PyResult<u64>;
+    fn __hash__(&self, py: Python) -> Self::Result
+        where Self: PyObjectHashProtocol
+    { unimplemented!() }
 
-    fn __bool__(&self, py: Python) -> PyResult<bool>;
+    fn __bool__(&self, py: Python) -> Self::Result
+        where Self: PyObjectBoolProtocol
+    { unimplemented!() }
 
-    fn __richcmp__(&self, py: Python, other: &PyObject, op: CompareOp) -> PyResult<PyObject>;
+    /// Comparison with `other`, `NotImplemented` is returned to python
+    /// if `other` can not be extracted.
+    fn __richcmp__(&self, py: Python, other: Self::Other, op: CompareOp) -> Self::Result
+        where Self: PyObjectRichcmpProtocol
+    { unimplemented!() }
+}
+
+// The following are a bunch of marker traits used to detect
+// the existance of a slotted method.
 
+pub trait PyObjectGetAttrProtocol: PyObjectProtocol {
+    type Name: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
 }
 
+pub trait PyObjectSetAttrProtocol: PyObjectProtocol {
+    type Name: for<'a> FromPyObject<'a>;
+    type Value: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
 
-impl<T> PyObjectProtocol for T {
+pub trait PyObjectDelAttrProtocol: PyObjectProtocol {
+    type Name: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
 
-    default fn __getattr__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
-    }
-    default fn __setattr__(&self, py: Python, _: &PyObject, _: &PyObject) -> PyResult<()> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
-    }
-    default fn __delattr__(&self, py: Python, _: &PyObject) -> PyResult<()> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+pub trait PyObjectStrProtocol: PyObjectProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyObjectReprProtocol: PyObjectProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyObjectHashProtocol: PyObjectProtocol {
+    type Result: Into<PyResult<usize>>;
+}
+
+pub trait PyObjectBoolProtocol: PyObjectProtocol {
+    type Result: Into<PyResult<bool>>;
+}
+
+pub trait PyObjectRichcmpProtocol: PyObjectProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+#[doc(hidden)]
+pub trait PyObjectProtocolImpl {
+    fn update_type_slots(slots: &mut TypeSlots);
+}
+
+impl<T> PyObjectProtocolImpl for T {
+    #[inline]
+    default fn update_type_slots(_slots: &mut TypeSlots) {}
+}
+
+impl<T> PyObjectProtocolImpl for T where T: PyObjectProtocol {
+    fn update_type_slots(slots: &mut TypeSlots) {
+        push_slot!(slots, Py_tp_str, Self::tp_str());
+        push_slot!(slots, Py_tp_repr, Self::tp_repr());
+        push_slot!(slots, Py_tp_hash, Self::tp_hash());
+        push_slot!(slots, Py_tp_getattro, Self::tp_getattro());
+        push_slot!(slots, Py_tp_richcompare, Self::tp_richcompare());
+        push_slot!(slots, Py_nb_bool, Self::nb_bool());
+
+        if <T as PyObjectSetAttrProtocolImpl>::implemented() ||
+            <T as PyObjectDelAttrProtocolImpl>::implemented()
+        {
+            push_slot!(slots, Py_tp_setattro, tp_setattro::<T>());
+        }
     }
+}
 
-    // __instancecheck__
-    // __subclasscheck__
-    // __iter__
-    // __next__
-    // __dir__
+trait PyObjectGetAttrProtocolImpl {
+    fn tp_getattro() -> Option<ffi::binaryfunc>;
+}
+
+impl<T> PyObjectGetAttrProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn tp_getattro() -> Option<ffi::binaryfunc> { None }
+}
 
-    default fn __str__(&self, py: Python) -> PyResult<PyObject> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+impl<T> PyObjectGetAttrProtocolImpl for T where T: PyObjectGetAttrProtocol {
+    #[inline]
+    fn tp_getattro() -> Option<ffi::binaryfunc> {
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     arg: *mut ffi::PyObject) -> *mut ffi::PyObject
+            where T: PyObjectGetAttrProtocol
+        {
+            // generic lookup first, `__getattr__` is a fallback like in python
+            let existing = ffi::PyObject_GenericGetAttr(slf, arg);
+            if !existing.is_null() {
+                return existing
+            }
+            if ffi::PyErr_ExceptionMatches(ffi::PyExc_AttributeError) == 0 {
+                return existing
+            }
+            ffi::PyErr_Clear();
+
+            const LOCATION: &'static str = "T.__getattr__()";
+            handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+                let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+                let arg = PyObject::from_borrowed_ptr(py, arg);
+                let ret = match arg.extract(py) {
+                    Ok(name) => py_borrow_call!(py, T, &slf, false,
+                                                slf.__getattr__(py, name).into()),
+                    Err(e) => Err(e),
+                };
+                PyDrop::release_ref(arg, py);
+                PyDrop::release_ref(slf, py);
+                ret
+            })
+        }
+        Some(wrap::<T>)
     }
-    default fn __repr__(&self, py: Python) -> PyResult<PyObject> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+}
+
+trait PyObjectStrProtocolImpl {
+    fn tp_str() -> Option<ffi::unaryfunc>;
+}
+
+impl<T> PyObjectStrProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn tp_str() -> Option<ffi::unaryfunc> { None }
+}
+
+impl<T> PyObjectStrProtocolImpl for T where T: PyObjectStrProtocol {
+    #[inline]
+    fn tp_str() -> Option<ffi::unaryfunc> {
+        py_unary_func!(PyObjectStrProtocol, T::__str__, PyObjectCallbackConverter)
     }
-    default fn __hash__(&self, py: Python) -> PyResult<u64> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+}
+
+trait PyObjectReprProtocolImpl {
+    fn tp_repr() -> Option<ffi::unaryfunc>;
+}
+
+impl<T> PyObjectReprProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn tp_repr() -> Option<ffi::unaryfunc> { None }
+}
+
+impl<T> PyObjectReprProtocolImpl for T where T: PyObjectReprProtocol {
+    #[inline]
+    fn tp_repr() -> Option<ffi::unaryfunc> {
+        py_unary_func!(PyObjectReprProtocol, T::__repr__, PyObjectCallbackConverter)
     }
-    default fn __bool__(&self, py: Python) -> PyResult<bool> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+}
+
+trait PyObjectHashProtocolImpl {
+    fn tp_hash() -> Option<ffi::hashfunc>;
+}
+
+impl<T> PyObjectHashProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn tp_hash() -> Option<ffi::hashfunc> { None }
+}
+
+impl<T> PyObjectHashProtocolImpl for T where T: PyObjectHashProtocol {
+    #[inline]
+    fn tp_hash() -> Option<ffi::hashfunc> {
+        py_unary_func!(PyObjectHashProtocol, T::__hash__, HashConverter, Py_hash_t)
     }
-    default fn __richcmp__(&self, py: Python, _: &PyObject, _: CompareOp) -> PyResult<PyObject> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+}
+
+trait PyObjectBoolProtocolImpl {
+    fn nb_bool() -> Option<ffi::inquiry>;
+}
+
+impl<T> PyObjectBoolProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn nb_bool() -> Option<ffi::inquiry> { None }
+}
+
+impl<T> PyObjectBoolProtocolImpl for T where T: PyObjectBoolProtocol {
+    #[inline]
+    fn nb_bool() -> Option<ffi::inquiry> {
+        py_unary_func!(PyObjectBoolProtocol, T::__bool__, BoolConverter, c_int)
     }
 }
 
-#[doc(hidden)]
-pub trait PyObjectProtocolImpl {
-    fn methods() -> &'static [&'static str];
-    fn py_methods() -> &'static [::class::PyMethodDefType];
+// `__setattr__` and `__delattr__` share `tp_setattro` slot,
+// operation which is not implemented falls back to generic attribute handling.
+
+trait PyObjectSetAttrProtocolImpl {
+    fn implemented() -> bool;
+    unsafe fn setattr(&self, py: Python, name: &PyObject, value: *mut ffi::PyObject)
+                      -> PyResult<()>;
 }
 
-impl<T> PyObjectProtocolImpl for T {
-    default fn methods() -> &'static [&'static str] {
-        NO_METHODS
+impl<T> PyObjectSetAttrProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn implemented() -> bool { false }
+
+    default unsafe fn setattr(&self, py: Python, name: &PyObject, value: *mut ffi::PyObject)
+                              -> PyResult<()> {
+        generic_setattr(py, self.as_object(), name, value)
     }
-    default fn py_methods() -> &'static [::class::PyMethodDefType] {
-        NO_PY_METHODS
+}
+
+impl<T> PyObjectSetAttrProtocolImpl for T where T: PyObjectSetAttrProtocol {
+    #[inline]
+    fn implemented() -> bool { true }
+
+    unsafe fn setattr(&self, py: Python, name: &PyObject, value: *mut ffi::PyObject)
+                      -> PyResult<()> {
+        let value = PyObject::from_borrowed_ptr(py, value);
+        let ret = match (name.extract(py), value.extract(py)) {
+            (Ok(name), Ok(value)) =>
+                py_borrow_call!(py, T, self, false, self.__setattr__(py, name, value).into()),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+        PyDrop::release_ref(value, py);
+        ret
     }
 }
 
-pub fn py_object_proto_impl<T>(type_object: &mut ffi::PyTypeObject)
-    where T: PyObjectProtocol + PyObjectProtocolImpl + PythonObject
-{
-    let methods = T::methods();
-    if methods.is_empty() {
-        return
+trait PyObjectDelAttrProtocolImpl {
+    fn implemented() -> bool;
+    fn delattr(&self, py: Python, name: &PyObject) -> PyResult<()>;
+}
+
+impl<T> PyObjectDelAttrProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn implemented() -> bool { false }
+
+    default fn delattr(&self, py: Python, name: &PyObject) -> PyResult<()> {
+        unsafe { generic_setattr(py, self.as_object(), name, ::std::ptr::null_mut()) }
     }
+}
 
-    for name in methods {
-        match name {
-            &"__str__" =>
-                type_object.tp_str = py_unary_func!(
-                    PyObjectProtocol, T::__str__, PyObjectCallbackConverter),
-            &"__repr__" =>
-                type_object.tp_repr = py_unary_func!(
-                    PyObjectProtocol, T::__repr__, PyObjectCallbackConverter),
-            &"__hash__" =>
-                type_object.tp_hash = py_unary_func!(
-                    PyObjectProtocol, T::__hash__, HashConverter, Py_hash_t),
-            &"__getattr__" =>
-                type_object.tp_getattro = py_binary_func!(
-                    PyObjectProtocol, T::__getattr__, PyObjectCallbackConverter),
-            &"__richcmp__" =>
-                type_object.tp_richcompare = tp_richcompare::<T>(),
-            _ => (),
+impl<T> PyObjectDelAttrProtocolImpl for T where T: PyObjectDelAttrProtocol {
+    #[inline]
+    fn implemented() -> bool { true }
+
+    fn delattr(&self, py: Python, name: &PyObject) -> PyResult<()> {
+        match name.extract(py) {
+            Ok(name) => py_borrow_call!(py, T, self, false, self.__delattr__(py, name).into()),
+            Err(e) => Err(e),
         }
     }
+}
 
-    if methods.contains(&"__setattr__") || methods.contains(&"__getattr__") {
-        type_object.tp_setattro = tp_setattro::<T>()
+unsafe fn generic_setattr(py: Python, obj: &PyObject, name: &PyObject,
+                          value: *mut ffi::PyObject) -> PyResult<()> {
+    if ffi::PyObject_GenericSetAttr(obj.as_ptr(), name.as_ptr(), value) < 0 {
+        Err(PyErr::fetch(py))
+    } else {
+        Ok(())
     }
 }
 
-
 fn tp_setattro<T>() -> Option<ffi::setattrofunc>
-    where T: PyObjectProtocol + PythonObject
+    where T: PyObjectProtocol
 {
     unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
                                  key: *mut ffi::PyObject,
                                  value: *mut ffi::PyObject) -> c_int
-        where T: PyObjectProtocol + PythonObject
+        where T: PyObjectProtocol
     {
-        const LOCATION: &'static str = concat!(stringify!(T), ".__setitem__()");
+        const LOCATION: &'static str = "T.__setattr__()";
 
         handle_callback(
             LOCATION, UnitCallbackConverter, |py|
@@ -151,14 +336,11 @@ fn tp_setattro<T>() -> Option<ffi::setattrofunc>
                 let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
                 let key = PyObject::from_borrowed_ptr(py, key);
 
-                // if value is none, then __delitem__
+                // if value is none, then __delattr__
                 let ret = if value.is_null() {
-                    slf.__delattr__(py, &key)
+                    <T as PyObjectDelAttrProtocolImpl>::delattr(&slf, py, &key)
                 } else {
-                    let value = PyObject::from_borrowed_ptr(py, value);
-                    let ret = slf.__setattr__(py, &key, &value);
-                    PyDrop::release_ref(value, py);
-                    ret
+                    <T as PyObjectSetAttrProtocolImpl>::setattr(&slf, py, &key, value)
                 };
 
                 PyDrop::release_ref(key, py);
@@ -184,26 +366,40 @@ fn extract_op(py: Python, op: c_int) -> PyResult<CompareOp> {
     }
 }
 
-fn tp_richcompare<T>() -> Option<ffi::richcmpfunc>
-    where T: PyObjectProtocol + PythonObject
-{
-    unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
-                                 arg: *mut ffi::PyObject,
-                                 op: c_int) -> *mut ffi::PyObject
-        where T: PyObjectProtocol + PythonObject
-    {
-        const LOCATION: &'static str = concat!(stringify!(T), ".__richcmp__()");
-        handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
-            let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-            let arg = PyObject::from_borrowed_ptr(py, arg);
-            let ret = match extract_op(py, op) {
-                Ok(op) => slf.__richcmp__(py, &arg, op),
-                Err(_) => Ok(py.NotImplemented())
-            };
-            PyDrop::release_ref(arg, py);
-            PyDrop::release_ref(slf, py);
-            ret
-        })
+trait PyObjectRichcmpProtocolImpl {
+    fn tp_richcompare() -> Option<ffi::richcmpfunc>;
+}
+
+impl<T> PyObjectRichcmpProtocolImpl for T where T: PyObjectProtocol {
+    #[inline]
+    default fn tp_richcompare() -> Option<ffi::richcmpfunc> { None }
+}
+
+impl<T> PyObjectRichcmpProtocolImpl for T where T: PyObjectRichcmpProtocol {
+    #[inline]
+    fn tp_richcompare() -> Option<ffi::richcmpfunc> {
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     arg: *mut ffi::PyObject,
+                                     op: c_int) -> *mut ffi::PyObject
+            where T: PyObjectRichcmpProtocol
+        {
+            const LOCATION: &'static str = "T.__richcmp__()";
+            handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+                let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+                let arg = PyObject::from_borrowed_ptr(py, arg);
+                let ret = match (arg.extract(py), extract_op(py, op)) {
+                    (Ok(arg), Ok(op)) => {
+                        let ret: PyResult<T::Success> = py_borrow_call!(
+                            py, T, &slf, false, slf.__richcmp__(py, arg, op).into());
+                        ret.map(|val| val.into_py_object(py))
+                    },
+                    _ => Ok(py.NotImplemented()),
+                };
+                PyDrop::release_ref(arg, py);
+                PyDrop::release_ref(slf, py);
+                ret
+            })
+        }
+        Some(wrap::<T>)
     }
-    Some(wrap::<T>)
 }
@@ -2,90 +2,267 @@
 
 //! Represent Python Buffer protocol implementation
 //!
+//! Class exports memory of its instance data as `PyBufferView`, the library fills
+//! `Py_buffer` for the consumer. Exported memory is kept alive by the instance,
+//! mutable borrow of the instance data raises `BufferError` while exports exist,
+//! shared borrow raises it while writable exports exist. Memory is exported writable
+//! only to consumers that request writable buffer (`PyBUF_WRITABLE`).
+//!
 //! more information on buffer protocol can be found
 //! https://docs.python.org/3/c-api/buffer.html
 
-use std::os::raw::c_int;
+use std::mem;
+use std::ptr;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
 
 use ffi;
-use err::PyResult;
-use python::{Python, PythonObject};
-use objects::PyObject;
+use err::{PyErr, PyResult};
+use python::{Python, PythonObject, PyDrop};
+use objects::{exc, PyObject};
+use buffer::Element;
 use callback::{handle_callback, UnitCallbackConverter};
-use class::NO_METHODS;
+use class::cell::{PyCellProtocolImpl, BorrowGuard, PyRef, PyRefMut};
 
 
 /// Buffer protocol interface
-pub trait PyBufferProtocol {
-
-    fn bf_getbuffer(&self, py: Python, view: *mut ffi::Py_buffer, flags: c_int)
-                    -> PyResult<()>;
+///
+/// `bf_getbuffer` exports field borrowed by its accessor, `PyBufferView::new`
+/// for read-only and `PyBufferView::new_mut` for writable memory.
+/// `writable` is true if the consumer requested writable buffer, writable export
+/// blocks shared borrows of instance data, so `new_mut` should be used only then.
+/// Writable request of read-only view raises `BufferError`.
+pub trait PyBufferProtocol: PythonObject {
+    type Element: Element;
 
-    fn bf_releasebuffer(&self, py: Python, view: *mut ffi::Py_buffer)
-                        -> PyResult<()>;
+    fn bf_getbuffer<'a>(&'a self, py: Python<'a>, writable: bool)
+                        -> PyResult<PyBufferView<'a, Self::Element>>;
 }
 
-#[doc(hidden)]
-pub trait PyBufferProtocolImpl {
-    fn methods() -> &'static [&'static str];
+
+/// Memory exported through the buffer protocol, one-dimensional by default.
+///
+/// The view keeps instance data borrowed until `Py_buffer` is filled.
+pub struct PyBufferView<'a, T: Element + 'a> {
+    ptr: *mut T,
+    len: usize,
+    readonly: bool,
+    // position of the first item in the memory
+    start: usize,
+    shape: Vec<usize>,
+    // in items, not in bytes
+    strides: Vec<isize>,
+    _guard: BorrowGuard<'a>,
+    _marker: PhantomData<&'a T>,
 }
 
-impl<T> PyBufferProtocolImpl for T {
-    default fn methods() -> &'static [&'static str] {
-        NO_METHODS
+impl<'a, T: Element + 'a> PyBufferView<'a, T> {
+    /// Read-only view of borrowed `data`, i.e. of a field returned by its accessor
+    pub fn new<D>(data: PyRef<'a, D>) -> PyBufferView<'a, T> where D: AsRef<[T]> + ?Sized {
+        let (data, guard) = PyRef::into_parts(data);
+        let data = data.as_ref();
+        PyBufferView::from_raw(data.as_ptr() as *mut T, data.len(), true, guard)
     }
-}
 
-impl<T> PyBufferProtocol for T {
+    /// Writable view of mutably borrowed `data`,
+    /// read-only for consumers that do not request writable buffer
+    pub fn new_mut<D>(data: PyRefMut<'a, D>) -> PyBufferView<'a, T>
+        where D: AsMut<[T]> + ?Sized
+    {
+        let (data, guard) = PyRefMut::into_parts(data);
+        let data = data.as_mut();
+        PyBufferView::from_raw(data.as_mut_ptr(), data.len(), false, guard)
+    }
 
-    default fn bf_getbuffer(&self, _py: Python,
-                            _view: *mut ffi::Py_buffer, _flags: c_int) -> PyResult<()> {
-        Ok(())
+    fn from_raw(ptr: *mut T, len: usize, readonly: bool, guard: BorrowGuard<'a>)
+                -> PyBufferView<'a, T> {
+        PyBufferView {
+            ptr: ptr,
+            len: len,
+            readonly: readonly,
+            start: 0,
+            shape: vec![len],
+            strides: vec![1],
+            _guard: guard,
+            _marker: PhantomData,
+        }
     }
-    default fn bf_releasebuffer(&self, _py: Python,
-                                _view: *mut ffi::Py_buffer) -> PyResult<()> {
+
+    /// Multi-dimensional view of the memory in C order (row-major).
+    /// Raises `BufferError` if the memory is smaller than the shape.
+    pub fn reshape(self, py: Python, shape: &[usize]) -> PyResult<PyBufferView<'a, T>> {
+        let mut strides = vec![1; shape.len()];
+        for i in (1..shape.len()).rev() {
+            strides[i - 1] = strides[i] * shape[i] as isize;
+        }
+        self.with_strides(py, shape, &strides)
+    }
+
+    /// Multi-dimensional view of the memory, `strides` are in items, not in bytes.
+    /// Negative stride walks the memory backwards.
+    /// Raises `BufferError` if any item of the view is out of the memory.
+    pub fn with_strides(mut self, py: Python, shape: &[usize], strides: &[isize])
+                        -> PyResult<PyBufferView<'a, T>> {
+        if shape.len() != strides.len() {
+            return Err(PyErr::new::<exc::BufferError, _>(
+                py, "shape and strides must have the same length"))
+        }
+        if shape.len() > ffi::PyBUF_MAX_NDIM as usize {
+            return Err(PyErr::new::<exc::BufferError, _>(py, "too many dimensions"))
+        }
+
+        // offsets of the first and the last item, relative to the item at index 0
+        let (mut min, mut max) = (0isize, 0isize);
+        if !shape.contains(&0) {
+            for (&dim, &stride) in shape.iter().zip(strides.iter()) {
+                let offset = (dim as isize - 1).checked_mul(stride);
+                match offset {
+                    Some(offset) if offset < 0 => min += offset,
+                    Some(offset) => max += offset,
+                    None => return Err(PyErr::new::<exc::BufferError, _>(
+                        py, "strides are out of range")),
+                }
+            }
+            if max - min >= self.len as isize {
+                return Err(PyErr::new::<exc::BufferError, _>(
+                    py, "shape and strides do not fit into the memory"))
+            }
+        }
+
+        self.start = (-min) as usize;
+        self.shape = shape.to_vec();
+        self.strides = strides.to_vec();
+        Ok(self)
+    }
+
+    fn is_contiguous(&self, fortran: bool) -> bool {
+        if self.shape.contains(&0) {
+            return true
+        }
+        let mut expected = 1;
+        let mut dims: Vec<(usize, isize)> =
+            self.shape.iter().cloned().zip(self.strides.iter().cloned()).collect();
+        if !fortran {
+            dims.reverse();
+        }
+        for (dim, stride) in dims {
+            if dim != 1 && stride != expected {
+                return false
+            }
+            expected *= dim as isize;
+        }
+        true
+    }
+
+    /// Fills `view` for the consumer, `flags` are flags of the request.
+    unsafe fn fill(self, py: Python, view: &mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        let has = |flag: c_int| flags & flag == flag;
+
+        if has(ffi::PyBUF_WRITABLE) && self.readonly {
+            return Err(PyErr::new::<exc::BufferError, _>(py, "Object is not writable"))
+        }
+        let c_contiguous = self.is_contiguous(false);
+        if !has(ffi::PyBUF_STRIDES) && !c_contiguous {
+            return Err(PyErr::new::<exc::BufferError, _>(py, "Object is not C-contiguous"))
+        }
+        if (has(ffi::PyBUF_C_CONTIGUOUS) && !c_contiguous) ||
+            (has(ffi::PyBUF_F_CONTIGUOUS) && !self.is_contiguous(true)) ||
+            (has(ffi::PyBUF_ANY_CONTIGUOUS) && !c_contiguous && !self.is_contiguous(true))
+        {
+            return Err(PyErr::new::<exc::BufferError, _>(
+                py, "Object does not have requested contiguity"))
+        }
+
+        let itemsize = mem::size_of::<T>();
+        let count: usize = self.shape.iter().product();
+
+        // shape followed by strides in bytes, released by `bf_releasebuffer`
+        let mut internal: Box<Vec<ffi::Py_ssize_t>> = Box::new(
+            self.shape.iter().map(|&dim| dim as ffi::Py_ssize_t).collect());
+        internal.extend(self.strides.iter().map(|&stride| stride * itemsize as isize));
+        let ndim = self.shape.len();
+
+        view.buf = self.ptr.offset(self.start as isize) as *mut c_void;
+        view.obj = ptr::null_mut();
+        view.len = (count * itemsize) as ffi::Py_ssize_t;
+        view.itemsize = itemsize as ffi::Py_ssize_t;
+        // writable export blocks shared borrows, so it is made only on request
+        view.readonly = (self.readonly || !has(ffi::PyBUF_WRITABLE)) as c_int;
+        view.ndim = ndim as c_int;
+        view.format = if has(ffi::PyBUF_FORMAT) {
+            T::format().as_ptr() as *mut _
+        } else {
+            ptr::null_mut()
+        };
+        view.shape = if has(ffi::PyBUF_ND) {
+            internal.as_mut_ptr()
+        } else {
+            ptr::null_mut()
+        };
+        view.strides = if has(ffi::PyBUF_STRIDES) {
+            internal.as_mut_ptr().offset(ndim as isize)
+        } else {
+            ptr::null_mut()
+        };
+        view.suboffsets = ptr::null_mut();
+        view.internal = Box::into_raw(internal) as *mut c_void;
         Ok(())
     }
 }
 
 
-impl ffi::PyBufferProcs {
+#[doc(hidden)]
+pub trait PyBufferProtocolImpl {
+    fn tp_as_buffer() -> Option<ffi::PyBufferProcs>;
+}
 
-    /// Construct PyBufferProcs struct for PyTypeObject.tp_as_buffer
-    pub fn new<T>() -> Option<ffi::PyBufferProcs>
-        where T: PyBufferProtocol + PyBufferProtocolImpl + PythonObject
-    {
-        let methods = T::methods();
-        if methods.is_empty() {
-            return None
+impl<T> PyBufferProtocolImpl for T {
+    #[inline]
+    default fn tp_as_buffer() -> Option<ffi::PyBufferProcs> { None }
+}
+
+impl<T> PyBufferProtocolImpl for T where T: PyBufferProtocol {
+    #[inline]
+    fn tp_as_buffer() -> Option<ffi::PyBufferProcs> {
+        Some(ffi::PyBufferProcs {
+            bf_getbuffer: Some(bf_getbuffer::<T>),
+            bf_releasebuffer: Some(bf_releasebuffer::<T>),
+        })
+    }
+}
+
+unsafe extern "C" fn bf_getbuffer<T>(slf: *mut ffi::PyObject,
+                                     view: *mut ffi::Py_buffer, flags: c_int) -> c_int
+    where T: PyBufferProtocol
+{
+    const LOCATION: &'static str = "T.bf_getbuffer()";
+    handle_callback(LOCATION, UnitCallbackConverter, |py| {
+        if view.is_null() {
+            return Err(PyErr::new::<exc::BufferError, _>(py, "View is null"))
         }
+        let obj = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+        let ret = py_borrow_call!(
+            py, T, &obj, false,
+            obj.bf_getbuffer(py, flags & ffi::PyBUF_WRITABLE != 0)
+                .and_then(|data| data.fill(py, &mut *view, flags)));
+        PyDrop::release_ref(obj, py);
 
-        let mut buf_procs: ffi::PyBufferProcs = ffi::PyBufferProcs_INIT;
-
-        for name in methods {
-            match name {
-                &"bf_getbuffer" => {
-                    buf_procs.bf_getbuffer = {
-                        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject, arg1: *mut ffi::Py_buffer, arg2: c_int) -> c_int
-                            where T: PyBufferProtocol + PythonObject
-                        {
-                            const LOCATION: &'static str = concat!(stringify!(T), ".buffer_get::<PyBufferProtocol>()");
-                            handle_callback(LOCATION, UnitCallbackConverter,
-                                            |py| {
-                                                let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-                                                let result = slf.bf_getbuffer(py, arg1, arg2);
-                                                ::PyDrop::release_ref(slf, py);
-                                                result
-                                            }
-                            )
-                        }
-                        Some(wrap::<T>)
-                    }
-                },
-                _ => ()
-            }
+        if ret.is_ok() {
+            // consumer releases the reference after `bf_releasebuffer`
+            ffi::Py_INCREF(slf);
+            (*view).obj = slf;
+            <T as PyCellProtocolImpl>::export_data(slf, true, (*view).readonly == 0);
         }
+        ret
+    })
+}
 
-        Some(buf_procs)
+unsafe extern "C" fn bf_releasebuffer<T>(slf: *mut ffi::PyObject, view: *mut ffi::Py_buffer)
+    where T: PyBufferProtocol
+{
+    if !(*view).internal.is_null() {
+        drop(Box::from_raw((*view).internal as *mut Vec<ffi::Py_ssize_t>));
+        (*view).internal = ptr::null_mut();
     }
+    <T as PyCellProtocolImpl>::export_data(slf, false, (*view).readonly == 0);
 }
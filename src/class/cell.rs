@@ -0,0 +1,327 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+//! Runtime borrow checking of `#[class]` instance data.
+//!
+//! Python code can call back into an object while one of its methods is running,
+//! so instance data is kept in `PyCell`, which tracks borrows like `RefCell` does.
+//! Conflicting borrow raises python `RuntimeError` instead of panicking.
+//!
+//! Borrows are tracked on two levels. Wrappers of `#[methods]` and of protocol
+//! methods hold shared method borrow while `&self` method runs and exclusive
+//! method borrow for `&mut self` method, so python code can not re-enter
+//! the object while `&mut self` method runs. Instance data itself is borrowed
+//! by field accessors, properties and class's `borrow()` and `borrow_mut()`
+//! methods, which return `PyRef` and `PyRefMut` guards. Handles of the same
+//! object can not hold aliasing mutable references to its data.
+//!
+//! Instance data exported through the buffer protocol can not be moved or resized,
+//! mutable data borrow raises python `BufferError` while exports exist.
+//! Writable export mutably borrows the data, so shared data borrow raises
+//! `BufferError` too. Method borrows are not affected by exports.
+
+use std::cell::{Cell, UnsafeCell};
+use std::ops::{Deref, DerefMut};
+
+use ffi;
+use err::{PyErr, PyResult};
+use objects::exc;
+use python::{Python, PythonObject};
+
+
+type BorrowFlag = isize;
+const UNUSED: BorrowFlag = 0;
+const WRITING: BorrowFlag = -1;
+
+/// Mutable memory location with dynamically checked borrow rules,
+/// used as storage of `#[class]` instance data.
+pub struct PyCell<T> {
+    borrow: Cell<BorrowFlag>,
+    method: Cell<BorrowFlag>,
+    exports: Cell<usize>,
+    writable_exports: Cell<usize>,
+    value: UnsafeCell<T>,
+}
+
+impl<T> PyCell<T> {
+    pub fn new(value: T) -> PyCell<T> {
+        PyCell {
+            borrow: Cell::new(UNUSED),
+            method: Cell::new(UNUSED),
+            exports: Cell::new(0),
+            writable_exports: Cell::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Immutably borrows the value, fails if the value is currently mutably borrowed
+    /// or exported through the buffer protocol as writable memory.
+    pub fn try_borrow<'a>(&'a self, py: Python) -> PyResult<PyRef<'a, T>> {
+        if self.writable_exports.get() != 0 {
+            return Err(PyErr::new::<exc::BufferError, _>(
+                py, "Existing writable exports of data: object cannot be borrowed"))
+        }
+        let guard = acquire(py, &self.borrow, false)?;
+        Ok(PyRef { value: unsafe { &*self.value.get() }, _guard: guard })
+    }
+
+    /// Mutably borrows the value, fails if the value is currently borrowed
+    /// or exported through the buffer protocol.
+    pub fn try_borrow_mut<'a>(&'a self, py: Python) -> PyResult<PyRefMut<'a, T>> {
+        if self.exports.get() != 0 {
+            return Err(PyErr::new::<exc::BufferError, _>(
+                py, "Existing exports of data: object cannot be re-sized"))
+        }
+        let guard = acquire(py, &self.borrow, true)?;
+        Ok(PyRefMut { value: unsafe { &mut *self.value.get() }, _guard: guard })
+    }
+
+    /// Checks if `try_borrow` or `try_borrow_mut` would succeed,
+    /// without raising an exception.
+    pub fn can_borrow(&self, mutable: bool) -> bool {
+        let flag = self.borrow.get();
+        if self.writable_exports.get() != 0 {
+            false
+        } else if mutable {
+            flag == UNUSED && self.exports.get() == 0
+        } else {
+            flag != WRITING
+        }
+    }
+
+    /// Checks if the value is mutably borrowed by `try_borrow_mut`,
+    /// buffer exports are not counted.
+    pub fn is_borrowed_mut(&self) -> bool {
+        self.borrow.get() == WRITING
+    }
+
+    /// Marks the object as used by a method, without borrowing the value.
+    ///
+    /// Method and slot wrappers hold the guard while the method runs,
+    /// the method itself borrows the value with `try_borrow` and `try_borrow_mut`.
+    pub fn borrow_guard<'a>(&'a self, py: Python, mutable: bool) -> PyResult<BorrowGuard<'a>> {
+        acquire(py, &self.method, mutable)
+    }
+
+    /// Counts export of the value through the buffer protocol.
+    pub fn add_export(&self, writable: bool) {
+        self.exports.set(self.exports.get() + 1);
+        if writable {
+            self.writable_exports.set(self.writable_exports.get() + 1)
+        }
+    }
+
+    /// Releases export of the value, added by `add_export`.
+    pub fn release_export(&self, writable: bool) {
+        self.exports.set(self.exports.get() - 1);
+        if writable {
+            self.writable_exports.set(self.writable_exports.get() - 1)
+        }
+    }
+
+    /// Raw pointer to the value, borrow state is not checked.
+    pub fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+}
+
+fn acquire<'a>(py: Python, borrow: &'a Cell<BorrowFlag>, mutable: bool)
+               -> PyResult<BorrowGuard<'a>> {
+    let flag = borrow.get();
+    if mutable {
+        if flag != UNUSED {
+            return Err(PyErr::new::<exc::RuntimeError, _>(py, "Already borrowed"))
+        }
+        borrow.set(WRITING);
+    } else {
+        if flag == WRITING {
+            return Err(PyErr::new::<exc::RuntimeError, _>(py, "Already mutably borrowed"))
+        }
+        borrow.set(flag + 1);
+    }
+    Ok(BorrowGuard { borrow: borrow })
+}
+
+/// Releases borrow of `PyCell` when dropped.
+pub struct BorrowGuard<'a> {
+    borrow: &'a Cell<BorrowFlag>,
+}
+
+impl<'a> Drop for BorrowGuard<'a> {
+    fn drop(&mut self) {
+        let flag = self.borrow.get();
+        if flag == WRITING {
+            self.borrow.set(UNUSED)
+        } else {
+            self.borrow.set(flag - 1)
+        }
+    }
+}
+
+/// Immutable reference to the value of `PyCell`.
+pub struct PyRef<'a, T: 'a + ?Sized> {
+    value: &'a T,
+    _guard: BorrowGuard<'a>,
+}
+
+impl<'a, T: ?Sized> PyRef<'a, T> {
+    /// Makes a new `PyRef` for a component of the borrowed data.
+    pub fn map<U: ?Sized, F>(orig: PyRef<'a, T>, f: F) -> PyRef<'a, U>
+        where F: FnOnce(&T) -> &U
+    {
+        PyRef { value: f(orig.value), _guard: orig._guard }
+    }
+
+    /// Splits the reference into the value and the guard that keeps the value borrowed.
+    #[doc(hidden)]
+    pub fn into_parts(orig: PyRef<'a, T>) -> (&'a T, BorrowGuard<'a>) {
+        (orig.value, orig._guard)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for PyRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+/// Mutable reference to the value of `PyCell`.
+pub struct PyRefMut<'a, T: 'a + ?Sized> {
+    value: &'a mut T,
+    _guard: BorrowGuard<'a>,
+}
+
+impl<'a, T: ?Sized> PyRefMut<'a, T> {
+    /// Makes a new `PyRefMut` for a component of the borrowed data.
+    pub fn map<U: ?Sized, F>(orig: PyRefMut<'a, T>, f: F) -> PyRefMut<'a, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let PyRefMut { value, _guard } = orig;
+        PyRefMut { value: f(value), _guard: _guard }
+    }
+
+    /// Splits the reference into the value and the guard that keeps the value borrowed.
+    #[doc(hidden)]
+    pub fn into_parts(orig: PyRefMut<'a, T>) -> (&'a mut T, BorrowGuard<'a>) {
+        (orig.value, orig._guard)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for PyRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for PyRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+
+/// Borrow of instance data by method and slot wrappers,
+/// types without `#[class]` instance data are not checked.
+#[doc(hidden)]
+pub trait PyCellProtocolImpl {
+    unsafe fn borrow_data<'a>(py: Python, obj: *mut ffi::PyObject, mutable: bool)
+                              -> PyResult<Option<BorrowGuard<'a>>>;
+
+    /// Adds or releases buffer export of instance data.
+    unsafe fn export_data(obj: *mut ffi::PyObject, export: bool, writable: bool);
+}
+
+impl<T> PyCellProtocolImpl for T {
+    default unsafe fn borrow_data<'a>(_py: Python, _obj: *mut ffi::PyObject, _mutable: bool)
+                                      -> PyResult<Option<BorrowGuard<'a>>> {
+        Ok(None)
+    }
+
+    default unsafe fn export_data(_obj: *mut ffi::PyObject, _export: bool, _writable: bool) {}
+}
+
+/// Borrows instance data of `obj` for a protocol method call.
+#[doc(hidden)]
+pub fn borrow_object<'a, T>(py: Python, obj: &'a T, mutable: bool)
+                            -> PyResult<Option<BorrowGuard<'a>>> where T: PythonObject
+{
+    unsafe { <T as PyCellProtocolImpl>::borrow_data(py, obj.as_object().as_ptr(), mutable) }
+}
+
+
+#[cfg(test)]
+mod test {
+    use python::{Python, PythonObject};
+    use objects::exc;
+    use super::{PyCell, PyRef};
+
+    #[test]
+    fn test_borrow() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let cell = PyCell::new(1);
+
+        {
+            let r1 = cell.try_borrow(py).unwrap();
+            let r2 = cell.try_borrow(py).unwrap();
+            assert_eq!(*r1 + *r2, 2);
+            let err = cell.try_borrow_mut(py).err().unwrap();
+            assert!(err.matches(py, py.get_type::<exc::RuntimeError>()));
+        }
+        {
+            let mut w = cell.try_borrow_mut(py).unwrap();
+            *w = 5;
+            assert!(cell.try_borrow(py).is_err());
+            assert!(cell.try_borrow_mut(py).is_err());
+        }
+        assert_eq!(*cell.try_borrow(py).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_borrow_guard() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let cell = PyCell::new(String::from("data"));
+
+        {
+            let _guard = cell.borrow_guard(py, true).unwrap();
+            assert!(cell.borrow_guard(py, false).is_err());
+            // method borrow does not borrow the value
+            assert!(cell.can_borrow(true));
+            let mut w = cell.try_borrow_mut(py).unwrap();
+            w.push_str("!");
+            assert!(!cell.can_borrow(false));
+        }
+        assert!(cell.borrow_guard(py, true).is_ok());
+        assert!(cell.can_borrow(true));
+        let r = PyRef::map(cell.try_borrow(py).unwrap(), |s| s.as_str());
+        assert_eq!(&*r, "data!");
+    }
+
+    #[test]
+    fn test_export() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let cell = PyCell::new(vec![1, 2, 3]);
+
+        cell.add_export(false);
+        assert!(cell.try_borrow(py).is_ok());
+        assert!(!cell.can_borrow(true));
+        let err = cell.try_borrow_mut(py).err().unwrap();
+        assert!(err.matches(py, py.get_type::<exc::BufferError>()));
+        // exports do not block method borrows
+        assert!(cell.borrow_guard(py, true).is_ok());
+        cell.release_export(false);
+        assert!(cell.try_borrow_mut(py).is_ok());
+
+        cell.add_export(true);
+        assert!(!cell.can_borrow(false));
+        let err = cell.try_borrow(py).err().unwrap();
+        assert!(err.matches(py, py.get_type::<exc::BufferError>()));
+        cell.release_export(true);
+        assert!(cell.try_borrow(py).is_ok());
+    }
+}
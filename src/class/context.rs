@@ -5,50 +5,49 @@
 //!
 
 use err::PyResult;
-use python::Python;
-use objects::PyObject;
-use class::{NO_METHODS, NO_PY_METHODS};
+use python::{Python, PythonObject};
+use conversion::{ToPyObject, FromPyObject};
+use class::NO_PY_METHODS;
 
 
-/// Awaitable interface
-pub trait PyContextProtocol {
+/// Context manager interface
+#[allow(unused_variables)]
+pub trait PyContextProtocol: PythonObject {
 
-    fn __enter__(&self, py: Python) -> PyResult<PyObject>;
+    fn __enter__(&self, py: Python) -> Self::Result
+        where Self: PyContextEnterProtocol
+    { unimplemented!() }
 
     fn __exit__(&self, py: Python,
-                exc_type: Option<PyObject>,
-                exc_value: Option<PyObject>,
-                traceback: Option<PyObject>) -> PyResult<PyObject>;
+                exc_type: Self::ExcType,
+                exc_value: Self::ExcValue,
+                traceback: Self::Traceback) -> Self::Result
+        where Self: PyContextExitProtocol
+    { unimplemented!() }
 }
 
+pub trait PyContextEnterProtocol: PyContextProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
 
-impl<P> PyContextProtocol for P {
-
-    default fn __enter__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.None())
-    }
-
-    default fn __exit__(&self, py: Python,
-                        _exc_type: Option<PyObject>,
-                        _exc_value: Option<PyObject>,
-                        _traceback: Option<PyObject>) -> PyResult<PyObject> {
-        Ok(py.None())
-    }
+pub trait PyContextExitProtocol: PyContextProtocol {
+    type ExcType: for<'a> FromPyObject<'a>;
+    type ExcValue: for<'a> FromPyObject<'a>;
+    type Traceback: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
 }
 
 
+/// Context manager methods have no slots, they are added as methods by `#[proto]`.
 #[doc(hidden)]
 pub trait PyContextProtocolImpl {
-    fn methods() -> &'static [&'static str];
-
-    fn py_methods() -> &'static [::methods::PyMethodDefType];
+    fn py_methods() -> &'static [::class::PyMethodDefType];
 }
 
 impl<T> PyContextProtocolImpl for T {
-    default fn methods() -> &'static [&'static str] {
-        NO_METHODS
-    }
-    default fn py_methods() -> &'static [::methods::PyMethodDefType] {
+    default fn py_methods() -> &'static [::class::PyMethodDefType] {
         NO_PY_METHODS
     }
 }
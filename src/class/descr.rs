@@ -9,75 +9,205 @@ use std::os::raw::c_int;
 
 use ffi;
 use err::{PyErr, PyResult};
-use python::{Python, PythonObject};
+use python::{Python, PythonObject, PyDrop};
 use objects::{exc, PyObject};
-use class::{NO_METHODS, NO_PY_METHODS};
-use callback::{PyObjectCallbackConverter, UnitCallbackConverter};
+use conversion::{ToPyObject, FromPyObject};
+use callback::{handle_callback, PyObjectCallbackConverter, UnitCallbackConverter};
+use class::NO_PY_METHODS;
 
 /// Descriptor interface
-pub trait PyDescrProtocol {
+#[allow(unused_variables)]
+pub trait PyDescrProtocol: PythonObject {
 
-    fn __get__(&self, py: Python, instance: &PyObject, owner: &PyObject) -> PyResult<PyObject>;
+    /// `instance` and `owner` are `None` if python passes no value.
+    fn __get__(&self, py: Python, instance: Self::Inst, owner: Self::Owner) -> Self::Result
+        where Self: PyDescrGetProtocol
+    { unimplemented!() }
 
-    fn __set__(&self, py: Python, instance: &PyObject, value: &PyObject) -> PyResult<()>;
+    fn __set__(&self, py: Python, instance: Self::Inst, value: Self::Value) -> Self::Result
+        where Self: PyDescrSetProtocol
+    { unimplemented!() }
 
-    fn __delete__(&self, py: Python, instance: &PyObject) -> PyResult<()>;
+    fn __delete__(&self, py: Python, instance: Self::Inst) -> Self::Result
+        where Self: PyDescrDeleteProtocol
+    { unimplemented!() }
 
-    fn __set_name__(&self, py: Python, instance: &PyObject) -> PyResult<()>;
+    fn __set_name__(&self, py: Python, owner: Self::Owner, name: Self::Name) -> Self::Result
+        where Self: PyDescrSetNameProtocol
+    { unimplemented!() }
+}
+
+pub trait PyDescrGetProtocol: PyDescrProtocol {
+    type Inst: for<'a> FromPyObject<'a>;
+    type Owner: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyDescrSetProtocol: PyDescrProtocol {
+    type Inst: for<'a> FromPyObject<'a>;
+    type Value: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
 
+pub trait PyDescrDeleteProtocol: PyDescrProtocol {
+    type Inst: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
 }
 
-impl<P> PyDescrProtocol for P {
-    default fn __get__(&self, py: Python, _: &PyObject, _: &PyObject) -> PyResult<PyObject> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+pub trait PyDescrSetNameProtocol: PyDescrProtocol {
+    type Owner: for<'a> FromPyObject<'a>;
+    type Name: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+/// Object for a pointer python passed to descriptor slot, `None` for NULL.
+unsafe fn object_or_none(py: Python, ptr: *mut ffi::PyObject) -> PyObject {
+    if ptr.is_null() {
+        py.None()
+    } else {
+        PyObject::from_borrowed_ptr(py, ptr)
     }
+}
+
+trait PyDescrGetProtocolImpl {
+    fn tp_descr_get() -> Option<ffi::descrgetfunc>;
+}
+
+impl<T> PyDescrGetProtocolImpl for T {
+    #[inline]
+    default fn tp_descr_get() -> Option<ffi::descrgetfunc> { None }
+}
+
+impl<T> PyDescrGetProtocolImpl for T where T: PyDescrGetProtocol {
+    #[inline]
+    fn tp_descr_get() -> Option<ffi::descrgetfunc> {
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     instance: *mut ffi::PyObject,
+                                     owner: *mut ffi::PyObject) -> *mut ffi::PyObject
+            where T: PyDescrGetProtocol
+        {
+            const LOCATION: &'static str = "T.__get__()";
+            handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+                let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+                let instance = object_or_none(py, instance);
+                let owner = object_or_none(py, owner);
+                let ret = match (instance.extract(py), owner.extract(py)) {
+                    (Ok(instance), Ok(owner)) => py_borrow_call!(
+                        py, T, &slf, false, slf.__get__(py, instance, owner).into()),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                };
+                PyDrop::release_ref(instance, py);
+                PyDrop::release_ref(owner, py);
+                PyDrop::release_ref(slf, py);
+                ret
+            })
+        }
+        Some(wrap::<T>)
+    }
+}
+
+// `__set__` and `__delete__` share `tp_descr_set` slot.
 
-    default fn __set__(&self, py: Python, _: &PyObject, _: &PyObject) -> PyResult<()> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+trait PyDescrSetProtocolImpl {
+    fn implemented() -> bool;
+    fn set(&self, py: Python, instance: &PyObject, value: &PyObject) -> PyResult<()>;
+}
+
+impl<T> PyDescrSetProtocolImpl for T {
+    #[inline]
+    default fn implemented() -> bool { false }
+
+    default fn set(&self, py: Python, _: &PyObject, _: &PyObject) -> PyResult<()> {
+        Err(PyErr::new::<exc::AttributeError, _>(py, "can't set attribute"))
     }
+}
+
+impl<T> PyDescrSetProtocolImpl for T where T: PyDescrSetProtocol {
+    #[inline]
+    fn implemented() -> bool { true }
 
-    default fn __delete__(&self, py: Python, _: &PyObject) -> PyResult<()> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+    fn set(&self, py: Python, instance: &PyObject, value: &PyObject) -> PyResult<()> {
+        match (instance.extract(py), value.extract(py)) {
+            (Ok(instance), Ok(value)) =>
+                py_borrow_call!(py, T, self, false, self.__set__(py, instance, value).into()),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        }
     }
+}
+
+trait PyDescrDeleteProtocolImpl {
+    fn implemented() -> bool;
+    fn delete(&self, py: Python, instance: &PyObject) -> PyResult<()>;
+}
+
+impl<T> PyDescrDeleteProtocolImpl for T {
+    #[inline]
+    default fn implemented() -> bool { false }
 
-    default fn __set_name__(&self, py: Python, _: &PyObject) -> PyResult<()> {
-        Err(PyErr::new::<exc::NotImplementedError, _>(py, "Not implemented"))
+    default fn delete(&self, py: Python, _: &PyObject) -> PyResult<()> {
+        Err(PyErr::new::<exc::AttributeError, _>(py, "can't delete attribute"))
     }
 }
 
+impl<T> PyDescrDeleteProtocolImpl for T where T: PyDescrDeleteProtocol {
+    #[inline]
+    fn implemented() -> bool { true }
 
-pub fn get_descrfunc<T>() -> Option<ffi::descrgetfunc>
-    where T: PyDescrProtocol + PyDescrProtocolImpl + PythonObject
-{
-    if T::methods().contains(&"__get__") {
-        py_ternary_func!(PyDescrProtocol, T::__get__, PyObjectCallbackConverter)
-    } else {
-        None
+    fn delete(&self, py: Python, instance: &PyObject) -> PyResult<()> {
+        match instance.extract(py) {
+            Ok(instance) =>
+                py_borrow_call!(py, T, self, false, self.__delete__(py, instance).into()),
+            Err(e) => Err(e),
+        }
     }
 }
 
-pub fn set_descrfunc<T>() -> Option<ffi::descrsetfunc>
-    where T: PyDescrProtocol + PyDescrProtocolImpl + PythonObject
-{
-    if T::methods().contains(&"__set__") {
-        py_ternary_func!(PyDescrProtocol, T::__set__, UnitCallbackConverter, c_int)
+pub fn set_descrfunc<T>() -> Option<ffi::descrsetfunc> where T: PythonObject {
+    unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                 instance: *mut ffi::PyObject,
+                                 value: *mut ffi::PyObject) -> c_int
+        where T: PythonObject
+    {
+        const LOCATION: &'static str = "T.__set__()";
+        handle_callback(LOCATION, UnitCallbackConverter, |py| {
+            let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+            let instance = PyObject::from_borrowed_ptr(py, instance);
+            let ret = if value.is_null() {
+                <T as PyDescrDeleteProtocolImpl>::delete(&slf, py, &instance)
+            } else {
+                let value = PyObject::from_borrowed_ptr(py, value);
+                let ret = <T as PyDescrSetProtocolImpl>::set(&slf, py, &instance, &value);
+                PyDrop::release_ref(value, py);
+                ret
+            };
+            PyDrop::release_ref(instance, py);
+            PyDrop::release_ref(slf, py);
+            ret
+        })
+    }
+
+    if <T as PyDescrSetProtocolImpl>::implemented() ||
+        <T as PyDescrDeleteProtocolImpl>::implemented()
+    {
+        Some(wrap::<T>)
     } else {
         None
     }
 }
 
+pub fn get_descrfunc<T>() -> Option<ffi::descrgetfunc> {
+    <T as PyDescrGetProtocolImpl>::tp_descr_get()
+}
+
+/// `__set_name__` has no slot, it is added as method by `#[proto]`.
 #[doc(hidden)]
 pub trait PyDescrProtocolImpl {
-    fn methods() -> &'static [&'static str];
-
-    fn py_methods() -> &'static [::methods::PyMethodDefType];
+    fn py_methods() -> &'static [::class::PyMethodDefType];
 }
 
 impl<T> PyDescrProtocolImpl for T {
-    default fn methods() -> &'static [&'static str] {
-        NO_METHODS
-    }
-    default fn py_methods() -> &'static [::methods::PyMethodDefType] {
+    default fn py_methods() -> &'static [::class::PyMethodDefType] {
         NO_PY_METHODS
     }
 }
@@ -0,0 +1,113 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+//! Free allocation list of `#[class(freelist = N)]` types.
+//!
+//! Memory of deallocated objects is kept and reused for new objects
+//! of the same type instead of going through python allocator.
+//! Only objects of exactly the class type are recycled, instances of subclasses
+//! and types with GC support are allocated and freed as usual.
+
+use std::os::raw::c_void;
+
+use ffi;
+use err::{self, PyResult};
+use objects::{PyObject, PyType};
+use python::{Python, PythonObjectWithTypeObject, PyDrop};
+use class::BaseObject;
+
+
+/// Fixed size list of free objects.
+pub struct FreeList<T> {
+    entries: Vec<T>,
+    capacity: usize,
+}
+
+impl<T> FreeList<T> {
+    /// Creates new free list which keeps at most `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> FreeList<T> {
+        FreeList { entries: Vec::with_capacity(capacity), capacity: capacity }
+    }
+
+    /// Takes entry from the list.
+    pub fn pop(&mut self) -> Option<T> {
+        self.entries.pop()
+    }
+
+    /// Puts entry to the list, returns the value back if the list is full.
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        if self.entries.len() < self.capacity {
+            self.entries.push(value);
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+
+/// Implemented by `#[class(freelist = N)]`.
+pub trait PyObjectWithFreeList: BaseObject + PythonObjectWithTypeObject {
+    /// Free list of the class, has to be accessed with the GIL held.
+    fn get_free_list(py: Python) -> &'static mut FreeList<*mut ffi::PyObject>;
+}
+
+/// Allocates object of type `ty`, reuses memory from the free list of `T`
+/// if `ty` is exactly `T` type.
+pub unsafe fn alloc<T>(py: Python, ty: &PyType, init_val: ()) -> PyResult<PyObject>
+    where T: PyObjectWithFreeList
+{
+    if is_recyclable::<T>(py, ty.as_type_ptr()) {
+        if let Some(obj) = T::get_free_list(py).pop() {
+            // restore object header, `PyObject_Init` increfs heap type since 3.8 only
+            ffi::PyObject_Init(obj, ty.as_type_ptr());
+            if cfg!(not(Py_3_8)) {
+                ffi::Py_INCREF(ty.as_type_ptr() as *mut ffi::PyObject);
+            }
+            return err::result_from_owned_ptr(py, obj)
+        }
+    }
+    <PyObject as BaseObject>::alloc(py, ty, init_val)
+}
+
+/// Keeps memory of the object in the free list of `T`, frees it if the list is full.
+pub unsafe fn dealloc<T>(py: Python, obj: *mut ffi::PyObject)
+    where T: PyObjectWithFreeList
+{
+    let ty = ffi::Py_TYPE(obj);
+    if is_recyclable::<T>(py, ty) {
+        if let Some(obj) = T::get_free_list(py).insert(obj) {
+            <PyObject as BaseObject>::dealloc(py, obj)
+        } else {
+            // object in the free list does not keep its type alive
+            ffi::Py_DECREF(ty as *mut ffi::PyObject);
+        }
+        return
+    }
+    <PyObject as BaseObject>::dealloc(py, obj)
+}
+
+unsafe fn is_recyclable<T>(py: Python, ty: *mut ffi::PyTypeObject) -> bool
+    where T: PyObjectWithFreeList
+{
+    let cls = py.get_type::<T>();
+    let exact = cls.as_type_ptr() == ty;
+    cls.release_ref(py);
+    exact && ffi::PyType_IS_GC(ty) == 0
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::FreeList;
+
+    #[test]
+    fn test_free_list() {
+        let mut list = FreeList::with_capacity(2);
+        assert_eq!(list.insert(1), None);
+        assert_eq!(list.insert(2), None);
+        assert_eq!(list.insert(3), Some(3));
+        assert_eq!(list.pop(), Some(2));
+        assert_eq!(list.pop(), Some(1));
+        assert_eq!(list.pop(), None);
+    }
+}
@@ -2,8 +2,16 @@
 
 //! Python GC support
 //!
+//! `#[class(gc)]` implements `PyGCProtocol` over instance data fields,
+//! fields that hold python references are visited and cleared through `PyTraverse`.
+//! Typed references have to be stored as `Option<T>`, which is cleared to `None`,
+//! or as `PyObject`, which is cleared to python `None`. Cleared values are dropped,
+//! `PyObject` releases its reference on drop.
 
 use std::mem;
+use std::hash::{Hash, BuildHasher};
+use std::cell::RefCell;
+use std::collections::{HashMap, BTreeMap};
 use std::os::raw::{c_int, c_void};
 
 use ffi;
@@ -11,6 +19,7 @@ use python::{Python, PythonObject, PyDrop, ToPythonPointer};
 use objects::PyObject;
 use callback::AbortOnDrop;
 use class::NO_METHODS;
+use class::typeob::TypeSlots;
 
 pub struct PyTraverseError(c_int);
 
@@ -35,7 +44,7 @@ impl<T> PyGCProtocol for T {
 pub trait PyGCProtocolImpl {
     fn methods() -> &'static [&'static str];
 
-    fn update_type_object(type_object: &mut ffi::PyTypeObject);
+    fn update_type_slots(slots: &mut TypeSlots);
 }
 
 impl<T> PyGCProtocolImpl for T where T: PyGCProtocol + PythonObject {
@@ -43,18 +52,134 @@ impl<T> PyGCProtocolImpl for T where T: PyGCProtocol + PythonObject {
         NO_METHODS
     }
 
-    fn update_type_object(type_object: &mut ffi::PyTypeObject) {
-        if <T as PyGCProtocolImpl>::methods().is_empty() {
-            type_object.tp_flags = ffi::Py_TPFLAGS_DEFAULT
-        } else {
-            type_object.tp_flags = ffi::Py_TPFLAGS_DEFAULT | ffi::Py_TPFLAGS_HAVE_GC;
-            type_object.tp_traverse = Some(tp_traverse::<T>);
-            type_object.tp_clear = Some(tp_clear::<T>);
+    fn update_type_slots(slots: &mut TypeSlots) {
+        if !<T as PyGCProtocolImpl>::methods().is_empty() {
+            slots.flags |= ffi::Py_TPFLAGS_HAVE_GC;
+            slots.push(ffi::Py_tp_traverse, tp_traverse::<T> as *mut c_void);
+            slots.push(ffi::Py_tp_clear, tp_clear::<T> as *mut c_void);
         }
     }
 }
 
 
+/// Python references held by a value, visited and cleared by `#[class(gc)]`
+///
+/// Implement for user types stored in `#[class(gc)]` fields.
+pub trait PyTraverse {
+    /// Visits every python object the value refers to.
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError>;
+
+    /// Drops references that may take part in a reference cycle.
+    fn clear(&mut self, py: Python);
+}
+
+impl PyTraverse for PyObject {
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        visit.call(self)
+    }
+
+    fn clear(&mut self, py: Python) {
+        *self = py.None()
+    }
+}
+
+/// Typed reference can not be replaced by python `None`, so it is cleared
+/// as `Option<T>`, bare typed fields are not visited.
+impl<T> PyTraverse for Option<T> where T: PythonObject {
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        match *self {
+            Some(ref obj) => visit.call(obj),
+            None => Ok(()),
+        }
+    }
+
+    fn clear(&mut self, _py: Python) {
+        *self = None
+    }
+}
+
+/// Mutably borrowed value is skipped, the borrow holder keeps its references.
+impl<T> PyTraverse for RefCell<T> where T: PyTraverse {
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        match self.try_borrow() {
+            Ok(value) => value.traverse(visit),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn clear(&mut self, py: Python) {
+        self.get_mut().clear(py)
+    }
+}
+
+impl<T> PyTraverse for Vec<T> where T: PyTraverse {
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        for item in self.iter() {
+            item.traverse(visit)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, _py: Python) {
+        self.clear()
+    }
+}
+
+impl<K, V, S> PyTraverse for HashMap<K, V, S>
+    where K: Eq + Hash, V: PyTraverse, S: BuildHasher
+{
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        for value in self.values() {
+            value.traverse(visit)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, _py: Python) {
+        self.clear()
+    }
+}
+
+impl<K, V> PyTraverse for BTreeMap<K, V> where K: Ord, V: PyTraverse {
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        for value in self.values() {
+            value.traverse(visit)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, _py: Python) {
+        self.clear()
+    }
+}
+
+/// Field of `#[class(gc)]` instance data, fields without python references are skipped.
+#[doc(hidden)]
+pub trait PyTraverseField {
+    fn traverse_field(&self, visit: PyVisit) -> Result<(), PyTraverseError>;
+
+    fn clear_field(&mut self, py: Python);
+}
+
+impl<T> PyTraverseField for T {
+    default fn traverse_field(&self, _visit: PyVisit) -> Result<(), PyTraverseError> {
+        Ok(())
+    }
+
+    default fn clear_field(&mut self, _py: Python) {}
+}
+
+impl<T> PyTraverseField for T where T: PyTraverse {
+    fn traverse_field(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        self.traverse(visit)
+    }
+
+    fn clear_field(&mut self, py: Python) {
+        self.clear(py)
+    }
+}
+
+
 #[derive(Copy, Clone)]
 pub struct PyVisit<'a> {
     visit: ffi::visitproc,
@@ -69,7 +194,17 @@ impl <'a> PyVisit<'a> {
     pub fn call<T>(&self, obj: &T) -> Result<(), PyTraverseError>
         where T: PythonObject
     {
-        let r = unsafe { (self.visit)(obj.as_ptr(), self.arg) };
+        unsafe { self.call_ptr(obj.as_ptr()) }
+    }
+
+    /// Visits object at `ptr`, null pointer is skipped,
+    /// i.e. instance `__dict__` that is not created yet.
+    #[doc(hidden)]
+    pub unsafe fn call_ptr(&self, ptr: *mut ffi::PyObject) -> Result<(), PyTraverseError> {
+        if ptr.is_null() {
+            return Ok(())
+        }
+        let r = (self.visit)(ptr, self.arg);
         if r == 0 {
             Ok(())
         } else {
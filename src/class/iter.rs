@@ -0,0 +1,102 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+//! Python Iterator Interface
+//! Trait and support implementation for implementing iterators
+//!
+//! `__next__` returns `None` when the iterator is exhausted, the slot signals
+//! the end of iteration without creating `StopIteration` instance.
+
+use ffi;
+use err::PyResult;
+use python::{Python, PythonObject, PyDrop};
+use objects::PyObject;
+use conversion::ToPyObject;
+use callback::{handle_callback, PyObjectCallbackConverter, IterNextResultConverter};
+use class::cell::PyCellProtocolImpl;
+use class::typeob::TypeSlots;
+
+
+/// Iterator interface
+#[allow(unused_variables)]
+pub trait PyIterProtocol: PythonObject {
+    fn __iter__(&self, py: Python) -> Self::Result
+        where Self: PyIterIterProtocol { unimplemented!() }
+
+    fn __next__(&mut self, py: Python) -> Self::Result
+        where Self: PyIterNextProtocol { unimplemented!() }
+}
+
+pub trait PyIterIterProtocol: PyIterProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyIterNextProtocol: PyIterProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Option<Self::Success>>>;
+}
+
+#[doc(hidden)]
+pub trait PyIterProtocolImpl {
+    fn update_type_slots(slots: &mut TypeSlots);
+}
+
+impl<T> PyIterProtocolImpl for T {
+    #[inline]
+    default fn update_type_slots(_slots: &mut TypeSlots) {}
+}
+
+impl<T> PyIterProtocolImpl for T where T: PyIterProtocol {
+    fn update_type_slots(slots: &mut TypeSlots) {
+        push_slot!(slots, Py_tp_iter, Self::tp_iter());
+        push_slot!(slots, Py_tp_iternext, Self::tp_iternext());
+    }
+}
+
+trait PyIterIterProtocolImpl {
+    fn tp_iter() -> Option<ffi::getiterfunc>;
+}
+
+impl<T> PyIterIterProtocolImpl for T where T: PyIterProtocol {
+    #[inline]
+    default fn tp_iter() -> Option<ffi::getiterfunc> { None }
+}
+
+impl<T> PyIterIterProtocolImpl for T where T: PyIterIterProtocol {
+    #[inline]
+    fn tp_iter() -> Option<ffi::getiterfunc> {
+        py_unary_func!(PyIterIterProtocol, T::__iter__, PyObjectCallbackConverter)
+    }
+}
+
+trait PyIterNextProtocolImpl {
+    fn tp_iternext() -> Option<ffi::iternextfunc>;
+}
+
+impl<T> PyIterNextProtocolImpl for T where T: PyIterProtocol {
+    #[inline]
+    default fn tp_iternext() -> Option<ffi::iternextfunc> { None }
+}
+
+impl<T> PyIterNextProtocolImpl for T where T: PyIterNextProtocol {
+    #[inline]
+    fn tp_iternext() -> Option<ffi::iternextfunc> {
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject) -> *mut ffi::PyObject
+            where T: PyIterNextProtocol
+        {
+            const LOCATION: &'static str = "T.__next__()";
+            handle_callback(LOCATION, IterNextResultConverter, |py| {
+                let mut obj = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+                // `__next__` takes `&mut self`, instance data is borrowed for the call
+                let ret: PyResult<Option<T::Success>> =
+                    match <T as PyCellProtocolImpl>::borrow_data(py, slf, true) {
+                        Ok(_borrow) => obj.__next__(py).into(),
+                        Err(e) => Err(e),
+                    };
+                PyDrop::release_ref(obj, py);
+                ret
+            })
+        }
+        Some(wrap::<T>)
+    }
+}
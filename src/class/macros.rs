@@ -1,10 +1,23 @@
 // Copyright (c) 2017-present PyO3 Project and Contributors
 
+/// Calls protocol method of `$slf: &T` while the instance is borrowed by the method,
+/// `&mut self` methods are called with exclusive borrow.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! py_borrow_call {
+    ($py:ident, $class:ident, $slf:expr, $mutable:expr, $call:expr) => {
+        match $crate::class::cell::borrow_object::<$class>($py, $slf, $mutable) {
+            Ok(_borrow) => $call,
+            Err(e) => Err(e),
+        }
+    }
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! py_unary_func {
     ($trait:ident, $class:ident :: $f:ident, $conv:expr) => {
-        py_unary_func!($trait, $class::$f, $conv, *mut $crate::ffi::PyObject);
+        py_unary_func!($trait, $class::$f, $conv, *mut $crate::ffi::PyObject)
     };
     ($trait:ident, $class:ident :: $f:ident, $conv:expr, $res_type:ty) => {{
         unsafe extern "C" fn wrap<T>(slf: *mut $crate::ffi::PyObject) -> $res_type
@@ -13,9 +26,9 @@ macro_rules! py_unary_func {
             const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
             $crate::callback::handle_callback(LOCATION, $conv, |py| {
                 let slf = $crate::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-                let ret = slf.$f(py);
+                let ret = py_borrow_call!(py, T, &slf, false, slf.$f(py).into());
                 $crate::PyDrop::release_ref(slf, py);
-                ret.into()
+                ret
             })
         }
         Some(wrap::<$class>)
@@ -37,7 +50,7 @@ macro_rules! py_binary_func {
                 let slf = $crate::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
                 let arg = $crate::PyObject::from_borrowed_ptr(py, arg);
                 let ret = match arg.extract(py) {
-                    Ok(arg) => slf.$f(py, arg).into(),
+                    Ok(arg) => py_borrow_call!(py, T, &slf, false, slf.$f(py, arg).into()),
                     Err(e) => Err(e),
                 };
                 $crate::PyDrop::release_ref(arg, py);
@@ -49,11 +62,41 @@ macro_rules! py_binary_func {
     }}
 }
 
+/// In-place numeric slot, method mutates instance data and the slot returns `self`.
+/// Returns `NotImplemented` if the argument does not match its type,
+/// so python falls back to the binary operation.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! py_binary_inplace_func {
+    ($trait:ident, $class:ident :: $f:ident) => {{
+        unsafe extern "C" fn wrap<T>(
+            slf: *mut $crate::ffi::PyObject,
+            arg: *mut $crate::ffi::PyObject,
+        ) -> *mut $crate::ffi::PyObject
+            where T: $trait
+        {
+            const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
+            $crate::callback::handle_callback(
+                LOCATION, $crate::callback::PyObjectCallbackConverter, |py|
+            {
+                let arg = $crate::PyObject::from_borrowed_ptr(py, arg);
+                let ret = match arg.extract(py) {
+                    Ok(arg) => py_inplace_call!(py, slf, T::$f(arg)),
+                    Err(err) => $crate::class::number::not_implemented(py, err),
+                };
+                $crate::PyDrop::release_ref(arg, py);
+                ret
+            })
+        }
+        Some(wrap::<$class>)
+    }}
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! py_ternary_func {
     ($trait:ident, $class:ident :: $f:ident, $conv:expr) => {
-        py_ternary_func!($trait, $class::$f, $conv, *mut $crate::ffi::PyObject);
+        py_ternary_func!($trait, $class::$f, $conv, *mut $crate::ffi::PyObject)
     };
     ($trait:ident, $class:ident :: $f:ident, $conv:expr, $res_type:ty) => {{
         unsafe extern "C" fn wrap<T>(
@@ -68,17 +111,73 @@ macro_rules! py_ternary_func {
                 let slf = $crate::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
                 let arg1 = $crate::PyObject::from_borrowed_ptr(py, arg1);
                 let arg2 = $crate::PyObject::from_borrowed_ptr(py, arg2);
-                let ret = slf.$f(py, &arg1, &arg2);
+                let ret = match (arg1.extract(py), arg2.extract(py)) {
+                    (Ok(arg1), Ok(arg2)) =>
+                        py_borrow_call!(py, T, &slf, false, slf.$f(py, arg1, arg2).into()),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                };
                 $crate::PyDrop::release_ref(arg1, py);
                 $crate::PyDrop::release_ref(arg2, py);
                 $crate::PyDrop::release_ref(slf, py);
-                ret.into()
+                ret
+            })
+        }
+        Some(wrap::<$class>)
+    }}
+}
+
+/// In-place ternary numeric slot (`**=`), see `py_binary_inplace_func`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! py_ternary_inplace_func {
+    ($trait:ident, $class:ident :: $f:ident) => {{
+        unsafe extern "C" fn wrap<T>(
+            slf: *mut $crate::ffi::PyObject,
+            arg1: *mut $crate::ffi::PyObject,
+            arg2: *mut $crate::ffi::PyObject,
+        ) -> *mut $crate::ffi::PyObject
+            where T: $trait
+        {
+            const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
+            $crate::callback::handle_callback(
+                LOCATION, $crate::callback::PyObjectCallbackConverter, |py|
+            {
+                let arg1 = $crate::PyObject::from_borrowed_ptr(py, arg1);
+                let arg2 = $crate::PyObject::from_borrowed_ptr(py, arg2);
+                let ret = match (arg1.extract(py), arg2.extract(py)) {
+                    (Ok(arg1), Ok(arg2)) => py_inplace_call!(py, slf, T::$f(arg1, arg2)),
+                    (Err(err), _) | (_, Err(err)) =>
+                        $crate::class::number::not_implemented(py, err),
+                };
+                $crate::PyDrop::release_ref(arg1, py);
+                $crate::PyDrop::release_ref(arg2, py);
+                ret
             })
         }
         Some(wrap::<$class>)
     }}
 }
 
+/// Calls in-place method with mutably borrowed instance data, result is `slf` itself.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! py_inplace_call {
+    ($py:ident, $slf:ident, $class:ident :: $f:ident ($($arg:ident),*)) => {{
+        let mut obj = $crate::PyObject::from_borrowed_ptr($py, $slf).unchecked_cast_into::<$class>();
+        let ret = match <$class as $crate::class::cell::PyCellProtocolImpl>::borrow_data(
+            $py, $slf, true)
+        {
+            Ok(_borrow) => {
+                let ret: $crate::PyResult<()> = obj.$f($py, $($arg),*).into();
+                ret.map(|_| $crate::PyObject::from_borrowed_ptr($py, $slf))
+            },
+            Err(e) => Err(e),
+        };
+        $crate::PyDrop::release_ref(obj, $py);
+        ret
+    }}
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! py_len_func {
@@ -89,9 +188,9 @@ macro_rules! py_len_func {
             const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
             $crate::callback::handle_callback(LOCATION, $conv, |py| {
                 let slf = $crate::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-                let ret = slf.$f(py);
+                let ret = py_borrow_call!(py, T, &slf, false, slf.$f(py).into());
                 $crate::PyDrop::release_ref(slf, py);
-                ret.into()
+                ret
             })
         }
         Some(wrap::<$class>)
@@ -110,9 +209,9 @@ macro_rules! py_ssizearg_func {
             const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
             $crate::callback::handle_callback(LOCATION, $conv, |py| {
                 let slf = $crate::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-                let ret = slf.$f(py, arg as isize);
+                let ret = py_borrow_call!(py, T, &slf, false, slf.$f(py, arg as isize).into());
                 $crate::PyDrop::release_ref(slf, py);
-                ret.into()
+                ret
             })
         }
         Some(wrap::<$class>)
@@ -128,14 +227,14 @@ macro_rules! py_objobj_proc {
             slf: *mut $crate::ffi::PyObject,
             arg: *mut $crate::ffi::PyObject,
         ) -> $crate::c_int
-            where T: $trait + PythonObject
+            where T: $trait
         {
             const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
             $crate::callback::handle_callback(LOCATION, $conv, |py| {
                 let slf = $crate::PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-                let arg = PyObject::from_borrowed_ptr(py, arg);
+                let arg = $crate::PyObject::from_borrowed_ptr(py, arg);
                 let ret = match arg.extract(py) {
-                    Ok(arg) => slf.$f(py, arg).into(),
+                    Ok(arg) => py_borrow_call!(py, T, &slf, false, slf.$f(py, arg).into()),
                     Err(e) => Err(e),
                 };
                 $crate::PyDrop::release_ref(arg, py);
@@ -147,45 +246,11 @@ macro_rules! py_objobj_proc {
     }}
 }
 
-#[macro_export]
-#[doc(hidden)]
-macro_rules! py_ternary_slot {
-    ($trait:ident, $class:ident :: $f:ident,
-     $arg1_type:ty, $arg2_type:ty, $res_type:ty, $conv:expr) => {{
-        unsafe extern "C" fn wrap<T>(
-            slf: *mut $crate::ffi::PyObject,
-            arg1: *mut $crate::ffi::PyObject,
-            arg2: *mut $crate::ffi::PyObject,
-        ) -> $res_type
-            where T: $trait + PythonObject
-        {
-            const LOCATION: &'static str = concat!(stringify!($class), ".", stringify!($f), "()");
-            $crate::callback::handle_callback(
-                LOCATION, $conv, |py|
-                {
-                    let slf = $crate::PyObject::from_borrowed_ptr(
-                        py, slf).unchecked_cast_into::<T>();
-                    let arg1 = $crate::PyObject::from_borrowed_ptr(py, arg1);
-
-                    let tmp;
-                    let value = if arg2.is_null() {
-                        None
-                    } else {
-                        tmp = $crate::PyObject::from_borrowed_ptr(py, arg2);
-                        Some(&tmp)
-                    };
-
-                    let ret = slf.$f(py, &arg1, value);
-
-                    $crate::PyDrop::release_ref(arg1, py);
-                    if ! arg2.is_null() {
-                        $crate::PyDrop::release_ref(
-                            $crate::PyObject::from_borrowed_ptr(py, arg2), py);
-                    }
-                    $crate::PyDrop::release_ref(slf, py);
-                    ret
-                })
+/// Adds type slot to `TypeSlots` if function is defined.
+macro_rules! push_slot {
+    ($slots:expr, $slot:ident, $func:expr) => {
+        if let Some(func) = $func {
+            $slots.push($crate::ffi::$slot, func as *mut ::std::os::raw::c_void);
         }
-         Some(wrap::<T>)
-    }}
+    }
 }
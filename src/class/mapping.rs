@@ -2,6 +2,11 @@
 
 //! Python Mapping Interface
 //! Trait and support implementation for implementing mapping support
+//!
+//! Keys of mapping are arbitrary python objects extracted to `Key` type,
+//! they are not resolved against `__len__`. Index and slice keys are supported
+//! by `PySequenceProtocol`, its `__getitem__` and `__setitem__` receive `SliceOrIndex`
+//! key and fill `mp_subscript` and `mp_ass_subscript` slots too.
 
 use std::os::raw::c_int;
 
@@ -139,11 +144,14 @@ impl<T> PyMappingSetItemProtocolImpl for T
                 let ret = match key.extract(py) {
                     Ok(key) =>
                         if value.is_null() {
-                            slf.__setitem__(py, key, None).into()
+                            py_borrow_call!(py, T, &slf, false,
+                                            slf.__setitem__(py, key, None).into())
                         } else {
                             let value = PyObject::from_borrowed_ptr(py, value);
                             let ret = match value.extract(py) {
-                                Ok(value) => slf.__setitem__(py, key, Some(value)).into(),
+                                Ok(value) => py_borrow_call!(
+                                    py, T, &slf, false,
+                                    slf.__setitem__(py, key, Some(value)).into()),
                                 Err(e) => Err(e),
                             };
                             PyDrop::release_ref(value, py);
@@ -3,18 +3,25 @@
 use std::ffi::CString;
 
 use ffi;
+use ::{Python, PyObject};
 use class::NO_PY_METHODS;
 
 pub enum PyMethodDefType {
+    New(PyMethodDef),
+    Call(PyMethodDef),
+    Class(PyMethodDef),
+    Static(PyMethodDef),
     Method(PyMethodDef),
     Getter(PyGetterDef),
     Setter(PySetterDef),
+    ClassAttribute(PyClassAttributeDef),
 }
 
 #[derive(Copy, Clone)]
 pub enum PyMethodType {
     PyCFunction(ffi::PyCFunction),
     PyCFunctionWithKeywords(ffi::PyCFunctionWithKeywords),
+    PyNewFunc(ffi::newfunc),
 }
 
 #[derive(Copy, Clone)]
@@ -39,11 +46,20 @@ pub struct PySetterDef {
     pub doc: &'static str,
 }
 
+/// Class attribute, value is computed once during type initialization
+/// and stored in the type's `__dict__`.
+#[derive(Copy, Clone)]
+pub struct PyClassAttributeDef {
+    pub name: &'static str,
+    pub meth: fn(Python) -> PyObject,
+}
+
 unsafe impl Sync for PyMethodDef {}
 unsafe impl Sync for ffi::PyMethodDef {}
 
 unsafe impl Sync for PyGetterDef {}
 unsafe impl Sync for PySetterDef {}
+unsafe impl Sync for PyClassAttributeDef {}
 unsafe impl Sync for ffi::PyGetSetDef {}
 
 
@@ -56,7 +72,11 @@ impl PyMethodDef {
                 unsafe {
                     ::std::mem::transmute::<
                             ffi::PyCFunctionWithKeywords, ffi::PyCFunction>(meth)
-                }
+                },
+            PyMethodType::PyNewFunc(meth) =>
+                unsafe {
+                    ::std::mem::transmute::<ffi::newfunc, ffi::PyCFunction>(meth)
+                },
         };
 
         ffi::PyMethodDef {
@@ -64,7 +84,7 @@ impl PyMethodDef {
                 "Method name must not contain NULL byte").into_raw(),
             ml_meth: Some(meth),
             ml_flags: self.ml_flags,
-            ml_doc: 0 as *const ::c_char,
+            ml_doc: doc_ptr(self.ml_doc) as *const ::c_char,
         }
     }
 }
@@ -75,6 +95,9 @@ impl PyGetterDef {
             dst.name = CString::new(self.name).expect(
                 "Method name must not contain NULL byte").into_raw();
         }
+        if dst.doc.is_null() {
+            dst.doc = doc_ptr(self.doc);
+        }
         dst.get = Some(self.meth.clone());
     }
 }
@@ -85,16 +108,63 @@ impl PySetterDef {
             dst.name = CString::new(self.name).expect(
                 "Method name must not contain NULL byte").into_raw();
         }
+        if dst.doc.is_null() {
+            dst.doc = doc_ptr(self.doc);
+        }
         dst.set = Some(self.meth.clone());
     }
 }
 
+/// Convert docstring to C string, empty docstring is represented by NULL.
+fn doc_ptr(doc: &'static str) -> *mut ::c_char {
+    if doc.is_empty() {
+        0 as *mut ::c_char
+    } else {
+        CString::new(doc).expect("Docstring must not contain NULL byte").into_raw()
+    }
+}
+
+/// Methods of one `#[methods]` block, `Block` is the index of the block within the class.
+#[doc(hidden)]
+pub trait PyMethodsProtocolImpl<Block> {
+    fn py_methods() -> &'static [PyMethodDefType];
+}
+
+impl<T, Block> PyMethodsProtocolImpl<Block> for T {
+    default fn py_methods() -> &'static [PyMethodDefType] {
+        NO_PY_METHODS
+    }
+}
+
+macro_rules! py_methods_blocks {
+    ($($block:ident),+) => {
+        $(
+            #[doc(hidden)]
+            pub struct $block;
+        )+
+
+        /// Methods of all `#[methods]` blocks of the class.
+        #[doc(hidden)]
+        pub fn py_class_methods<T>() -> Vec<&'static PyMethodDefType> {
+            let mut methods = Vec::new();
+            $(
+                methods.extend(<T as PyMethodsProtocolImpl<$block>>::py_methods().iter());
+            )+
+            methods
+        }
+    }
+}
+
+// number of blocks has to match `MAX_METHODS_BLOCKS` in pyo3cls
+py_methods_blocks!(Block0, Block1, Block2, Block3, Block4, Block5, Block6, Block7,
+                   Block8, Block9, Block10, Block11, Block12, Block13, Block14, Block15);
+
 #[doc(hidden)]
-pub trait PyMethodsProtocolImpl {
+pub trait PyPropMethodsProtocolImpl {
     fn py_methods() -> &'static [PyMethodDefType];
 }
 
-impl<T> PyMethodsProtocolImpl for T {
+impl<T> PyPropMethodsProtocolImpl for T {
     default fn py_methods() -> &'static [PyMethodDefType] {
         NO_PY_METHODS
     }
@@ -6,37 +6,43 @@ use std::os::raw::c_void;
 #[macro_use] mod macros;
 
 pub mod async;
-//pub mod basic;
-//pub mod buffer;
-//pub mod context;
-//pub mod descr;
+pub mod basic;
+pub mod buffer;
+pub mod cell;
+pub mod context;
+pub mod descr;
+pub mod freelist;
+pub mod iter;
 pub mod mapping;
-//pub mod methods;
-//pub mod number;
-//pub mod gc;
+pub mod methods;
+pub mod number;
+pub mod gc;
+pub mod pickle;
 pub mod sequence;
-//pub mod typeob;
-
-//pub use self::basic::PyObjectProtocol;
-//pub use self::async::PyAsyncProtocol;
-//pub use self::buffer::PyBufferProtocol;
-//pub use self::context::PyContextProtocol;
-//pub use self::descr::PyDescrProtocol;
-//pub use self::number::PyNumberProtocol;
+pub mod typeob;
+
+pub use self::basic::PyObjectProtocol;
+pub use self::async::PyAsyncProtocol;
+pub use self::buffer::{PyBufferProtocol, PyBufferView};
+pub use self::context::PyContextProtocol;
+pub use self::descr::PyDescrProtocol;
+pub use self::iter::PyIterProtocol;
+pub use self::number::PyNumberProtocol;
+pub use self::pickle::PyPickleProtocol;
 pub use self::mapping::PyMappingProtocol;
 pub use self::sequence::PySequenceProtocol;
 
-//pub use self::typeob::PyTypeObject;
-//pub use self::gc::{PyVisit, PyGCProtocol, PyTraverseError};
-//pub use self::methods::{PyMethodDef, PyMethodDefType, PyMethodType,
-//                        PyGetterDef, PySetterDef};
+pub use self::typeob::PyTypeObject;
+pub use self::gc::{PyVisit, PyGCProtocol, PyTraverse, PyTraverseError};
+pub use self::methods::{PyMethodDef, PyMethodDefType, PyMethodType,
+                        PyGetterDef, PySetterDef, PyClassAttributeDef};
 
-//pub static NO_METHODS: &'static [&'static str] = &[];
-//pub static NO_PY_METHODS: &'static [PyMethodDefType] = &[];
+pub static NO_METHODS: &'static [&'static str] = &[];
+pub static NO_PY_METHODS: &'static [PyMethodDefType] = &[];
 
 use ffi;
 use err::{self, PyResult};
-use objects::{PyObject, PyType};
+use objects::{exc, PyObject, PyType, PyDict, PyList};
 use python::{Python, PythonObject};
 
 
@@ -68,8 +74,25 @@ pub trait BaseObject : PythonObject {
     /// (usually by calling ptr->ob_type->tp_free).
     /// This function is used as tp_dealloc implementation.
     unsafe fn dealloc(py: Python, obj: *mut ffi::PyObject);
+
+    /// Offset of instance `__dict__` pointer, set by `#[class(dict)]`.
+    /// Offsets of base types are inherited by python.
+    fn dict_offset() -> Option<isize> {
+        None
+    }
+
+    /// Offset of weak reference list pointer, set by `#[class(weakref)]`.
+    fn weakref_offset() -> Option<isize> {
+        None
+    }
 }
 
+/// Base type which allows subclasses, i.e. native base type or `#[class(subclass)]`.
+/// Base type of `#[class(base = ...)]` has to implement it.
+pub trait PyBaseType : BaseObject {}
+
+impl PyBaseType for PyObject {}
+
 
 impl BaseObject for PyObject {
     #[inline]
@@ -100,3 +123,91 @@ impl BaseObject for PyObject {
         }
     }
 }
+
+
+/// Implements `BaseObject` for a native python type, so #[class] can use it as base type.
+/// Object is allocated with native `tp_new` and destroyed with native `tp_dealloc`.
+macro_rules! native_base_object {
+    ($name:ty, $typeobject:expr) => {
+        impl PyBaseType for $name {}
+
+        impl BaseObject for $name {
+            #[inline]
+            fn size() -> usize {
+                unsafe { (*$typeobject).tp_basicsize as usize }
+            }
+
+            type Type = ();
+
+            unsafe fn alloc(py: Python, ty: &PyType, _init_val: ()) -> PyResult<PyObject> {
+                let base = $typeobject;
+                let args = ffi::PyTuple_New(0);
+                let ptr = match (*base).tp_new {
+                    Some(tp_new) => tp_new(ty.as_type_ptr(), args, 0 as *mut ffi::PyObject),
+                    None => ffi::PyType_GenericAlloc(ty.as_type_ptr(), 0),
+                };
+                ffi::Py_DECREF(args);
+                err::result_from_owned_ptr(py, ptr)
+            }
+
+            unsafe fn dealloc(py: Python, obj: *mut ffi::PyObject) {
+                match (*$typeobject).tp_dealloc {
+                    Some(tp_dealloc) => {
+                        // native `tp_dealloc` frees the object with `tp_free`,
+                        // reference to the heap type is not released by it
+                        let ty = ffi::Py_TYPE(obj);
+                        tp_dealloc(obj);
+                        if ffi::PyType_HasFeature(ty, ffi::Py_TPFLAGS_HEAPTYPE) != 0 {
+                            ffi::Py_DECREF(ty as *mut ffi::PyObject);
+                        }
+                    },
+                    None => <PyObject as BaseObject>::dealloc(py, obj),
+                }
+            }
+        }
+    }
+}
+
+native_base_object!(PyDict, &mut ffi::PyDict_Type as *mut ffi::PyTypeObject);
+native_base_object!(PyList, &mut ffi::PyList_Type as *mut ffi::PyTypeObject);
+
+macro_rules! native_exc_base_object {
+    ($($name:ident: $exc_name:ident),*) => {
+        $(
+            native_base_object!(
+                exc::$name, ffi::$exc_name as *mut ffi::PyTypeObject);
+        )*
+    }
+}
+
+native_exc_base_object!(
+    BaseException: PyExc_BaseException,
+    Exception: PyExc_Exception,
+    LookupError: PyExc_LookupError,
+    AssertionError: PyExc_AssertionError,
+    AttributeError: PyExc_AttributeError,
+    EOFError: PyExc_EOFError,
+    EnvironmentError: PyExc_EnvironmentError,
+    FloatingPointError: PyExc_FloatingPointError,
+    IOError: PyExc_IOError,
+    ImportError: PyExc_ImportError,
+    IndexError: PyExc_IndexError,
+    KeyError: PyExc_KeyError,
+    MemoryError: PyExc_MemoryError,
+    NameError: PyExc_NameError,
+    NotImplementedError: PyExc_NotImplementedError,
+    OSError: PyExc_OSError,
+    OverflowError: PyExc_OverflowError,
+    ReferenceError: PyExc_ReferenceError,
+    RuntimeError: PyExc_RuntimeError,
+    SystemError: PyExc_SystemError,
+    TypeError: PyExc_TypeError,
+    ValueError: PyExc_ValueError,
+    ZeroDivisionError: PyExc_ZeroDivisionError,
+    BufferError: PyExc_BufferError,
+    ConnectionError: PyExc_ConnectionError,
+    FileExistsError: PyExc_FileExistsError,
+    FileNotFoundError: PyExc_FileNotFoundError,
+    PermissionError: PyExc_PermissionError,
+    TimeoutError: PyExc_TimeoutError
+);
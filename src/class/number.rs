@@ -2,415 +2,802 @@
 
 //! Python Number Interface
 //! Trait and support implementation for implementing number protocol
-
-use std::os::raw::c_int;
+//!
+//! Python calls binary slot of a type for both `a + x` and `x + a`, slot dispatches
+//! to `__add__` if left operand is instance of the type and to `__radd__` otherwise.
+//! Arguments are extracted with `FromPyObject`, python gets `NotImplemented`
+//! if the other operand does not match the argument type (extraction raises `TypeError`),
+//! so it can try the other operand. Other extraction errors are propagated.
+//!
+//! In-place operations (`__iadd__` etc.) mutate `self` and return `PyResult<()>`,
+//! result of the python operation is the object itself.
+//!
+//! Conversions return rust values, `__int__` and `__index__` return an integer type,
+//! `__float__` returns `f32` or `f64` and `__complex__` returns `(real, imag)` pair.
 
 use ffi;
-use err::PyResult;
-use python::{Python, PythonObject};
+use err::{PyErr, PyResult, result_from_owned_ptr};
+use python::{Python, PythonObject, PythonObjectWithTypeObject, PyDrop};
 use objects::PyObject;
-use callback::{BoolConverter, PyObjectCallbackConverter};
-use class::{NO_METHODS, NO_PY_METHODS};
-use class::basic::{PyObjectProtocol, PyObjectProtocolImpl};
+use conversion::{ToPyObject, FromPyObject};
+use callback::{handle_callback, PyObjectCallbackConverter};
+use class::NO_PY_METHODS;
+use class::typeob::TypeSlots;
 
 /// Number interface
-pub trait PyNumberProtocol {
-
-    fn __add__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __sub__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __mul__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __matmul__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __truediv__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __floordiv__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __mod__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __divmod__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __pow__(&self, py: Python, other: &PyObject, modulo: &PyObject) -> PyResult<PyObject>;
-    fn __lshift__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rshift__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __and__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __xor__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __or__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-
-    fn __radd__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rsub__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rmul__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rmatmul__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rtruediv__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rfloordiv__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rmod__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rdivmod__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rpow__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rlshift__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rrshift__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rand__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __rxor__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __ror__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-
-    fn __iadd__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __isub__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __imul__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __imatmul__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __itruediv__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __ifloordiv__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __imod__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __ipow__(&self, py: Python, other: &PyObject, modulo: &PyObject) -> PyResult<PyObject>;
-    fn __ilshift__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __irshift__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __iand__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __ixor__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
-    fn __ior__(&self, py: Python, other: &PyObject) -> PyResult<PyObject>;
+#[allow(unused_variables)]
+pub trait PyNumberProtocol: PythonObject {
+    fn __add__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberAddProtocol { unimplemented!() }
+    fn __sub__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberSubProtocol { unimplemented!() }
+    fn __mul__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberMulProtocol { unimplemented!() }
+    fn __matmul__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberMatmulProtocol { unimplemented!() }
+    fn __truediv__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberTruedivProtocol { unimplemented!() }
+    fn __floordiv__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberFloordivProtocol { unimplemented!() }
+    fn __mod__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberModProtocol { unimplemented!() }
+    fn __divmod__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberDivmodProtocol { unimplemented!() }
+    fn __pow__(&self, py: Python, other: Self::Other, modulo: Self::Modulo) -> Self::Result
+        where Self: PyNumberPowProtocol { unimplemented!() }
+    fn __lshift__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberLShiftProtocol { unimplemented!() }
+    fn __rshift__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRShiftProtocol { unimplemented!() }
+    fn __and__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberAndProtocol { unimplemented!() }
+    fn __xor__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberXorProtocol { unimplemented!() }
+    fn __or__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberOrProtocol { unimplemented!() }
+
+    fn __radd__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRAddProtocol { unimplemented!() }
+    fn __rsub__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRSubProtocol { unimplemented!() }
+    fn __rmul__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRMulProtocol { unimplemented!() }
+    fn __rmatmul__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRMatmulProtocol { unimplemented!() }
+    fn __rtruediv__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRTruedivProtocol { unimplemented!() }
+    fn __rfloordiv__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRFloordivProtocol { unimplemented!() }
+    fn __rmod__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRModProtocol { unimplemented!() }
+    fn __rdivmod__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRDivmodProtocol { unimplemented!() }
+    fn __rpow__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRPowProtocol { unimplemented!() }
+    fn __rlshift__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRLShiftProtocol { unimplemented!() }
+    fn __rrshift__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRRShiftProtocol { unimplemented!() }
+    fn __rand__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRAndProtocol { unimplemented!() }
+    fn __rxor__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberRXorProtocol { unimplemented!() }
+    fn __ror__(&self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberROrProtocol { unimplemented!() }
+
+    fn __iadd__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIAddProtocol { unimplemented!() }
+    fn __isub__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberISubProtocol { unimplemented!() }
+    fn __imul__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIMulProtocol { unimplemented!() }
+    fn __imatmul__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIMatmulProtocol { unimplemented!() }
+    fn __itruediv__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberITruedivProtocol { unimplemented!() }
+    fn __ifloordiv__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIFloordivProtocol { unimplemented!() }
+    fn __imod__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIModProtocol { unimplemented!() }
+    fn __ipow__(&mut self, py: Python, other: Self::Other, modulo: Self::Modulo) -> Self::Result
+        where Self: PyNumberIPowProtocol { unimplemented!() }
+    fn __ilshift__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberILShiftProtocol { unimplemented!() }
+    fn __irshift__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIRShiftProtocol { unimplemented!() }
+    fn __iand__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIAndProtocol { unimplemented!() }
+    fn __ixor__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIXorProtocol { unimplemented!() }
+    fn __ior__(&mut self, py: Python, other: Self::Other) -> Self::Result
+        where Self: PyNumberIOrProtocol { unimplemented!() }
 
     // Unary arithmetic
-    fn __neg__(&self, py: Python) -> PyResult<PyObject>;
-    fn __pos__(&self, py: Python) -> PyResult<PyObject>;
-    fn __abs__(&self, py: Python) -> PyResult<PyObject>;
-    fn __invert__(&self, py: Python) -> PyResult<PyObject>;
-    fn __complex__(&self, py: Python) -> PyResult<PyObject>;
-    fn __int__(&self, py: Python) -> PyResult<PyObject>;
-    fn __float__(&self, py: Python) -> PyResult<PyObject>;
-    fn __round__(&self, py: Python) -> PyResult<PyObject>;
-    fn __index__(&self, py: Python) -> PyResult<PyObject>;
-}
-
-impl<T> PyNumberProtocol for T where T: PythonObject {
-    default fn __add__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __sub__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __mul__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __matmul__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __truediv__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __floordiv__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __mod__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __divmod__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __pow__(&self, py: Python, _: &PyObject, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __lshift__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rshift__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __and__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __xor__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __or__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
+    fn __neg__(&self, py: Python) -> Self::Result
+        where Self: PyNumberNegProtocol { unimplemented!() }
+    fn __pos__(&self, py: Python) -> Self::Result
+        where Self: PyNumberPosProtocol { unimplemented!() }
+    fn __abs__(&self, py: Python) -> Self::Result
+        where Self: PyNumberAbsProtocol { unimplemented!() }
+    fn __invert__(&self, py: Python) -> Self::Result
+        where Self: PyNumberInvertProtocol { unimplemented!() }
+    fn __complex__(&self, py: Python) -> Self::Result
+        where Self: PyNumberComplexProtocol { unimplemented!() }
+    fn __int__(&self, py: Python) -> Self::Result
+        where Self: PyNumberIntProtocol { unimplemented!() }
+    fn __float__(&self, py: Python) -> Self::Result
+        where Self: PyNumberFloatProtocol { unimplemented!() }
+    fn __round__(&self, py: Python, ndigits: Option<isize>) -> Self::Result
+        where Self: PyNumberRoundProtocol { unimplemented!() }
+    fn __index__(&self, py: Python) -> Self::Result
+        where Self: PyNumberIndexProtocol { unimplemented!() }
+}
 
-    default fn __radd__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rsub__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rmul__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rmatmul__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rtruediv__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rfloordiv__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rmod__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rdivmod__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rpow__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rlshift__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rrshift__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rand__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __rxor__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __ror__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
+// The following are a bunch of marker traits used to detect
+// the existance of a slotted method.
 
-    default fn __iadd__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __isub__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __imul__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __imatmul__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __itruediv__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __ifloordiv__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __imod__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __ipow__(&self, py: Python, _: &PyObject, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __ilshift__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __irshift__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __iand__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __ixor__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __ior__(&self, py: Python, _: &PyObject) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __neg__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __pos__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __abs__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __invert__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __complex__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __int__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __float__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __round__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
-    default fn __index__(&self, py: Python) -> PyResult<PyObject> {
-        Ok(py.NotImplemented())
-    }
+pub trait PyNumberAddProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberSubProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberMulProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberMatmulProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberTruedivProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberFloordivProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberModProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberDivmodProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberPowProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Modulo: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberLShiftProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRShiftProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberAndProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberXorProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberOrProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRAddProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRSubProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRMulProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRMatmulProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRTruedivProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRFloordivProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRModProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRDivmodProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRPowProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRLShiftProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRRShiftProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRAndProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRXorProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberROrProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberIAddProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberISubProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIMulProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIMatmulProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberITruedivProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIFloordivProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIModProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIPowProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Modulo: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberILShiftProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIRShiftProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIAndProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIXorProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberIOrProtocol: PyNumberProtocol {
+    type Other: for<'a> FromPyObject<'a>;
+    type Result: Into<PyResult<()>>;
+}
+
+pub trait PyNumberNegProtocol: PyNumberProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberPosProtocol: PyNumberProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberAbsProtocol: PyNumberProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberInvertProtocol: PyNumberProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberComplexProtocol: PyNumberProtocol {
+    type Result: Into<PyResult<(f64, f64)>>;
+}
+
+pub trait PyNumberIntProtocol: PyNumberProtocol {
+    type Success: PyIntValue;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberFloatProtocol: PyNumberProtocol {
+    type Success: PyFloatValue;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberRoundProtocol: PyNumberProtocol {
+    type Success: ToPyObject;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+pub trait PyNumberIndexProtocol: PyNumberProtocol {
+    type Success: PyIntValue;
+    type Result: Into<PyResult<Self::Success>>;
+}
+
+/// Rust integer types, python requires `int` result of `__int__` and `__index__`
+pub trait PyIntValue: ToPyObject {}
+
+/// Rust floating point types, python requires `float` result of `__float__`
+pub trait PyFloatValue: ToPyObject {}
+
+macro_rules! impl_value {
+    ($trait:ident for $($t:ty),*) => { $(impl $trait for $t {})* }
 }
 
+impl_value!(PyIntValue for i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+impl_value!(PyFloatValue for f32, f64);
+
 #[doc(hidden)]
 pub trait PyNumberProtocolImpl {
-    fn methods() -> &'static [&'static str];
+    fn update_type_slots(slots: &mut TypeSlots);
     fn py_methods() -> &'static [::class::PyMethodDefType];
 }
 
 impl<T> PyNumberProtocolImpl for T {
-    default fn methods() -> &'static [&'static str] {
-        NO_METHODS
+    #[inline]
+    default fn update_type_slots(_slots: &mut TypeSlots) {}
+    #[inline]
+    default fn py_methods() -> &'static [::class::PyMethodDefType] {
+        NO_PY_METHODS
     }
+}
+
+impl<T> PyNumberProtocolImpl for T where T: PyNumberProtocol {
+    fn update_type_slots(slots: &mut TypeSlots) {
+        push_slot!(slots, Py_nb_add, nb_add::<T>());
+        push_slot!(slots, Py_nb_subtract, nb_subtract::<T>());
+        push_slot!(slots, Py_nb_multiply, nb_multiply::<T>());
+        push_slot!(slots, Py_nb_matrix_multiply, nb_matrix_multiply::<T>());
+        push_slot!(slots, Py_nb_true_divide, nb_true_divide::<T>());
+        push_slot!(slots, Py_nb_floor_divide, nb_floor_divide::<T>());
+        push_slot!(slots, Py_nb_remainder, nb_remainder::<T>());
+        push_slot!(slots, Py_nb_divmod, nb_divmod::<T>());
+        push_slot!(slots, Py_nb_power, nb_power::<T>());
+        push_slot!(slots, Py_nb_lshift, nb_lshift::<T>());
+        push_slot!(slots, Py_nb_rshift, nb_rshift::<T>());
+        push_slot!(slots, Py_nb_and, nb_and::<T>());
+        push_slot!(slots, Py_nb_xor, nb_xor::<T>());
+        push_slot!(slots, Py_nb_or, nb_or::<T>());
+        push_slot!(slots, Py_nb_inplace_add, Self::nb_inplace_add());
+        push_slot!(slots, Py_nb_inplace_subtract, Self::nb_inplace_subtract());
+        push_slot!(slots, Py_nb_inplace_multiply, Self::nb_inplace_multiply());
+        push_slot!(slots, Py_nb_inplace_matrix_multiply, Self::nb_inplace_matrix_multiply());
+        push_slot!(slots, Py_nb_inplace_true_divide, Self::nb_inplace_true_divide());
+        push_slot!(slots, Py_nb_inplace_floor_divide, Self::nb_inplace_floor_divide());
+        push_slot!(slots, Py_nb_inplace_remainder, Self::nb_inplace_remainder());
+        push_slot!(slots, Py_nb_inplace_power, Self::nb_inplace_power());
+        push_slot!(slots, Py_nb_inplace_lshift, Self::nb_inplace_lshift());
+        push_slot!(slots, Py_nb_inplace_rshift, Self::nb_inplace_rshift());
+        push_slot!(slots, Py_nb_inplace_and, Self::nb_inplace_and());
+        push_slot!(slots, Py_nb_inplace_xor, Self::nb_inplace_xor());
+        push_slot!(slots, Py_nb_inplace_or, Self::nb_inplace_or());
+        push_slot!(slots, Py_nb_negative, Self::nb_negative());
+        push_slot!(slots, Py_nb_positive, Self::nb_positive());
+        push_slot!(slots, Py_nb_absolute, Self::nb_absolute());
+        push_slot!(slots, Py_nb_invert, Self::nb_invert());
+        push_slot!(slots, Py_nb_int, Self::nb_int());
+        push_slot!(slots, Py_nb_float, Self::nb_float());
+        push_slot!(slots, Py_nb_index, Self::nb_index());
+    }
+
+    /// `__complex__` and `__round__` have no slots, they are added as methods by `#[proto]`.
+    #[inline]
     default fn py_methods() -> &'static [::class::PyMethodDefType] {
         NO_PY_METHODS
     }
 }
 
-impl ffi::PyNumberMethods {
+fn into_object<S>(py: Python, ret: PyResult<S>) -> PyResult<PyObject> where S: ToPyObject {
+    ret.map(|val| val.into_py_object(py))
+}
 
-    /// Construct PyNumberMethods struct for PyTypeObject.tp_as_number
-    pub fn new<T>() -> Option<ffi::PyNumberMethods>
-        where T: PyNumberProtocol + PyNumberProtocolImpl
-                 + PyObjectProtocol + PyObjectProtocolImpl
-                 + PythonObject
+/// `NotImplemented` if operand does not match the argument type, otherwise the error.
+#[doc(hidden)]
+pub fn not_implemented(py: Python, err: PyErr) -> PyResult<PyObject> {
+    if ::derive::is_mismatch(py, &err) {
+        Ok(py.NotImplemented())
+    } else {
+        Err(err)
+    }
+}
 
-    {
-        let objm = <T as PyObjectProtocolImpl>::methods();
-        let methods = <T as PyNumberProtocolImpl>::methods();
-        if methods.is_empty() && ! objm.contains(&"__bool__") {
-            return None
+/// Binary slot, dispatches to operation of the left operand if it is instance of the type,
+/// otherwise to reflected operation of the right operand.
+macro_rules! binary_slot {
+    ($slot:ident, $op_impl:ident, $op_proto:ident, $op:ident,
+     $rop_impl:ident, $rop_proto:ident, $rop:ident) => {
+        trait $op_impl {
+            fn implemented() -> bool;
+            fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject) -> Option<PyResult<PyObject>>;
+        }
+
+        impl<T> $op_impl for T where T: PyNumberProtocol {
+            #[inline]
+            default fn implemented() -> bool { false }
+            #[inline]
+            default fn dispatch(_py: Python, _lhs: &PyObject, _rhs: &PyObject)
+                                -> Option<PyResult<PyObject>> { None }
+        }
+
+        impl<T> $op_impl for T where T: $op_proto + PythonObjectWithTypeObject {
+            #[inline]
+            fn implemented() -> bool { true }
+
+            fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject)
+                        -> Option<PyResult<PyObject>> {
+                if !T::type_object(py).is_instance(py, lhs) {
+                    return None
+                }
+                let slf = unsafe { lhs.unchecked_cast_as::<T>() };
+                Some(match rhs.extract(py) {
+                    Ok(other) => py_borrow_call!(py, T, slf, false,
+                        into_object::<<T as $op_proto>::Success>(py, slf.$op(py, other).into())),
+                    Err(err) => not_implemented(py, err),
+                })
+            }
+        }
+
+        trait $rop_impl {
+            fn implemented() -> bool;
+            fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject) -> Option<PyResult<PyObject>>;
         }
 
-        let mut meth: ffi::PyNumberMethods = ffi::PyNumberMethods_INIT;
-
-        for name in methods {
-            match name {
-                &"__add__" => {
-                    meth.nb_add = py_binary_func!(
-                        PyNumberProtocol, T::__add__, PyObjectCallbackConverter);
-                },
-                &"__sub__" => {
-                    meth.nb_subtract = py_binary_func!(
-                        PyNumberProtocol, T::__sub__, PyObjectCallbackConverter);
-                },
-                &"__mul__" => {
-                    meth.nb_multiply = py_binary_func!(
-                        PyNumberProtocol, T::__mul__, PyObjectCallbackConverter);
-                },
-                &"__matmul__" => {
-                    meth.nb_matrix_multiply = py_binary_func!(
-                        PyNumberProtocol, T::__matmul__, PyObjectCallbackConverter);
-                },
-                &"__truediv__" => {
-                    meth.nb_true_divide = py_binary_func!(
-                        PyNumberProtocol, T::__truediv__, PyObjectCallbackConverter);
-                },
-                &"__floordiv__" => {
-                    meth.nb_floor_divide = py_binary_func!(
-                        PyNumberProtocol, T::__floordiv__, PyObjectCallbackConverter);
-                },
-                &"__mod__" => {
-                    meth.nb_remainder = py_binary_func!(
-                        PyNumberProtocol, T::__mod__, PyObjectCallbackConverter);
-                },
-                &"__divmod__" => {
-                    meth.nb_divmod = py_binary_func!(
-                        PyNumberProtocol, T::__divmod__, PyObjectCallbackConverter);
-                },
-                &"__pow__" => {
-                    meth.nb_power = py_ternary_func!(
-                        PyNumberProtocol, T::__pow__, PyObjectCallbackConverter);
-                },
-                &"__lshift__" => {
-                    meth.nb_lshift = py_binary_func!(
-                        PyNumberProtocol, T::__lshift__, PyObjectCallbackConverter);
-                },
-                &"__rshift__" => {
-                    meth.nb_rshift = py_binary_func!(
-                        PyNumberProtocol, T::__rshift__, PyObjectCallbackConverter);
-                },
-                &"__and__" => {
-                    meth.nb_and = py_binary_func!(
-                        PyNumberProtocol, T::__and__, PyObjectCallbackConverter);
-                },
-                &"__xor__" => {
-                    meth.nb_xor = py_binary_func!(
-                        PyNumberProtocol, T::__xor__, PyObjectCallbackConverter);
-                },
-                &"__or__" => {
-                    meth.nb_or = py_binary_func!(
-                        PyNumberProtocol, T::__or__, PyObjectCallbackConverter);
-                },
-                &"__iadd__" => {
-                    meth.nb_inplace_add = py_binary_func!(
-                        PyNumberProtocol, T::__iadd__, PyObjectCallbackConverter);
-                },
-                &"__isub__" => {
-                    meth.nb_inplace_subtract = py_binary_func!(
-                        PyNumberProtocol, T::__isub__, PyObjectCallbackConverter);
-                },
-                &"__imul__" => {
-                    meth.nb_inplace_multiply = py_binary_func!(
-                        PyNumberProtocol, T::__imul__, PyObjectCallbackConverter);
-                },
-                &"__imatmul__" => {
-                    meth.nb_inplace_matrix_multiply = py_binary_func!(
-                        PyNumberProtocol, T::__imatmul__, PyObjectCallbackConverter);
-                },
-                &"__itruediv__" => {
-                    meth.nb_inplace_true_divide = py_binary_func!(
-                        PyNumberProtocol, T::__itruediv__, PyObjectCallbackConverter);
-                },
-                &"__ifloordiv__" => {
-                    meth.nb_inplace_floor_divide = py_binary_func!(
-                        PyNumberProtocol, T::__ifloordiv__, PyObjectCallbackConverter);
-                },
-                &"__imod__" => {
-                    meth.nb_inplace_remainder = py_binary_func!(
-                        PyNumberProtocol, T::__imod__, PyObjectCallbackConverter);
-                },
-                &"__ipow__" => {
-                    meth.nb_inplace_power = py_ternary_func!(
-                        PyNumberProtocol, T::__ipow__, PyObjectCallbackConverter);
-                },
-                &"__ilshift__" => {
-                    meth.nb_inplace_lshift = py_binary_func!(
-                        PyNumberProtocol, T::__ilshift__, PyObjectCallbackConverter);
-                },
-                &"__irshift__" => {
-                    meth.nb_inplace_rshift = py_binary_func!(
-                        PyNumberProtocol, T::__irshift__, PyObjectCallbackConverter);
-                },
-                &"__iand__" => {
-                    meth.nb_inplace_and = py_binary_func!(
-                        PyNumberProtocol, T::__iand__, PyObjectCallbackConverter);
-                },
-                &"__ixor__" => {
-                    meth.nb_inplace_xor = py_binary_func!(
-                        PyNumberProtocol, T::__ixor__, PyObjectCallbackConverter);
-                },
-                &"__ior__" => {
-                    meth.nb_inplace_or = py_binary_func!(
-                        PyNumberProtocol, T::__ior__, PyObjectCallbackConverter);
-                },
-                &"__neg__" => {
-                    meth.nb_negative = py_unary_func!(
-                        PyNumberProtocol, T::__neg__, PyObjectCallbackConverter);
-                },
-                &"__pos__" => {
-                    meth.nb_positive = py_unary_func!(
-                        PyNumberProtocol, T::__pos__, PyObjectCallbackConverter);
-
-                },
-                &"__abs__" => {
-                    meth.nb_absolute = py_unary_func!(
-                        PyNumberProtocol, T::__abs__, PyObjectCallbackConverter);
-
-                },
-                &"__invert__" => {
-                    meth.nb_invert = py_unary_func!(
-                        PyNumberProtocol, T::__invert__, PyObjectCallbackConverter);
-
-                },
-                &"__int__" => {
-                    meth.nb_int = py_unary_func!(
-                        PyNumberProtocol, T::__int__, PyObjectCallbackConverter);
-
-                },
-                &"__float__" => {
-                    meth.nb_float = py_unary_func!(
-                        PyNumberProtocol, T::__float__, PyObjectCallbackConverter);
-
-                },
-                &"__index__" => {
-                    meth.nb_index = py_unary_func!(
-                        PyNumberProtocol, T::__index__, PyObjectCallbackConverter);
-                },
-                _ => (),
+        impl<T> $rop_impl for T where T: PyNumberProtocol {
+            #[inline]
+            default fn implemented() -> bool { false }
+            #[inline]
+            default fn dispatch(_py: Python, _lhs: &PyObject, _rhs: &PyObject)
+                                -> Option<PyResult<PyObject>> { None }
+        }
+
+        impl<T> $rop_impl for T where T: $rop_proto + PythonObjectWithTypeObject {
+            #[inline]
+            fn implemented() -> bool { true }
+
+            fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject)
+                        -> Option<PyResult<PyObject>> {
+                // like in python, reflected operation is not used for operands of the same type
+                let ty = T::type_object(py);
+                if ty.is_instance(py, lhs) || !ty.is_instance(py, rhs) {
+                    return None
+                }
+                let slf = unsafe { rhs.unchecked_cast_as::<T>() };
+                Some(match lhs.extract(py) {
+                    Ok(other) => py_borrow_call!(py, T, slf, false,
+                        into_object::<<T as $rop_proto>::Success>(py, slf.$rop(py, other).into())),
+                    Err(err) => not_implemented(py, err),
+                })
             }
         }
 
-        if objm.contains(&"__bool__") {
-            meth.nb_bool = py_unary_func!(
-                PyObjectProtocol, T::__bool__, BoolConverter, c_int);
+        fn $slot<T>() -> Option<ffi::binaryfunc> where T: PyNumberProtocol {
+            if !<T as $op_impl>::implemented() && !<T as $rop_impl>::implemented() {
+                return None
+            }
+
+            unsafe extern "C" fn wrap<T>(lhs: *mut ffi::PyObject,
+                                         rhs: *mut ffi::PyObject) -> *mut ffi::PyObject
+                where T: PyNumberProtocol
+            {
+                const LOCATION: &'static str = concat!("T.", stringify!($op), "()");
+                handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+                    let lhs = PyObject::from_borrowed_ptr(py, lhs);
+                    let rhs = PyObject::from_borrowed_ptr(py, rhs);
+                    let ret = <T as $op_impl>::dispatch(py, &lhs, &rhs)
+                        .or_else(|| <T as $rop_impl>::dispatch(py, &lhs, &rhs))
+                        .unwrap_or_else(|| Ok(py.NotImplemented()));
+                    PyDrop::release_ref(lhs, py);
+                    PyDrop::release_ref(rhs, py);
+                    ret
+                })
+            }
+            Some(wrap::<T>)
+        }
+    }
+}
+
+binary_slot!(nb_add, PyNumberAddProtocolImpl, PyNumberAddProtocol, __add__,
+             PyNumberRAddProtocolImpl, PyNumberRAddProtocol, __radd__);
+binary_slot!(nb_subtract, PyNumberSubProtocolImpl, PyNumberSubProtocol, __sub__,
+             PyNumberRSubProtocolImpl, PyNumberRSubProtocol, __rsub__);
+binary_slot!(nb_multiply, PyNumberMulProtocolImpl, PyNumberMulProtocol, __mul__,
+             PyNumberRMulProtocolImpl, PyNumberRMulProtocol, __rmul__);
+binary_slot!(nb_matrix_multiply, PyNumberMatmulProtocolImpl, PyNumberMatmulProtocol, __matmul__,
+             PyNumberRMatmulProtocolImpl, PyNumberRMatmulProtocol, __rmatmul__);
+binary_slot!(nb_true_divide, PyNumberTruedivProtocolImpl, PyNumberTruedivProtocol, __truediv__,
+             PyNumberRTruedivProtocolImpl, PyNumberRTruedivProtocol, __rtruediv__);
+binary_slot!(nb_floor_divide, PyNumberFloordivProtocolImpl, PyNumberFloordivProtocol, __floordiv__,
+             PyNumberRFloordivProtocolImpl, PyNumberRFloordivProtocol, __rfloordiv__);
+binary_slot!(nb_remainder, PyNumberModProtocolImpl, PyNumberModProtocol, __mod__,
+             PyNumberRModProtocolImpl, PyNumberRModProtocol, __rmod__);
+binary_slot!(nb_divmod, PyNumberDivmodProtocolImpl, PyNumberDivmodProtocol, __divmod__,
+             PyNumberRDivmodProtocolImpl, PyNumberRDivmodProtocol, __rdivmod__);
+binary_slot!(nb_lshift, PyNumberLShiftProtocolImpl, PyNumberLShiftProtocol, __lshift__,
+             PyNumberRLShiftProtocolImpl, PyNumberRLShiftProtocol, __rlshift__);
+binary_slot!(nb_rshift, PyNumberRShiftProtocolImpl, PyNumberRShiftProtocol, __rshift__,
+             PyNumberRRShiftProtocolImpl, PyNumberRRShiftProtocol, __rrshift__);
+binary_slot!(nb_and, PyNumberAndProtocolImpl, PyNumberAndProtocol, __and__,
+             PyNumberRAndProtocolImpl, PyNumberRAndProtocol, __rand__);
+binary_slot!(nb_xor, PyNumberXorProtocolImpl, PyNumberXorProtocol, __xor__,
+             PyNumberRXorProtocolImpl, PyNumberRXorProtocol, __rxor__);
+binary_slot!(nb_or, PyNumberOrProtocolImpl, PyNumberOrProtocol, __or__,
+             PyNumberROrProtocolImpl, PyNumberROrProtocol, __ror__);
+
+// `pow()` has optional modulo, reflected `__rpow__` is used only without modulo
+
+trait PyNumberPowProtocolImpl {
+    fn implemented() -> bool;
+    fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject, modulo: &PyObject)
+                -> Option<PyResult<PyObject>>;
+}
+
+impl<T> PyNumberPowProtocolImpl for T where T: PyNumberProtocol {
+    #[inline]
+    default fn implemented() -> bool { false }
+    #[inline]
+    default fn dispatch(_py: Python, _lhs: &PyObject, _rhs: &PyObject, _modulo: &PyObject)
+                        -> Option<PyResult<PyObject>> { None }
+}
+
+impl<T> PyNumberPowProtocolImpl for T where T: PyNumberPowProtocol + PythonObjectWithTypeObject {
+    #[inline]
+    fn implemented() -> bool { true }
+
+    fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject, modulo: &PyObject)
+                -> Option<PyResult<PyObject>> {
+        if !T::type_object(py).is_instance(py, lhs) {
+            return None
+        }
+        let slf = unsafe { lhs.unchecked_cast_as::<T>() };
+        Some(match (rhs.extract(py), modulo.extract(py)) {
+            (Ok(other), Ok(modulo)) => py_borrow_call!(py, T, slf, false,
+                into_object::<<T as PyNumberPowProtocol>::Success>(
+                    py, slf.__pow__(py, other, modulo).into())),
+            (Err(err), _) | (_, Err(err)) => not_implemented(py, err),
+        })
+    }
+}
+
+trait PyNumberRPowProtocolImpl {
+    fn implemented() -> bool;
+    fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject, modulo: &PyObject)
+                -> Option<PyResult<PyObject>>;
+}
+
+impl<T> PyNumberRPowProtocolImpl for T where T: PyNumberProtocol {
+    #[inline]
+    default fn implemented() -> bool { false }
+    #[inline]
+    default fn dispatch(_py: Python, _lhs: &PyObject, _rhs: &PyObject, _modulo: &PyObject)
+                        -> Option<PyResult<PyObject>> { None }
+}
+
+impl<T> PyNumberRPowProtocolImpl for T where T: PyNumberRPowProtocol + PythonObjectWithTypeObject {
+    #[inline]
+    fn implemented() -> bool { true }
+
+    fn dispatch(py: Python, lhs: &PyObject, rhs: &PyObject, modulo: &PyObject)
+                -> Option<PyResult<PyObject>> {
+        let ty = T::type_object(py);
+        if ty.is_instance(py, lhs) || !ty.is_instance(py, rhs) {
+            return None
+        }
+        if modulo.as_ptr() != unsafe { ffi::Py_None() } {
+            return Some(Ok(py.NotImplemented()))
+        }
+        let slf = unsafe { rhs.unchecked_cast_as::<T>() };
+        Some(match lhs.extract(py) {
+            Ok(other) => py_borrow_call!(py, T, slf, false,
+                into_object::<<T as PyNumberRPowProtocol>::Success>(
+                    py, slf.__rpow__(py, other).into())),
+            Err(err) => not_implemented(py, err),
+        })
+    }
+}
+
+fn nb_power<T>() -> Option<ffi::ternaryfunc> where T: PyNumberProtocol {
+    if !<T as PyNumberPowProtocolImpl>::implemented() &&
+        !<T as PyNumberRPowProtocolImpl>::implemented()
+    {
+        return None
+    }
+
+    unsafe extern "C" fn wrap<T>(lhs: *mut ffi::PyObject,
+                                 rhs: *mut ffi::PyObject,
+                                 modulo: *mut ffi::PyObject) -> *mut ffi::PyObject
+        where T: PyNumberProtocol
+    {
+        const LOCATION: &'static str = "T.__pow__()";
+        handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+            let lhs = PyObject::from_borrowed_ptr(py, lhs);
+            let rhs = PyObject::from_borrowed_ptr(py, rhs);
+            let modulo = PyObject::from_borrowed_ptr(py, modulo);
+            let ret = <T as PyNumberPowProtocolImpl>::dispatch(py, &lhs, &rhs, &modulo)
+                .or_else(|| <T as PyNumberRPowProtocolImpl>::dispatch(py, &lhs, &rhs, &modulo))
+                .unwrap_or_else(|| Ok(py.NotImplemented()));
+            PyDrop::release_ref(lhs, py);
+            PyDrop::release_ref(rhs, py);
+            PyDrop::release_ref(modulo, py);
+            ret
+        })
+    }
+    Some(wrap::<T>)
+}
+
+/// Slot of a protocol method, defined only if the marker trait is implemented.
+macro_rules! number_slot {
+    ($impl_trait:ident, $proto:ident, $slot:ident, $slot_type:ty, $func:expr) => {
+        trait $impl_trait {
+            fn $slot() -> Option<$slot_type>;
+        }
+
+        impl<T> $impl_trait for T where T: PyNumberProtocol {
+            #[inline]
+            default fn $slot() -> Option<$slot_type> { None }
         }
 
-        Some(meth)
+        impl<T> $impl_trait for T where T: $proto {
+            #[inline]
+            fn $slot() -> Option<$slot_type> { $func }
+        }
     }
 }
+
+number_slot!(PyNumberIAddProtocolImpl, PyNumberIAddProtocol, nb_inplace_add, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIAddProtocol, T::__iadd__));
+number_slot!(PyNumberISubProtocolImpl, PyNumberISubProtocol, nb_inplace_subtract, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberISubProtocol, T::__isub__));
+number_slot!(PyNumberIMulProtocolImpl, PyNumberIMulProtocol, nb_inplace_multiply, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIMulProtocol, T::__imul__));
+number_slot!(PyNumberIMatmulProtocolImpl, PyNumberIMatmulProtocol, nb_inplace_matrix_multiply, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIMatmulProtocol, T::__imatmul__));
+number_slot!(PyNumberITruedivProtocolImpl, PyNumberITruedivProtocol, nb_inplace_true_divide, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberITruedivProtocol, T::__itruediv__));
+number_slot!(PyNumberIFloordivProtocolImpl, PyNumberIFloordivProtocol, nb_inplace_floor_divide, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIFloordivProtocol, T::__ifloordiv__));
+number_slot!(PyNumberIModProtocolImpl, PyNumberIModProtocol, nb_inplace_remainder, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIModProtocol, T::__imod__));
+number_slot!(PyNumberIPowProtocolImpl, PyNumberIPowProtocol, nb_inplace_power, ffi::ternaryfunc,
+             py_ternary_inplace_func!(PyNumberIPowProtocol, T::__ipow__));
+number_slot!(PyNumberILShiftProtocolImpl, PyNumberILShiftProtocol, nb_inplace_lshift, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberILShiftProtocol, T::__ilshift__));
+number_slot!(PyNumberIRShiftProtocolImpl, PyNumberIRShiftProtocol, nb_inplace_rshift, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIRShiftProtocol, T::__irshift__));
+number_slot!(PyNumberIAndProtocolImpl, PyNumberIAndProtocol, nb_inplace_and, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIAndProtocol, T::__iand__));
+number_slot!(PyNumberIXorProtocolImpl, PyNumberIXorProtocol, nb_inplace_xor, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIXorProtocol, T::__ixor__));
+number_slot!(PyNumberIOrProtocolImpl, PyNumberIOrProtocol, nb_inplace_or, ffi::binaryfunc,
+             py_binary_inplace_func!(PyNumberIOrProtocol, T::__ior__));
+number_slot!(PyNumberNegProtocolImpl, PyNumberNegProtocol, nb_negative, ffi::unaryfunc,
+             py_unary_func!(PyNumberNegProtocol, T::__neg__, PyObjectCallbackConverter));
+number_slot!(PyNumberPosProtocolImpl, PyNumberPosProtocol, nb_positive, ffi::unaryfunc,
+             py_unary_func!(PyNumberPosProtocol, T::__pos__, PyObjectCallbackConverter));
+number_slot!(PyNumberAbsProtocolImpl, PyNumberAbsProtocol, nb_absolute, ffi::unaryfunc,
+             py_unary_func!(PyNumberAbsProtocol, T::__abs__, PyObjectCallbackConverter));
+number_slot!(PyNumberInvertProtocolImpl, PyNumberInvertProtocol, nb_invert, ffi::unaryfunc,
+             py_unary_func!(PyNumberInvertProtocol, T::__invert__, PyObjectCallbackConverter));
+number_slot!(PyNumberIntProtocolImpl, PyNumberIntProtocol, nb_int, ffi::unaryfunc,
+             py_unary_func!(PyNumberIntProtocol, T::__int__, PyObjectCallbackConverter));
+number_slot!(PyNumberFloatProtocolImpl, PyNumberFloatProtocol, nb_float, ffi::unaryfunc,
+             py_unary_func!(PyNumberFloatProtocol, T::__float__, PyObjectCallbackConverter));
+number_slot!(PyNumberIndexProtocolImpl, PyNumberIndexProtocol, nb_index, ffi::unaryfunc,
+             py_unary_func!(PyNumberIndexProtocol, T::__index__, PyObjectCallbackConverter));
+
+/// `__complex__` has no slot, `#[proto]` adds this wrapper as python method.
+#[doc(hidden)]
+pub unsafe extern "C" fn complex_method<T>(slf: *mut ffi::PyObject, _: *mut ffi::PyObject)
+                                           -> *mut ffi::PyObject
+    where T: PyNumberComplexProtocol
+{
+    const LOCATION: &'static str = "T.__complex__()";
+    handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+        let obj = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+        let ret: PyResult<(f64, f64)> = py_borrow_call!(py, T, &obj, false,
+                                                        obj.__complex__(py).into());
+        PyDrop::release_ref(obj, py);
+        ret.and_then(
+            |(real, imag)| result_from_owned_ptr(py, ffi::PyComplex_FromDoubles(real, imag)))
+    })
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+//! Pickle support of `#[class(pickle)]` types
+//!
+//! Instance is reduced to its state, `__reduce__` returns
+//! `(type(obj)._from_state, (state,))`, followed by `obj.__dict__` of python subclass
+//! instance, so the class has to be importable by `__module__` and `__qualname__`,
+//! i.e. added to its module with `PyModule::add_class`.
+//! `__copy__` rebuilds the object from the state directly,
+//! `__deepcopy__` rebuilds it from a deep copy of the state.
+//!
+//! more information on pickling can be found
+//! https://docs.python.org/3/library/pickle.html#pickling-class-instances
+
+use ffi;
+use err::PyResult;
+use python::{Python, PythonObject, PythonObjectWithTypeObject, PyDrop, PyClone};
+use objects::{exc, PyObject, PyModule, PyTuple, PyType, PyDict};
+use objectprotocol::ObjectProtocol;
+use conversion::{ToPyObject, FromPyObject};
+use callback::{handle_callback, PyObjectCallbackConverter};
+use class::cell::PyCellProtocolImpl;
+use class::methods::{PyMethodDef, PyMethodDefType, PyMethodType};
+
+
+/// State conversion of `#[class(pickle)]` instances
+pub trait PyPickleProtocol: PythonObject + PythonObjectWithTypeObject {
+    /// Python representation of the state is stored in the pickle.
+    type State: ToPyObject + for<'a> FromPyObject<'a>;
+
+    /// State of the instance, holds shared borrow of instance data.
+    fn to_state(&self, py: Python) -> PyResult<Self::State>;
+
+    /// Creates new instance of `cls` from the state, `cls` is the class
+    /// or its python subclass, i.e. create it with `create_instance_of`.
+    fn from_state(py: Python, cls: &PyType, state: Self::State) -> PyResult<Self>;
+}
+
+
+#[doc(hidden)]
+pub trait PyPickleProtocolImpl {
+    fn methods() -> Vec<PyMethodDefType>;
+}
+
+impl<T> PyPickleProtocolImpl for T {
+    default fn methods() -> Vec<PyMethodDefType> {
+        Vec::new()
+    }
+}
+
+/// Python methods of `#[class(pickle)]` type
+#[doc(hidden)]
+pub fn pickle_methods<T>() -> Vec<PyMethodDefType> where T: PyPickleProtocol {
+    vec![
+        PyMethodDefType::Method(PyMethodDef {
+            ml_name: "__reduce__",
+            ml_meth: PyMethodType::PyCFunction(reduce::<T>),
+            ml_flags: ffi::METH_NOARGS,
+            ml_doc: "Return state information for pickling.",
+        }),
+        PyMethodDefType::Method(PyMethodDef {
+            ml_name: "__copy__",
+            ml_meth: PyMethodType::PyCFunction(copy::<T>),
+            ml_flags: ffi::METH_NOARGS,
+            ml_doc: "Return a shallow copy of the object.",
+        }),
+        PyMethodDefType::Method(PyMethodDef {
+            ml_name: "__deepcopy__",
+            ml_meth: PyMethodType::PyCFunction(deepcopy::<T>),
+            ml_flags: ffi::METH_O,
+            ml_doc: "Return a deep copy of the object.",
+        }),
+        PyMethodDefType::Class(PyMethodDef {
+            ml_name: "_from_state",
+            ml_meth: PyMethodType::PyCFunction(from_state::<T>),
+            ml_flags: ffi::METH_O | ffi::METH_CLASS,
+            ml_doc: "Create the object from pickled state.",
+        }),
+    ]
+}
+
+/// State of the object at `slf`, instance data is borrowed while `to_state` runs
+unsafe fn get_state<T>(py: Python, slf: *mut ffi::PyObject) -> PyResult<T::State>
+    where T: PyPickleProtocol
+{
+    let obj = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+    let ret = match <T as PyCellProtocolImpl>::borrow_data(py, slf, false) {
+        Ok(_borrow) => obj.to_state(py),
+        Err(e) => Err(e),
+    };
+    PyDrop::release_ref(obj, py);
+    ret
+}
+
+/// Non-empty `__dict__` of python subclass instance
+fn instance_dict(py: Python, obj: &PyObject) -> PyResult<Option<PyDict>> {
+    match obj.getattr(py, "__dict__") {
+        Ok(dict) => {
+            let dict = dict.cast_into::<PyDict>(py)?;
+            Ok(if dict.len(py) == 0 { None } else { Some(dict) })
+        }
+        Err(ref err) if err.matches(py, py.get_type::<exc::AttributeError>()) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Updates `__dict__` of the new instance with `dict` of the original one
+fn update_dict(py: Python, obj: &PyObject, dict: PyObject) -> PyResult<()> {
+    obj.getattr(py, "__dict__")?.call_method(py, "update", (dict,), None).map(|_| ())
+}
+
+unsafe extern "C" fn reduce<T>(slf: *mut ffi::PyObject, _: *mut ffi::PyObject)
+                               -> *mut ffi::PyObject
+    where T: PyPickleProtocol
+{
+    const LOCATION: &'static str = "T.__reduce__()";
+    handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+        let obj = PyObject::from_borrowed_ptr(py, slf);
+        let state = get_state::<T>(py, slf)?.to_py_object(py);
+        let from_state = obj.get_type(py).as_object().getattr(py, "_from_state")?;
+        let args = PyTuple::new(py, &[state]).into_object();
+        let reduced = match instance_dict(py, &obj)? {
+            Some(dict) => PyTuple::new(py, &[from_state, args, dict.into_object()]),
+            None => PyTuple::new(py, &[from_state, args]),
+        };
+        Ok(reduced.into_object())
+    })
+}
+
+unsafe extern "C" fn copy<T>(slf: *mut ffi::PyObject, _: *mut ffi::PyObject)
+                             -> *mut ffi::PyObject
+    where T: PyPickleProtocol
+{
+    const LOCATION: &'static str = "T.__copy__()";
+    handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+        let obj = PyObject::from_borrowed_ptr(py, slf);
+        let state = get_state::<T>(py, slf)?;
+        let copy = T::from_state(py, &obj.get_type(py), state)?.into_object();
+        if let Some(dict) = instance_dict(py, &obj)? {
+            update_dict(py, &copy, dict.into_object())?;
+        }
+        Ok(copy)
+    })
+}
+
+unsafe extern "C" fn deepcopy<T>(slf: *mut ffi::PyObject, memo: *mut ffi::PyObject)
+                                 -> *mut ffi::PyObject
+    where T: PyPickleProtocol
+{
+    const LOCATION: &'static str = "T.__deepcopy__()";
+    handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+        let obj = PyObject::from_borrowed_ptr(py, slf);
+        let memo = PyObject::from_borrowed_ptr(py, memo);
+        let copy_module = PyModule::import(py, "copy")?;
+
+        let state = get_state::<T>(py, slf)?.to_py_object(py);
+        let state = copy_module.call(py, "deepcopy", (state, memo.clone_ref(py)), None)?;
+        let state = T::State::extract(py, &state)?;
+        let copy = T::from_state(py, &obj.get_type(py), state)?.into_object();
+        if let Some(dict) = instance_dict(py, &obj)? {
+            // `__dict__` can refer to the object itself, like `copy.deepcopy` does
+            // the copy is memoized before its `__dict__` is copied
+            memo.set_item(py, slf as usize, &copy)?;
+            let dict = copy_module.call(py, "deepcopy", (dict, memo), None)?;
+            update_dict(py, &copy, dict)?;
+        }
+        Ok(copy)
+    })
+}
+
+unsafe extern "C" fn from_state<T>(cls: *mut ffi::PyObject, state: *mut ffi::PyObject)
+                                   -> *mut ffi::PyObject
+    where T: PyPickleProtocol
+{
+    const LOCATION: &'static str = "T._from_state()";
+    handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+        // method descriptor checks that `cls` is subtype of the class
+        let cls = PyType::from_type_ptr(py, cls as *mut ffi::PyTypeObject);
+        let state = PyObject::from_borrowed_ptr(py, state);
+        let ret = T::State::extract(py, &state)
+            .and_then(|state| T::from_state(py, &cls, state))
+            .map(|obj| obj.into_object());
+        state.release_ref(py);
+        ret
+    })
+}
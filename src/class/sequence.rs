@@ -2,13 +2,19 @@
 
 //! Python Sequence Interface
 //! Trait and support implementation for implementing sequence
+//!
+//! `__getitem__` and `__setitem__` receive `SliceOrIndex` key, negative indices and slices
+//! are resolved against `__len__` of the sequence, non-negative index is passed as is
+//! and the sequence raises `IndexError` if it is out of range. Besides `sq_item` and `sq_ass_item`
+//! they fill `mp_subscript` and `mp_ass_subscript` slots, so `obj[1:10:2]` works.
+//! Value of `__setitem__` is `None` for `del obj[key]`.
 
 use std::os::raw::c_int;
 
 use ffi;
-use err::PyResult;
+use err::{PyErr, PyResult};
 use python::{Python, PythonObject, PyDrop};
-use objects::PyObject;
+use objects::{PyObject, SliceOrIndex};
 use callback::{
     PyObjectCallbackConverter,
     LenResultConverter,
@@ -24,11 +30,12 @@ pub trait PySequenceProtocol: PythonObject {
         where Self: PySequenceLenProtocol
     { unimplemented!() }
 
-    fn __getitem__(&self, py: Python, key: isize) -> Self::Result
+    fn __getitem__(&self, py: Python, key: SliceOrIndex) -> Self::Result
         where Self: PySequenceGetItemProtocol
     { unimplemented!() }
 
-    fn __setitem__(&self, py: Python, key: isize, value: Option<Self::Value>) -> Self::Result
+    fn __setitem__(&self, py: Python, key: SliceOrIndex, value: Option<Self::Value>)
+                   -> Self::Result
         where Self: PySequenceSetItemProtocol
     { unimplemented!() }
     
@@ -98,11 +105,16 @@ pub trait PySequenceInplaceRepeatProtocol: PySequenceProtocol {
 #[doc(hidden)]
 pub trait PySequenceProtocolImpl {
     fn tp_as_sequence() -> Option<ffi::PySequenceMethods>;
+
+    /// Subscript slots, `__getitem__` and `__setitem__` of mapping protocol take precedence
+    fn tp_as_mapping() -> Option<ffi::PyMappingMethods>;
 }
 
 impl<T> PySequenceProtocolImpl for T {
     #[inline]
     default fn tp_as_sequence() -> Option<ffi::PySequenceMethods> { None }
+    #[inline]
+    default fn tp_as_mapping() -> Option<ffi::PyMappingMethods> { None }
 }
 
 impl<T> PySequenceProtocolImpl for T where T: PySequenceProtocol {
@@ -121,6 +133,23 @@ impl<T> PySequenceProtocolImpl for T where T: PySequenceProtocol {
             sq_inplace_repeat: Self::sq_inplace_repeat(),
         })
     }
+
+    #[inline]
+    fn tp_as_mapping() -> Option<ffi::PyMappingMethods> {
+        Some(ffi::PyMappingMethods {
+            mp_length: None,
+            mp_subscript: Self::mp_subscript(),
+            mp_ass_subscript: Self::mp_ass_subscript(),
+        })
+    }
+}
+
+/// Length of the sequence, negative indices and slices are resolved against it
+unsafe fn sequence_len(py: Python, slf: *mut ffi::PyObject) -> PyResult<isize> {
+    match ffi::PyObject_Size(slf) {
+        -1 => Err(PyErr::fetch(py)),
+        len => Ok(len as isize),
+    }
 }
 
 trait PySequenceLenProtocolImpl {
@@ -145,6 +174,7 @@ impl<T> PySequenceLenProtocolImpl for T
 
 trait PySequenceGetItemProtocolImpl {
     fn sq_item() -> Option<ffi::ssizeargfunc>;
+    fn mp_subscript() -> Option<ffi::binaryfunc>;
 }
 
 impl<T> PySequenceGetItemProtocolImpl for T
@@ -152,6 +182,8 @@ impl<T> PySequenceGetItemProtocolImpl for T
 {
     #[inline]
     default fn sq_item() -> Option<ffi::ssizeargfunc> { None }
+    #[inline]
+    default fn mp_subscript() -> Option<ffi::binaryfunc> { None }
 }
 
 impl<T> PySequenceGetItemProtocolImpl for T
@@ -159,12 +191,51 @@ impl<T> PySequenceGetItemProtocolImpl for T
 {
     #[inline]
     fn sq_item() -> Option<ffi::ssizeargfunc> {
-        py_ssizearg_func!(PySequenceGetItemProtocol, T::__getitem__, PyObjectCallbackConverter)
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     key: ffi::Py_ssize_t) -> *mut ffi::PyObject
+            where T: PySequenceGetItemProtocol
+        {
+            const LOCATION: &'static str = "T.__getitem__()";
+            ::callback::handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+                SliceOrIndex::index(py, key as isize)
+                    .and_then(|key| get_item::<T>(py, slf, key))
+            })
+        }
+        Some(wrap::<T>)
     }
+
+    #[inline]
+    fn mp_subscript() -> Option<ffi::binaryfunc> {
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     key: *mut ffi::PyObject) -> *mut ffi::PyObject
+            where T: PySequenceGetItemProtocol
+        {
+            const LOCATION: &'static str = "T.__getitem__()";
+            ::callback::handle_callback(LOCATION, PyObjectCallbackConverter, |py| {
+                let key = PyObject::from_borrowed_ptr(py, key);
+                let ret = SliceOrIndex::extract(py, &key, || sequence_len(py, slf))
+                    .and_then(|key| get_item::<T>(py, slf, key));
+                PyDrop::release_ref(key, py);
+                ret
+            })
+        }
+        Some(wrap::<T>)
+    }
+}
+
+unsafe fn get_item<T>(py: Python, slf: *mut ffi::PyObject, key: SliceOrIndex)
+                      -> PyResult<T::Success>
+    where T: PySequenceGetItemProtocol
+{
+    let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+    let ret = py_borrow_call!(py, T, &slf, false, slf.__getitem__(py, key).into());
+    PyDrop::release_ref(slf, py);
+    ret
 }
 
 trait PySequenceSetItemProtocolImpl {
     fn sq_ass_item() -> Option<ffi::ssizeobjargproc>;
+    fn mp_ass_subscript() -> Option<ffi::objobjargproc>;
 }
 
 impl<T> PySequenceSetItemProtocolImpl for T
@@ -172,6 +243,8 @@ impl<T> PySequenceSetItemProtocolImpl for T
 {
     #[inline]
     default fn sq_ass_item() -> Option<ffi::ssizeobjargproc> { None }
+    #[inline]
+    default fn mp_ass_subscript() -> Option<ffi::objobjargproc> { None }
 }
 
 impl<T> PySequenceSetItemProtocolImpl for T
@@ -179,30 +252,33 @@ impl<T> PySequenceSetItemProtocolImpl for T
 {
     #[inline]
     fn sq_ass_item() -> Option<ffi::ssizeobjargproc> {
-         unsafe extern "C" fn wrap<T>(
-             slf: *mut ffi::PyObject,
-             key: ffi::Py_ssize_t,
-             value: *mut ffi::PyObject,
-         ) -> c_int
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     key: ffi::Py_ssize_t,
+                                     value: *mut ffi::PyObject) -> c_int
+            where T: PySequenceSetItemProtocol
+        {
+            const LOCATION: &'static str = "T.__setitem__()";
+            ::callback::handle_callback(LOCATION, UnitCallbackConverter, |py| {
+                SliceOrIndex::index(py, key as isize)
+                    .and_then(|key| set_item::<T>(py, slf, key, value))
+            })
+        }
+        Some(wrap::<T>)
+    }
+
+    #[inline]
+    fn mp_ass_subscript() -> Option<ffi::objobjargproc> {
+        unsafe extern "C" fn wrap<T>(slf: *mut ffi::PyObject,
+                                     key: *mut ffi::PyObject,
+                                     value: *mut ffi::PyObject) -> c_int
             where T: PySequenceSetItemProtocol
         {
             const LOCATION: &'static str = "T.__setitem__()";
             ::callback::handle_callback(LOCATION, UnitCallbackConverter, |py| {
-                let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
-                
-                let ret = if value.is_null() {
-                    slf.__setitem__(py, key as isize, None).into()
-                } else {
-                    let value = PyObject::from_borrowed_ptr(py, value);
-                    let ret = match value.extract(py) {
-                        Ok(value) => slf.__setitem__(py, key as isize, Some(value)).into(),
-                        Err(e) => Err(e),
-                    };
-                    PyDrop::release_ref(value, py);
-                    ret
-                };
-
-                PyDrop::release_ref(slf, py);
+                let key = PyObject::from_borrowed_ptr(py, key);
+                let ret = SliceOrIndex::extract(py, &key, || sequence_len(py, slf))
+                    .and_then(|key| set_item::<T>(py, slf, key, value));
+                PyDrop::release_ref(key, py);
                 ret
             })
         }
@@ -210,6 +286,28 @@ impl<T> PySequenceSetItemProtocolImpl for T
     }
 }
 
+/// `value` is null for `del obj[key]`
+unsafe fn set_item<T>(py: Python, slf: *mut ffi::PyObject, key: SliceOrIndex,
+                      value: *mut ffi::PyObject) -> PyResult<()>
+    where T: PySequenceSetItemProtocol
+{
+    let slf = PyObject::from_borrowed_ptr(py, slf).unchecked_cast_into::<T>();
+    let ret = if value.is_null() {
+        py_borrow_call!(py, T, &slf, false, slf.__setitem__(py, key, None).into())
+    } else {
+        let value = PyObject::from_borrowed_ptr(py, value);
+        let ret = match value.extract(py) {
+            Ok(value) => py_borrow_call!(py, T, &slf, false,
+                                         slf.__setitem__(py, key, Some(value)).into()),
+            Err(e) => Err(e),
+        };
+        PyDrop::release_ref(value, py);
+        ret
+    };
+    PyDrop::release_ref(slf, py);
+    ret
+}
+
 trait PySequenceContainsProtocolImpl {
     fn sq_contains() -> Option<ffi::objobjproc>;
 }
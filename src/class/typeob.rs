@@ -3,128 +3,235 @@
 use std::mem;
 use std::ffi::CString;
 use std::collections::HashMap;
+use std::os::raw::{c_int, c_void};
 
-use ::{ffi, class, PyErr, Python, PyResult, PythonObject};
+use ::{ffi, class, PyErr, Python, PyResult, PythonObject, PythonObjectWithTypeObject};
 use objects::{PyType, PyModule};
 use callback::AbortOnDrop;
-use class::{BaseObject, PyMethodDefType};
+use objectprotocol::ObjectProtocol;
+use class::{BaseObject, PyBaseType, PyMethodDefType};
 
 
 pub trait PyTypeObject : BaseObject + PythonObject {
 
     fn add_to_module(py: Python, module: &PyModule) -> PyResult<()>;
 
-    unsafe fn type_obj() -> &'static mut ffi::PyTypeObject;
-
     unsafe fn initialized(py: Python, module_name: Option<&str>) -> PyType;
 
 }
 
-pub fn initialize_type<T>(py: Python, module_name: Option<&str>,
-                          type_object: &mut ffi::PyTypeObject) -> PyResult<PyType>
+/// Slots and flags of `PyType_Spec`, collected from implemented protocols.
+pub struct TypeSlots {
+    pub flags: ::c_ulong,
+    slots: Vec<ffi::PyType_Slot>,
+}
+
+impl TypeSlots {
+    pub fn new() -> TypeSlots {
+        TypeSlots { flags: ffi::Py_TPFLAGS_DEFAULT, slots: Vec::new() }
+    }
+
+    /// Adds slot function or data pointer, see `ffi::typeslots` for slot ids.
+    pub fn push(&mut self, slot: c_int, pfunc: *mut c_void) {
+        self.slots.push(ffi::PyType_Slot { slot: slot, pfunc: pfunc });
+    }
+}
+
+/// Type object of `#[class(base = ...)]` base type.
+/// `PyType_FromSpecWithBases` fails if the base type does not allow subclasses,
+/// so the base type has to be native type or `#[class(subclass)]`.
+pub fn base_type_object<T>(py: Python) -> PyType where T: PyBaseType + PythonObjectWithTypeObject {
+    py.get_type::<T>()
+}
+
+/// Adds type object of the class to `module` under `name`.
+/// Type object can be created before the class is added to the module,
+/// so `__module__` of the type is set to the module name.
+pub fn add_type_to_module(py: Python, module: &PyModule, name: &str, ty: PyType)
+                          -> PyResult<()> {
+    ty.as_object().setattr(py, "__module__", module.name(py)?)?;
+    module.add(py, name, ty)
+}
+
+/// Creates heap type for the class with `PyType_FromSpec`.
+///
+/// Each class gets its own slot arrays, method and property definitions are
+/// leaked, because the type object refers to them for its whole life.
+/// `flags` are added to type flags, i.e. `Py_TPFLAGS_BASETYPE` for `#[class(subclass)]`.
+pub fn initialize_type<T>(py: Python, module_name: Option<&str>, type_name: &str,
+                          base: Option<&PyType>, doc: &str, flags: ::c_ulong) -> PyResult<PyType>
     where T: BaseObject + PythonObject
 {
-    // type name
+    // type name, heap type keeps pointer to the spec name
     let name = match module_name {
-        Some(module_name) => CString::new(
-            format!("{}.{}", module_name, stringify!(type_name))),
-        None => CString::new(stringify!(type_name))
+        Some(module_name) => CString::new(format!("{}.{}", module_name, type_name)),
+        None => CString::new(type_name)
     };
     let name = name.expect(
         "Module name/type name must not contain NUL byte").into_raw();
 
-    type_object.tp_name = name;
+    let mut slots = TypeSlots::new();
+    slots.flags |= flags;
 
     // dealloc
-    type_object.tp_dealloc = Some(tp_dealloc_callback::<T>);
+    push_slot!(slots, Py_tp_dealloc, Some(tp_dealloc_callback::<T>));
+
+    // docstring, PyType_FromSpec copies it
+    let doc = if doc.is_empty() {
+        None
+    } else {
+        Some(CString::new(doc).expect("Docstring must not contain NUL byte"))
+    };
+    if let Some(ref doc) = doc {
+        slots.push(ffi::Py_tp_doc, doc.as_ptr() as *mut c_void);
+    }
 
     // GC support
-    <T as class::gc::PyGCProtocolImpl>::update_type_object(type_object);
+    <T as class::gc::PyGCProtocolImpl>::update_type_slots(&mut slots);
 
-    // type size
-    type_object.tp_basicsize = <T as BaseObject>::size() as ffi::Py_ssize_t;
+    // basic customization
+    <T as class::basic::PyObjectProtocolImpl>::update_type_slots(&mut slots);
 
     // descriptor protocol
-    type_object.tp_descr_get = class::descr::get_descrfunc::<T>();
-    type_object.tp_descr_set = class::descr::set_descrfunc::<T>();
+    push_slot!(slots, Py_tp_descr_get, class::descr::get_descrfunc::<T>());
+    push_slot!(slots, Py_tp_descr_set, class::descr::set_descrfunc::<T>());
+
+    // iterator methods
+    <T as class::iter::PyIterProtocolImpl>::update_type_slots(&mut slots);
 
     // number methods
-    if let Some(meth) = ffi::PyNumberMethods::new::<T>() {
-        static mut NB_METHODS: ffi::PyNumberMethods = ffi::PyNumberMethods_INIT;
-        *(unsafe { &mut NB_METHODS }) = meth;
-        type_object.tp_as_number = unsafe { &mut NB_METHODS };
-    } else {
-        type_object.tp_as_number = 0 as *mut ffi::PyNumberMethods;
+    <T as class::number::PyNumberProtocolImpl>::update_type_slots(&mut slots);
+
+    // sequence subscript, mapping methods override it
+    if let Some(meth) = <T as class::sequence::PySequenceProtocolImpl>::tp_as_mapping() {
+        push_slot!(slots, Py_mp_subscript, meth.mp_subscript);
+        push_slot!(slots, Py_mp_ass_subscript, meth.mp_ass_subscript);
     }
 
     // mapping methods
-    if let Some(meth) = ffi::PyMappingMethods::new::<T>() {
-        static mut MP_METHODS: ffi::PyMappingMethods = ffi::PyMappingMethods_INIT;
-        *(unsafe { &mut MP_METHODS }) = meth;
-        type_object.tp_as_mapping = unsafe { &mut MP_METHODS };
-    } else {
-        type_object.tp_as_mapping = 0 as *mut ffi::PyMappingMethods;
+    if let Some(meth) = <T as class::mapping::PyMappingProtocolImpl>::tp_as_mapping() {
+        push_slot!(slots, Py_mp_length, meth.mp_length);
+        push_slot!(slots, Py_mp_subscript, meth.mp_subscript);
+        push_slot!(slots, Py_mp_ass_subscript, meth.mp_ass_subscript);
     }
 
     // sequence methods
-    if let Some(meth) = ffi::PySequenceMethods::new::<T>() {
-        static mut SQ_METHODS: ffi::PySequenceMethods = ffi::PySequenceMethods_INIT;
-        *(unsafe { &mut SQ_METHODS }) = meth;
-        type_object.tp_as_sequence = unsafe { &mut SQ_METHODS };
-    } else {
-        type_object.tp_as_sequence = 0 as *mut ffi::PySequenceMethods;
+    if let Some(meth) = <T as class::sequence::PySequenceProtocolImpl>::tp_as_sequence() {
+        push_slot!(slots, Py_sq_length, meth.sq_length);
+        push_slot!(slots, Py_sq_concat, meth.sq_concat);
+        push_slot!(slots, Py_sq_repeat, meth.sq_repeat);
+        push_slot!(slots, Py_sq_item, meth.sq_item);
+        push_slot!(slots, Py_sq_ass_item, meth.sq_ass_item);
+        push_slot!(slots, Py_sq_contains, meth.sq_contains);
+        push_slot!(slots, Py_sq_inplace_concat, meth.sq_inplace_concat);
+        push_slot!(slots, Py_sq_inplace_repeat, meth.sq_inplace_repeat);
     }
 
     // async methods
-    if let Some(meth) = ffi::PyAsyncMethods::new::<T>() {
-        static mut ASYNC_METHODS: ffi::PyAsyncMethods = ffi::PyAsyncMethods_INIT;
-        *(unsafe { &mut ASYNC_METHODS }) = meth;
-        type_object.tp_as_async = unsafe { &mut ASYNC_METHODS };
-    } else {
-        type_object.tp_as_async = 0 as *mut ffi::PyAsyncMethods;
-    }
-
-    // buffer protocol
-    if let Some(meth) = ffi::PyBufferProcs::new::<T>() {
-        static mut BUFFER_PROCS: ffi::PyBufferProcs = ffi::PyBufferProcs_INIT;
-        *(unsafe { &mut BUFFER_PROCS }) = meth;
-        type_object.tp_as_buffer = unsafe { &mut BUFFER_PROCS };
-    } else {
-        type_object.tp_as_buffer = 0 as *mut ffi::PyBufferProcs;
+    if let Some(meth) = <T as class::async::PyAsyncProtocolImpl>::tp_as_async() {
+        push_slot!(slots, Py_am_await, meth.am_await);
+        push_slot!(slots, Py_am_aiter, meth.am_aiter);
+        push_slot!(slots, Py_am_anext, meth.am_anext);
     }
 
     // normal methods
-    let mut methods = py_class_method_defs::<T>();
+    let (new, call, mut methods) = py_class_method_defs::<T>();
+    // without `#[new]` inherited `tp_new` would leave instance data uninitialized
+    push_slot!(slots, Py_tp_new, Some(new.unwrap_or(tp_new_disabled)));
+    push_slot!(slots, Py_tp_call, call);
     if !methods.is_empty() {
         methods.push(ffi::PyMethodDef_INIT);
-        type_object.tp_methods = methods.as_ptr() as *mut _;
-
-        static mut METHODS: *const ffi::PyMethodDef = 0 as *const _;
-        *(unsafe { &mut METHODS }) = methods.as_ptr();
+        let methods = Box::into_raw(methods.into_boxed_slice());
+        slots.push(ffi::Py_tp_methods, methods as *mut ffi::PyMethodDef as *mut c_void);
     }
 
     // properties
     let mut props = py_class_properties::<T>();
+    if <T as BaseObject>::dict_offset().is_some() {
+        props.push(ffi::PyGetSetDef {
+            name: "__dict__\0".as_ptr() as *mut _,
+            get: Some(ffi::PyObject_GenericGetDict),
+            set: Some(ffi::PyObject_GenericSetDict),
+            doc: 0 as *mut _,
+            closure: 0 as *mut _,
+        });
+    }
     if !props.is_empty() {
         props.push(ffi::PyGetSetDef_INIT);
-        let props = props.into_boxed_slice();
-        type_object.tp_getset = props.as_ptr() as *mut _;
+        let props = Box::into_raw(props.into_boxed_slice());
+        slots.push(ffi::Py_tp_getset, props as *mut ffi::PyGetSetDef as *mut c_void);
+    }
+
+    let TypeSlots { flags, mut slots } = slots;
+    slots.push(ffi::PyType_Slot { slot: 0, pfunc: 0 as *mut c_void });
+    let mut spec = ffi::PyType_Spec {
+        name: name,
+        basicsize: <T as BaseObject>::size() as c_int,
+        itemsize: 0,
+        flags: flags as ::c_uint,
+        slots: slots.as_mut_ptr(),
+    };
+
+    // create type object
+    let type_object = unsafe {
+        let ptr = match base {
+            Some(base) => {
+                let bases = ffi::PyTuple_Pack(1, base.as_object().as_ptr());
+                let ptr = ffi::PyType_FromSpecWithBases(&mut spec, bases);
+                ffi::Py_XDECREF(bases);
+                ptr
+            },
+            None => ffi::PyType_FromSpec(&mut spec),
+        };
+        try!(::err::result_cast_from_owned_ptr::<PyType>(py, ptr))
+    };
 
-        static mut PROPS: *const ffi::PyGetSetDef = 0 as *const _;
-        *(unsafe { &mut PROPS }) = props.as_ptr();
+    // buffer protocol, there is no type slot for it
+    #[cfg(not(Py_LIMITED_API))]
+    {
+        if let Some(meth) = <T as class::buffer::PyBufferProtocolImpl>::tp_as_buffer() {
+            unsafe {
+                let heap_type = type_object.as_type_ptr() as *mut ffi::PyHeapTypeObject;
+                (*heap_type).as_buffer = meth;
+            }
+        }
+    }
 
-        // strange
-        mem::forget(props);
+    // instance dict and weak references
+    if <T as BaseObject>::dict_offset().is_some() || <T as BaseObject>::weakref_offset().is_some() {
+        set_instance_offsets::<T>(py, &type_object)?;
     }
 
-    // register type object
+    // class attributes
+    py_class_attributes::<T>(py, &type_object)?;
+
+    Ok(type_object)
+}
+
+/// `PyType_Spec` can not describe `tp_dictoffset` and `tp_weaklistoffset`,
+/// they are set on the created type, before any instance or subclass exists.
+#[cfg(not(Py_LIMITED_API))]
+fn set_instance_offsets<T>(_py: Python, type_object: &PyType) -> PyResult<()>
+    where T: BaseObject
+{
     unsafe {
-        if ffi::PyType_Ready(type_object) == 0 {
-            Ok(PyType::from_type_ptr(py, type_object))
-        } else {
-            Err(PyErr::fetch(py))
+        let ty = type_object.as_type_ptr();
+        if let Some(offset) = <T as BaseObject>::dict_offset() {
+            (*ty).tp_dictoffset = offset as ffi::Py_ssize_t;
+        }
+        if let Some(offset) = <T as BaseObject>::weakref_offset() {
+            (*ty).tp_weaklistoffset = offset as ffi::Py_ssize_t;
         }
     }
+    Ok(())
+}
+
+#[cfg(Py_LIMITED_API)]
+fn set_instance_offsets<T>(py: Python, _type_object: &PyType) -> PyResult<()>
+    where T: BaseObject
+{
+    Err(PyErr::new::<::objects::exc::TypeError, _>(
+        py, "#[class(dict)] and #[class(weakref)] are not supported with Py_LIMITED_API"))
 }
 
 unsafe extern "C" fn tp_dealloc_callback<T>(obj: *mut ffi::PyObject) where T: BaseObject
@@ -136,8 +243,22 @@ unsafe extern "C" fn tp_dealloc_callback<T>(obj: *mut ffi::PyObject) where T: Ba
     r
 }
 
-fn py_class_method_defs<T>() -> Vec<ffi::PyMethodDef> {
+/// `tp_new` of classes that can not be instantiated from python
+unsafe extern "C" fn tp_new_disabled(_: *mut ffi::PyTypeObject,
+                                     _: *mut ffi::PyObject,
+                                     _: *mut ffi::PyObject) -> *mut ffi::PyObject
+{
+    let py = Python::assume_gil_acquired();
+    PyErr::new::<::objects::exc::TypeError, _>(py, "No constructor defined").restore(py);
+    ::std::ptr::null_mut()
+}
+
+fn py_class_method_defs<T>() -> (Option<ffi::newfunc>,
+                                 Option<ffi::PyCFunctionWithKeywords>,
+                                 Vec<ffi::PyMethodDef>) {
     let mut defs = Vec::new();
+    let mut new = None;
+    let mut call = None;
 
     for def in <T as class::context::PyContextProtocolImpl>::py_methods() {
         match def {
@@ -152,21 +273,51 @@ fn py_class_method_defs<T>() -> Vec<ffi::PyMethodDef> {
             _ => (),
         }
     }
-    for def in <T as class::methods::PyMethodsProtocolImpl>::py_methods() {
+
+    for def in <T as class::descr::PyDescrProtocolImpl>::py_methods() {
+        match def {
+            &PyMethodDefType::Method(ref def) => defs.push(def.as_method_def()),
+            _ => (),
+        }
+    }
+    for def in <T as class::pickle::PyPickleProtocolImpl>::methods() {
         match def {
+            PyMethodDefType::Method(ref def) => defs.push(def.as_method_def()),
+            PyMethodDefType::Class(ref def) => defs.push(def.as_method_def()),
+            _ => (),
+        }
+    }
+
+    for def in class::methods::py_class_methods::<T>() {
+        match def {
+            &PyMethodDefType::New(ref def) => {
+                if let class::methods::PyMethodType::PyNewFunc(meth) = def.ml_meth {
+                    new = Some(meth)
+                }
+            },
+            &PyMethodDefType::Call(ref def) => {
+                if let class::methods::PyMethodType::PyCFunctionWithKeywords(meth) = def.ml_meth {
+                    call = Some(meth)
+                }
+            },
             &PyMethodDefType::Method(ref def) => defs.push(def.as_method_def()),
+            &PyMethodDefType::Class(ref def) => defs.push(def.as_method_def()),
+            &PyMethodDefType::Static(ref def) => defs.push(def.as_method_def()),
             _ => (),
         }
     }
 
-    defs
+    (new, call, defs)
 }
 
 
 fn py_class_properties<T>() -> Vec<ffi::PyGetSetDef> {
     let mut defs = HashMap::new();
 
-    for def in <T as class::methods::PyMethodsProtocolImpl>::py_methods() {
+    let methods = class::methods::py_class_methods::<T>().into_iter()
+        .chain(<T as class::methods::PyPropMethodsProtocolImpl>::py_methods().iter());
+
+    for def in methods {
         match def {
             &PyMethodDefType::Getter(ref getter) => {
                 let name = getter.name.to_string();
@@ -190,3 +341,21 @@ fn py_class_properties<T>() -> Vec<ffi::PyGetSetDef> {
 
     defs.values().map(|i| i.clone()).collect()
 }
+
+
+fn py_class_attributes<T>(py: Python, type_object: &PyType) -> PyResult<()> {
+    for def in class::methods::py_class_methods::<T>() {
+        if let &PyMethodDefType::ClassAttribute(ref attr) = def {
+            let name = CString::new(attr.name).expect(
+                "Class attribute name must not contain NULL byte");
+            let value = (attr.meth)(py);
+            unsafe {
+                if ffi::PyObject_SetAttrString(
+                    type_object.as_object().as_ptr(), name.as_ptr(), value.as_ptr()) != 0 {
+                    return Err(PyErr::fetch(py))
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2017-present PyO3 Project and Contributors
+
+//! Support code of `#[derive(FromPyObject)]` and `#[derive(ToPyObject)]`.
+//!
+//! Extraction keeps track of the path of the value being extracted,
+//! so failures can name the field that caused them, i.e. `Config.retries: expected int, got str`.
+
+use std::ptr;
+use std::os::raw::{c_char, c_int};
+
+use ffi;
+use conversion::{FromPyObject, ToPyObject};
+use err::{self, PyErr, PyResult};
+use objects::{exc, PyObject, PySequence, PyDict, NoArgs};
+use objectprotocol::ObjectProtocol;
+use python::{Python, PythonObject};
+
+
+/// Extraction of a value at `path` of outer value.
+///
+/// Implemented for every `FromPyObject` type, `TypeError` is reported with `expected` type name,
+/// other errors are propagated. Derived types specialize it to report path of the inner field.
+pub trait FromPyObjectPath: Sized {
+    fn extract_path(py: Python, obj: &PyObject, path: &str, expected: &str) -> PyResult<Self>;
+}
+
+impl<T> FromPyObjectPath for T where for<'a> T: FromPyObject<'a> {
+    default fn extract_path(py: Python, obj: &PyObject, path: &str, expected: &str) -> PyResult<T> {
+        T::extract(py, obj).map_err(|err| if is_mismatch(py, &err) {
+            extract_error(py, obj, path, expected)
+        } else {
+            err
+        })
+    }
+}
+
+/// Checks if extraction failed because the value does not match the type.
+/// Only `TypeError` is a mismatch, other errors (i.e. `OverflowError`
+/// or `KeyboardInterrupt`) are propagated by derived extraction.
+pub fn is_mismatch(py: Python, err: &PyErr) -> bool {
+    err.matches(py, py.get_type::<exc::TypeError>())
+}
+
+/// `TypeError` for value at `path` that is not of `expected` type.
+pub fn extract_error(py: Python, obj: &PyObject, path: &str, expected: &str) -> PyErr {
+    let msg = format!("{}: expected {}, got {}", path, expected, obj.get_type(py).name(py));
+    PyErr::new::<exc::TypeError, _>(py, msg)
+}
+
+/// Extracts field from attribute `name` of `obj`.
+pub fn extract_attr<T>(py: Python, obj: &PyObject, path: &str, name: &str, expected: &str)
+                       -> PyResult<T> where T: FromPyObjectPath
+{
+    let path = format!("{}.{}", path, name);
+    match obj.getattr(py, name) {
+        Ok(value) => T::extract_path(py, &value, &path, expected),
+        Err(ref err) if err.matches(py, py.get_type::<exc::AttributeError>()) =>
+            Err(PyErr::new::<exc::TypeError, _>(py, format!("{}: missing attribute", path))),
+        Err(err) => Err(err),
+    }
+}
+
+/// Extracts field from mapping item `key` of `obj`.
+pub fn extract_item<T>(py: Python, obj: &PyObject, path: &str, key: &str, expected: &str)
+                       -> PyResult<T> where T: FromPyObjectPath
+{
+    let path = format!("{}[{:?}]", path, key);
+    match obj.get_item(py, key) {
+        Ok(value) => T::extract_path(py, &value, &path, expected),
+        Err(ref err) if err.matches(py, py.get_type::<exc::KeyError>()) =>
+            Err(PyErr::new::<exc::TypeError, _>(py, format!("{}: missing key", path))),
+        Err(err) => Err(err),
+    }
+}
+
+/// Checks that `obj` is a sequence of `len` items.
+pub fn extract_sequence<'a>(py: Python, obj: &'a PyObject, path: &str, len: usize)
+                            -> PyResult<&'a PySequence>
+{
+    let seq = match obj.cast_as::<PySequence>(py) {
+        Ok(seq) => seq,
+        Err(_) => return Err(extract_error(py, obj, path, "sequence")),
+    };
+    let actual = seq.len(py)?;
+    if actual as usize != len {
+        let msg = format!("{}: expected sequence of length {}, got {} of length {}",
+                          path, len, obj.get_type(py).name(py), actual);
+        return Err(PyErr::new::<exc::TypeError, _>(py, msg))
+    }
+    Ok(seq)
+}
+
+/// Extracts item `index` of sequence checked by `extract_sequence`.
+pub fn extract_seq_item<T>(py: Python, seq: &PySequence, path: &str, index: usize, expected: &str)
+                           -> PyResult<T> where T: FromPyObjectPath
+{
+    let path = format!("{}[{}]", path, index);
+    let value = seq.get_item(py, index as isize)?;
+    T::extract_path(py, &value, &path, expected)
+}
+
+/// `TypeError` for value at `path` that does not match any enum variant,
+/// lists failure of each variant. Failures are mismatches, see `is_mismatch`.
+pub fn extract_variants_error(py: Python, obj: &PyObject, path: &str, expected: &str,
+                              errors: Vec<(&str, PyErr)>) -> PyErr {
+    let mut causes = Vec::new();
+    for (variant, mut err) in errors {
+        let msg = match err.instance(py).str(py) {
+            Ok(s) => s.to_string_lossy(py).into_owned(),
+            Err(_) => String::from("<error>"),
+        };
+        causes.push(format!("{}: {}", variant, msg));
+    }
+    let msg = format!("{}: expected {}, got {} (no variant matched: {})",
+                      path, expected, obj.get_type(py).name(py), causes.join("; "));
+    PyErr::new::<exc::TypeError, _>(py, msg)
+}
+
+/// Sets item `name` of the dict of derived `ToPyObject` conversion.
+///
+/// Conversion can not fail, so this panics on python error (i.e. out of memory)
+/// like `struct_sequence` does.
+pub fn set_dict_item<V>(py: Python, dict: &PyDict, name: &str, value: &V) where V: ToPyObject {
+    err::unwrap_or_panic(py, dict.set_item(py, name, value))
+}
+
+/// `types.SimpleNamespace` with attributes set from `dict`.
+///
+/// Panics on python error, i.e. if `types` module can not be imported.
+pub fn namespace(py: Python, dict: PyDict) -> PyObject {
+    let types = err::unwrap_or_panic(py, py.import("types"));
+    err::unwrap_or_panic(py, types.call(py, "SimpleNamespace", NoArgs, Some(&dict)))
+}
+
+/// Struct sequence type of derived type, created on first use and stored in `ty`.
+///
+/// `name`, `doc` and `fields` have to be nul-terminated.
+pub unsafe fn struct_sequence_type(ty: &mut *mut ffi::PyTypeObject, name: &'static str,
+                                   doc: &'static str, fields: &'static [&'static str])
+                                   -> *mut ffi::PyTypeObject {
+    if ty.is_null() {
+        // python keeps pointers to field names, so descriptions are never freed
+        let mut members: Vec<ffi::PyStructSequence_Field> = fields.iter()
+            .map(|name| ffi::PyStructSequence_Field {
+                name: name.as_ptr() as *mut c_char, doc: ptr::null_mut() })
+            .collect();
+        members.push(ffi::PyStructSequence_Field { name: ptr::null_mut(), doc: ptr::null_mut() });
+        let desc = Box::new(ffi::PyStructSequence_Desc {
+            name: name.as_ptr() as *mut c_char,
+            doc: doc.as_ptr() as *mut c_char,
+            fields: Box::into_raw(members.into_boxed_slice()) as *mut ffi::PyStructSequence_Field,
+            n_in_sequence: fields.len() as c_int,
+        });
+        *ty = ffi::PyStructSequence_NewType(Box::into_raw(desc));
+        if ty.is_null() {
+            panic!("can not create struct sequence type {}", &name[..name.len()-1]);
+        }
+    }
+    *ty
+}
+
+/// Instance of struct sequence type `ty` with `values` as items.
+pub unsafe fn struct_sequence(py: Python, ty: *mut ffi::PyTypeObject, values: Vec<PyObject>)
+                              -> PyObject {
+    let obj = err::from_owned_ptr_or_panic(py, ffi::PyStructSequence_New(ty));
+    for (index, value) in values.into_iter().enumerate() {
+        ffi::PyStructSequence_SetItem(obj.as_ptr(), index as ffi::Py_ssize_t, value.steal_ptr());
+    }
+    obj
+}
@@ -447,6 +447,18 @@ pub unsafe fn cast_from_owned_ptr_or_panic<T>(py : Python, p : *mut ffi::PyObjec
     }
 }
 
+/// Unwraps `result` of Python API call that can not report error to the caller.
+/// Prints the exception and panics on error, like `from_owned_ptr_or_panic`.
+pub fn unwrap_or_panic<T>(py: Python, result: PyResult<T>) -> T {
+    match result {
+        Ok(val) => val,
+        Err(err) => {
+            err.restore(py);
+            panic_after_error(py)
+        }
+    }
+}
+
 /// Returns Ok if the error code is not -1.
 #[inline]
 pub fn error_on_minusone(py : Python, result : libc::c_int) -> PyResult<()> {
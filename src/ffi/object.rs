@@ -222,11 +222,11 @@ pub type allocfunc =
 #[cfg(Py_LIMITED_API)]
 pub enum PyTypeObject { }
 
-#[cfg(not(Py_LIMITED_API))]
-mod typeobject {
-    use ffi::{self, object};
-    use std::os::raw::{c_void, c_char, c_ulong, c_uint};
-    use ffi::pyport::Py_ssize_t;
+/// Protocol method tables, with `Py_LIMITED_API` they are only used to
+/// collect slots of `PyType_Spec`.
+mod protocolmethods {
+    use ffi::object;
+    use std::os::raw::c_void;
 
     #[repr(C)]
     #[derive(Copy)]
@@ -394,6 +394,18 @@ mod typeobject {
         am_aiter: None,
         am_anext: None,
     };
+}
+pub use self::protocolmethods::*;
+
+#[cfg(not(Py_LIMITED_API))]
+mod typeobject {
+    use ffi::{self, object};
+    use std::os::raw::{c_void, c_char, c_ulong, c_uint};
+    use ffi::pyport::Py_ssize_t;
+    use ffi::object::{PyNumberMethods, PySequenceMethods, PyMappingMethods, PyAsyncMethods};
+
+    macro_rules! as_expr { ($e:expr) => {$e} }
+
     #[repr(C)]
     #[derive(Copy, Debug)]
     pub struct PyBufferProcs {
@@ -706,6 +718,8 @@ pub unsafe fn PyType_CheckExact(op: *mut PyObject) -> c_int {
      -> *mut PyObject;
     pub fn PyObject_GenericSetAttr(arg1: *mut PyObject, arg2: *mut PyObject,
                                    arg3: *mut PyObject) -> c_int;
+    pub fn PyObject_GenericGetDict(arg1: *mut PyObject, arg2: *mut c_void)
+     -> *mut PyObject;
     pub fn PyObject_GenericSetDict(arg1: *mut PyObject, arg2: *mut PyObject,
                                    arg3: *mut c_void)
      -> c_int;
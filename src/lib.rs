@@ -182,6 +182,8 @@ pub mod buffer;
 pub mod class;
 pub use class::*;
 pub mod callback;
+#[doc(hidden)]
+pub mod derive;
 
 // re-export for simplicity
 pub use std::os::raw::*;
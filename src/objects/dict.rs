@@ -152,11 +152,60 @@ impl <K, V> ToPyObject for collections::BTreeMap<K, V>
     }
 }
 
+/// Conversion of key-value collections into `PyDict`,
+/// i.e. keyword arguments: `obj.call(py, NoArgs, Some(&[("key", 1)][..].into_py_dict(py)))`.
+pub trait IntoPyDict {
+    /// Converts self into a `PyDict` object.
+    ///
+    /// Panics if a key can not be inserted, i.e. when key is not hashable
+    /// or when running out of memory.
+    fn into_py_dict(self, py: Python) -> PyDict;
+}
+
+impl <'a, K, V> IntoPyDict for &'a [(K, V)]
+    where K: ToPyObject,
+          V: ToPyObject
+{
+    fn into_py_dict(self, py: Python) -> PyDict {
+        let dict = PyDict::new(py);
+        for &(ref key, ref value) in self {
+            err::unwrap_or_panic(py, dict.set_item(py, key, value));
+        };
+        dict
+    }
+}
+
+impl <K, V> IntoPyDict for collections::HashMap<K, V>
+    where K: hash::Hash+cmp::Eq+ToPyObject,
+          V: ToPyObject
+{
+    fn into_py_dict(self, py: Python) -> PyDict {
+        let dict = PyDict::new(py);
+        for (key, value) in self {
+            err::unwrap_or_panic(py, dict.set_item(py, key, value));
+        };
+        dict
+    }
+}
+
+impl <K, V> IntoPyDict for collections::BTreeMap<K, V>
+    where K: cmp::Eq+ToPyObject,
+          V: ToPyObject
+{
+    fn into_py_dict(self, py: Python) -> PyDict {
+        let dict = PyDict::new(py);
+        for (key, value) in self {
+            err::unwrap_or_panic(py, dict.set_item(py, key, value));
+        };
+        dict
+    }
+}
+
 #[cfg(test)]
 mod test {
     use python::{Python, PythonObject, PythonObjectWithCheckedDowncast};
     use conversion::ToPyObject;
-    use objects::{PyDict, PyTuple};
+    use objects::{PyDict, PyTuple, IntoPyDict};
     use std::collections::{BTreeMap, HashMap};
 
     #[test]
@@ -310,4 +359,43 @@ mod test {
         assert!(py_map.len(py) == 1);
         assert!( py_map.get_item(py, 1).unwrap().extract::<i32>(py).unwrap() == 1);
     }
+
+    #[test]
+    fn test_slice_into_dict() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let py_map = [("a", 1), ("b", 2)][..].into_py_dict(py);
+
+        assert_eq!(py_map.len(py), 2);
+        assert_eq!(py_map.get_item(py, "b").unwrap().extract::<i32>(py).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_hashmap_into_dict() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let mut map = HashMap::<i32, i32>::new();
+        map.insert(1, 1);
+
+        let py_map = map.into_py_dict(py);
+
+        assert_eq!(py_map.len(py), 1);
+        assert_eq!(py_map.get_item(py, 1).unwrap().extract::<i32>(py).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_btreemap_into_dict() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        let mut map = BTreeMap::<i32, i32>::new();
+        map.insert(1, 1);
+
+        let py_map = map.into_py_dict(py);
+
+        assert_eq!(py_map.len(py), 1);
+        assert_eq!(py_map.get_item(py, 1).unwrap().extract::<i32>(py).unwrap(), 1);
+    }
 }
@@ -24,11 +24,11 @@ pub use self::iterator::PyIterator;
 pub use self::boolobject::PyBool;
 pub use self::bytearray::PyByteArray;
 pub use self::tuple::{PyTuple, NoArgs};
-pub use self::dict::PyDict;
+pub use self::dict::{PyDict, IntoPyDict};
 pub use self::list::PyList;
 pub use self::num::{PyLong, PyFloat};
 pub use self::sequence::PySequence;
-pub use self::slice::PySlice;
+pub use self::slice::{PySlice, SliceOrIndex};
 
 #[macro_export]
 macro_rules! pyobject_newtype(
@@ -25,6 +25,7 @@ use conversion::{ToPyObject, ToPyTuple};
 use objects::{PyObject, PyDict, exc};
 //use class::PyTypeObject;
 use err::{self, PyResult, PyErr};
+use class::PyTypeObject;
 use std::ffi::{CStr, CString};
 
 /// Represents a Python module object.
@@ -111,10 +112,9 @@ impl PyModule {
     /// sets `new_type.__module__` to this module's name,
     /// and adds the type to this module.
     pub fn add_class<'p, T>(&self, py: Python<'p>) -> PyResult<()>
-    //    where T: PyTypeObject
+        where T: PyTypeObject
     {
-        unimplemented!()
-    //    T::add_to_module(py, self)
+        T::add_to_module(py, self)
     }
 }
 
@@ -1,11 +1,11 @@
 // Copyright (c) 2017-present PyO3 Project and Contributors
 
-use std::os::raw::c_long;
 use objects::PyObject;
 use python::{Python, PythonObject};
 use err::{self, PyErr, PyResult};
 use ffi::{self, Py_ssize_t};
 use conversion::ToPyObject;
+use objects::exc;
 
 /// Represents a Python `slice` indices
 pub struct PySliceIndices {
@@ -46,7 +46,7 @@ impl PySlice {
 
     /// Retrieve the start, stop, and step indices from the slice object slice assuming a sequence of length length, and store the length of the slice in slicelength.
     #[inline]
-    pub fn indices(&self, py: Python, length: c_long) -> PyResult<PySliceIndices> {
+    pub fn indices(&self, py: Python, length: Py_ssize_t) -> PyResult<PySliceIndices> {
         // non-negative Py_ssize_t should always fit into Rust usize
         unsafe {
             let slicelen: isize = 0;
@@ -54,7 +54,7 @@ impl PySlice {
             let stop: isize = 0;
             let step: isize = 0;
             let r = ffi::PySlice_GetIndicesEx(
-                self.0.as_ptr(), length as Py_ssize_t,
+                self.0.as_ptr(), length,
                 &start as *const _ as *mut _,
                 &stop as *const _ as *mut _,
                 &step as *const _ as *mut _,
@@ -73,6 +73,48 @@ impl PySlice {
     }
 }
 
+/// Key of sequence `__getitem__` and `__setitem__`, python index or slice.
+/// Negative index and slice are resolved against length of the sequence.
+pub enum SliceOrIndex {
+    /// Non-negative index, the sequence raises `IndexError`
+    /// if it is not less than its length
+    Index(usize),
+    Slice(PySliceIndices),
+}
+
+impl SliceOrIndex {
+    /// Extract index or slice from `key`, negative index counts from the end of the sequence.
+    /// `length` is called only for negative index and slice.
+    /// Raises `IndexError` if negative index is out of range or index does not fit `isize`.
+    pub fn extract<F>(py: Python, key: &PyObject, length: F) -> PyResult<SliceOrIndex>
+        where F: FnOnce() -> PyResult<isize>
+    {
+        if let Ok(slice) = key.cast_as::<PySlice>(py) {
+            return slice.indices(py, length()?).map(SliceOrIndex::Slice)
+        }
+        let index = unsafe { ffi::PyNumber_AsSsize_t(key.as_ptr(), ffi::PyExc_IndexError) };
+        if index == -1 && PyErr::occurred(py) {
+            return Err(PyErr::fetch(py))
+        }
+        if index < 0 {
+            SliceOrIndex::index(py, index + length()?)
+        } else {
+            SliceOrIndex::index(py, index)
+        }
+    }
+
+    /// Index of `sq_item` and `sq_ass_item` slots, python has already added length
+    /// to negative index, so index that is still negative is out of range.
+    /// Raises `IndexError` for negative index.
+    pub fn index(py: Python, index: isize) -> PyResult<SliceOrIndex> {
+        if index >= 0 {
+            Ok(SliceOrIndex::Index(index as usize))
+        } else {
+            Err(PyErr::new::<exc::IndexError, _>(py, "index out of range"))
+        }
+    }
+}
+
 impl ToPyObject for PySliceIndices {
 
     fn to_py_object(&self, py: Python) -> PyObject {
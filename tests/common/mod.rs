@@ -0,0 +1,28 @@
+//! Macros shared by integration tests, `#[macro_use] mod common;`
+#![allow(unused_macros)]
+
+/// Runs `$code` with `$val` as local variable.
+macro_rules! py_run {
+    ($py:expr, $val:ident, $code:expr) => {{
+        let d = PyDict::new($py);
+        d.set_item($py, stringify!($val), &$val).unwrap();
+        $py.run($code, None, Some(&d)).expect($code);
+    }}
+}
+
+macro_rules! py_assert {
+    ($py:expr, $val:ident, $assertion:expr) => { py_run!($py, $val, concat!("assert ", $assertion)) };
+}
+
+/// Runs `$code` and checks that it raises `exc::$err`.
+macro_rules! py_expect_exception {
+    ($py:expr, $val:ident, $code:expr, $err:ident) => {{
+        let d = PyDict::new($py);
+        d.set_item($py, stringify!($val), &$val).unwrap();
+        let res = $py.run($code, None, Some(&d));
+        let err = res.unwrap_err();
+        if !err.matches($py, $py.get_type::<exc::$err>()) {
+            panic!(format!("Expected {} but got {:?}", stringify!($err), err))
+        }
+    }}
+}
@@ -0,0 +1,31 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Args {}
+
+#[methods]
+//~^ ERROR can not parse default value
+//~| ERROR #[args] refers to unknown argument: `missing`
+//~| ERROR arguments can not follow keyword arguments (**) in #[args]
+//~| ERROR positional-only separator (/) has to be defined before var arguments (*) in #[args]
+impl Args {
+    #[args(a = "1 +", missing = "2")]
+    fn default(&self, py: Python, a: i32) -> PyResult<()> {
+        Ok(())
+    }
+
+    #[args(kwargs = "**", a = "1")]
+    fn kwargs(&self, py: Python, a: i32, kwargs: Option<&PyDict>) -> PyResult<()> {
+        Ok(())
+    }
+
+    #[args("*", "/")]
+    fn separators(&self, py: Python, a: i32) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Base {}
+
+#[class(base=Base)] //~ ERROR PyBaseType` is not satisfied
+struct Derived {}
+
+fn main() {}
@@ -0,0 +1,32 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class] //~ ERROR #[class] can only be used with normal structs and enums: `Tuple`
+struct Tuple(i32);
+
+#[class] //~ ERROR #[class] `Generic` can not be generic
+struct Generic<T> {
+    data: T,
+}
+
+#[class(unknown = 1)] //~ ERROR unsupported #[class] parameter: `unknown = 1`
+struct Unknown {}
+
+#[class(freelist = many)] //~ ERROR #[class] freelist parameter requires integer capacity: `freelist = many`
+struct FreeListCapacity {}
+
+#[class(freelist = 10, base = Unknown)] //~ ERROR #[class(freelist)] can not be used together with `base`
+struct FreeListBase {}
+
+#[class(freelist = 10, gc)] //~ ERROR #[class(freelist)] can not be used together with `gc`
+struct FreeListGC {}
+
+#[class] //~ ERROR #[class] can not be used with empty enums: `Empty`
+enum Empty {}
+
+#[class(subclass)] //~ ERROR #[class] parameters are not supported for enums: `Flags`
+enum Flags { A }
+
+fn main() {}
@@ -0,0 +1,23 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct ClassAttr {}
+
+#[methods] //~ ERROR #[classattr] can not have self argument: `& self`
+//~| ERROR #[classattr] can only have `py: Python` argument: `with_args`
+impl ClassAttr {
+    #[classattr]
+    fn with_self(&self) -> i32 {
+        1
+    }
+
+    #[classattr]
+    fn with_args(py: Python, arg: i32) -> i32 {
+        arg
+    }
+}
+
+fn main() {}
@@ -0,0 +1,32 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class] //~ ERROR property `num` is defined multiple times: `other`
+struct Props {
+    #[prop(get)]
+    num: i32,
+    #[prop(get, set, name = "num")]
+    other: i32,
+}
+
+#[methods] //~ ERROR getter of property `value` is defined multiple times: `value`
+impl Props {
+    #[getter]
+    fn get_value(&self, py: Python) -> PyResult<i32> {
+        Ok(1)
+    }
+
+    #[getter]
+    fn value(&self, py: Python) -> PyResult<i32> {
+        Ok(2)
+    }
+
+    #[setter(value)]
+    fn set(&self, py: Python, value: i32) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {}
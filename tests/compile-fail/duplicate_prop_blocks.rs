@@ -0,0 +1,33 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class] //~ ERROR duplicate definitions with name `__pyo3_getter_num`
+struct Props {
+    #[prop(get, set)]
+    num: i32,
+}
+
+#[methods] //~ ERROR duplicate definitions with name `__pyo3_getter_value`
+impl Props {
+    #[getter]
+    fn get_num(&self, py: Python) -> PyResult<i32> {
+        Ok(1)
+    }
+
+    #[getter]
+    fn value(&self, py: Python) -> PyResult<i32> {
+        Ok(2)
+    }
+}
+
+#[methods(block = 1)]
+impl Props {
+    #[getter(value)]
+    fn other_value(&self, py: Python) -> PyResult<i32> {
+        Ok(3)
+    }
+}
+
+fn main() {}
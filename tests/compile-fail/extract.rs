@@ -0,0 +1,29 @@
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+use pyo3::*;
+
+#[derive(FromPyObject)] //~ ERROR #[derive(FromPyObject)] can not be used with unit structs: `Unit`
+struct Unit;
+
+#[derive(FromPyObject)] //~ ERROR #[derive(FromPyObject)] `Generic` can not be generic
+struct Generic<T> {
+    data: T,
+}
+
+#[derive(FromPyObject)] //~ ERROR unsupported #[extract] parameter: `key = "a"`
+struct UnknownParam {
+    #[extract(key = "a")]
+    a: i32,
+}
+
+#[derive(FromPyObject)] //~ ERROR #[extract] can only be used with named fields
+struct TupleItem(#[extract(item)] i32, i32);
+
+#[derive(FromPyObject)] //~ ERROR #[derive(FromPyObject)] can not be used with unit variants: `B`
+enum UnitVariant {
+    A(i32),
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Generic {}
+
+#[methods] //~ ERROR python method can not be generic: `method`
+impl Generic {
+    fn method<T>(&self, py: Python, arg: T) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {}
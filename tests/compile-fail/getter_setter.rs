@@ -0,0 +1,40 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Properties {
+    num: i32,
+}
+
+#[methods]
+//~^ ERROR setter/getter attribute requires name or str value
+//~| ERROR setter/getter requires one value
+//~| ERROR setter/getter attribute can not be used mutiple times
+//~| ERROR attribute can not be combined with other attributes
+impl Properties {
+    #[getter(1)]
+    fn get_num(&self, py: Python) -> PyResult<i32> {
+        Ok(*self.num(py)?)
+    }
+
+    #[setter(num, other)]
+    fn set_num(&self, py: Python, value: i32) -> PyResult<()> {
+        Ok(())
+    }
+
+    #[getter]
+    #[setter]
+    fn num2(&self, py: Python) -> PyResult<i32> {
+        Ok(*self.num(py)?)
+    }
+
+    #[staticmethod]
+    #[classmethod]
+    fn method(py: Python) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {}
@@ -0,0 +1,23 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Blocks {}
+
+#[methods(block = 16)] //~ ERROR #[methods] block index has to be less than 16: `block = 16`
+impl Blocks {
+    fn first(&self, py: Python) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+#[methods(other)] //~ ERROR unsupported #[methods] parameter: `other`
+impl Blocks {
+    fn second(&self, py: Python) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class(pickle)] //~ ERROR PyPickleProtocol` is not satisfied
+struct NoState {}
+
+fn main() {}
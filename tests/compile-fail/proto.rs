@@ -0,0 +1,71 @@
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Reference {}
+
+#[proto] //~ ERROR #[proto] argument of `__getitem__` can not be a reference, use owned type: `& str`
+//~| ERROR method `__getitem__` has an incompatible type for trait
+impl PyMappingProtocol for Reference {
+    fn __getitem__(&self, py: Python, key: &str) -> PyResult<i32> {
+        Ok(0)
+    }
+}
+
+#[class]
+struct WrongResult {}
+
+#[proto] //~ ERROR #[proto] `__repr__` has to return `PyResult<_>`: `String`
+//~| ERROR method `__repr__` has an incompatible type for trait
+impl PyObjectProtocol for WrongResult {
+    fn __repr__(&self, py: Python) -> String {
+        String::new()
+    }
+}
+
+#[class]
+struct WrongArguments {}
+
+#[proto] //~ ERROR #[proto] `__add__` requires 1 argument(s) after `py: Python`, got 2
+//~| ERROR method `__add__` has 4 parameters but the declaration in trait `__add__` has 3
+impl PyNumberProtocol for WrongArguments {
+    fn __add__(&self, py: Python, a: i32, b: i32) -> PyResult<i32> {
+        Ok(a + b)
+    }
+}
+
+#[class]
+struct NotOptional {}
+
+#[proto] //~ ERROR #[proto] argument of `__setitem__` has to be `Option<_>`: `i32`
+//~| ERROR method `__setitem__` has an incompatible type for trait
+impl PyMappingProtocol for NotOptional {
+    fn __setitem__(&self, py: Python, key: i32, value: i32) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+#[class]
+struct NotOption {}
+
+#[proto] //~ ERROR #[proto] `__next__` has to return `PyResult<Option<_>>`: `PyResult < i32 >`
+//~| ERROR method `__next__` has an incompatible type for trait
+impl PyIterProtocol for NotOption {
+    fn __next__(&mut self, py: Python) -> PyResult<i32> {
+        Ok(0)
+    }
+}
+
+#[class]
+struct NotInteger {}
+
+#[proto] //~ ERROR PyIntValue` is not satisfied
+impl PyNumberProtocol for NotInteger {
+    fn __index__(&self, py: Python) -> PyResult<String> {
+        Ok(String::new())
+    }
+}
+
+fn main() {}
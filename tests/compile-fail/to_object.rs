@@ -0,0 +1,20 @@
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+use pyo3::*;
+
+#[derive(ToPyObject)] //~ ERROR #[derive(ToPyObject)] can only be used with structs with named fields: `Tuple`
+struct Tuple(i32);
+
+#[derive(ToPyObject)] //~ ERROR #[derive(ToPyObject)] `Generic` can not be generic
+struct Generic<T> {
+    data: T,
+}
+
+#[derive(ToPyObject)] //~ ERROR unsupported #[to_py_object] parameter: `list`
+#[to_py_object(list)]
+struct Unknown {
+    a: i32,
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+#![feature(proc_macro, specialization)]
+
+extern crate pyo3;
+use pyo3::*;
+
+#[class]
+struct Arguments {}
+
+#[methods]
+//~^ ERROR unsupported argument pattern: `( a , b )`
+//~| ERROR explicit Self type in a 'qualified path' is not supported
+impl Arguments {
+    fn pattern(&self, py: Python, (a, b): (i32, i32)) -> PyResult<()> {
+        Ok(())
+    }
+
+    fn qualified(&self, py: Python, arg: <Self as Iterator>::Item) -> PyResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {}
@@ -0,0 +1,137 @@
+#![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+#[macro_use] mod common;
+
+use pyo3::*;
+
+
+#[class]
+struct Number {
+    #[prop(get)]
+    num: i32,
+}
+
+#[proto]
+impl PyNumberProtocol for Number {
+    fn __sub__(&self, py: Python, other: i32) -> PyResult<i32> {
+        Ok(*self.num(py)? - other)
+    }
+
+    fn __rsub__(&self, py: Python, other: i32) -> PyResult<i32> {
+        Ok(other - *self.num(py)?)
+    }
+
+    fn __rpow__(&self, py: Python, other: i32) -> PyResult<i32> {
+        Ok(other.pow(*self.num(py)? as u32))
+    }
+
+    fn __iadd__(&mut self, py: Python, other: i32) -> PyResult<()> {
+        *self.num_mut(py)? += other;
+        Ok(())
+    }
+}
+
+#[test]
+fn binary_and_reflected() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Number::create_instance(py, 10).unwrap();
+
+    py_assert!(py, obj, "obj - 1 == 9");
+    py_assert!(py, obj, "1 - obj == -9");
+    py_assert!(py, obj, "2 ** obj == 1024");
+    py_expect_exception!(py, obj, "obj - 'str'", TypeError);
+    py_expect_exception!(py, obj, "'str' - obj", TypeError);
+    // reflected operation is not used for operands of the same type
+    py_expect_exception!(py, obj, "obj - obj", TypeError);
+    py_expect_exception!(py, obj, "obj ** 2", TypeError);
+    py_expect_exception!(py, obj, "pow(2, obj, 5)", TypeError);
+    // only type mismatch falls back to the other operand
+    py_expect_exception!(py, obj, "obj - 2 ** 40", OverflowError);
+    py_expect_exception!(py, obj, "2 ** 40 - obj", OverflowError);
+}
+
+#[test]
+fn inplace() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Number::create_instance(py, 10).unwrap();
+
+    py_run!(py, obj, "orig = obj\nobj += 5\nassert obj is orig\nassert obj.num == 15");
+    assert_eq!(*obj.num(py).unwrap(), 15);
+    py_expect_exception!(py, obj, "obj += 'str'", TypeError);
+    py_expect_exception!(py, obj, "obj += 2 ** 40", OverflowError);
+    // without `__isub__` python falls back to `__sub__`
+    py_run!(py, obj, "obj -= 5\nassert obj == 10");
+}
+
+
+#[class]
+struct Reflected {}
+
+#[proto]
+impl PyNumberProtocol for Reflected {
+    fn __rmul__(&self, py: Python, other: String) -> PyResult<String> {
+        Ok(format!("{} * Reflected", other))
+    }
+}
+
+#[test]
+fn reflected_only() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Reflected::create_instance(py).unwrap();
+
+    py_assert!(py, obj, "'a' * obj == 'a * Reflected'");
+    py_expect_exception!(py, obj, "obj * 'a'", TypeError);
+    py_assert!(py, obj, "hasattr(obj, '__rmul__')");
+}
+
+
+#[class]
+struct Fixed {
+    // value in hundredths
+    value: i64,
+}
+
+#[proto]
+impl PyNumberProtocol for Fixed {
+    fn __index__(&self, py: Python) -> PyResult<i64> {
+        Ok(*self.value(py)? / 100)
+    }
+
+    fn __int__(&self, py: Python) -> PyResult<i64> {
+        Ok(*self.value(py)? / 100)
+    }
+
+    fn __float__(&self, py: Python) -> PyResult<f64> {
+        Ok(*self.value(py)? as f64 / 100.0)
+    }
+
+    fn __complex__(&self, py: Python) -> PyResult<(f64, f64)> {
+        Ok((*self.value(py)? as f64 / 100.0, 0.0))
+    }
+
+    fn __round__(&self, py: Python, ndigits: Option<isize>) -> PyResult<f64> {
+        let scale = 10f64.powi(ndigits.unwrap_or(0) as i32);
+        Ok((*self.value(py)? as f64 / 100.0 * scale).round() / scale)
+    }
+}
+
+#[test]
+fn conversions() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Fixed::create_instance(py, 256).unwrap();
+
+    py_assert!(py, obj, "[0, 1, 2, 3][obj] == 2");
+    py_assert!(py, obj, "__import__('operator').index(obj) == 2");
+    py_assert!(py, obj, "int(obj) == 2");
+    py_assert!(py, obj, "float(obj) == 2.56");
+    py_assert!(py, obj, "complex(obj) == 2.56 + 0j");
+    py_assert!(py, obj, "round(obj) == 3.0");
+    py_assert!(py, obj, "round(obj, 1) == 2.6");
+}
@@ -3,76 +3,160 @@
 
 #[macro_use] extern crate pyo3;
 
-use std::ptr;
-use std::os::raw::{c_int, c_void};
-
-use pyo3::*;
-use pyo3::cls;
+#[macro_use] mod common;
 
+use std::mem;
 
-py_class!(class TestClass |py| {
-    data vec: Vec<u8>;
-});
+use pyo3::*;
 
-#[cls::py_impl]
-impl class::PyBufferProtocol for TestClass {
-    fn bf_getbuffer(&self, py: Python, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
 
-        if view == ptr::null_mut() {
-            return Err(PyErr::new::<exc::BufferError, _>(py, "View is null"))
-        }
+#[class]
+struct TestClass {
+    vec: Vec<u8>,
+}
 
-        unsafe {
-            (*view).obj = ptr::null_mut();
-        }
+#[proto]
+impl PyBufferProtocol for TestClass {
+    type Element = u8;
 
-        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
-            return Err(PyErr::new::<exc::BufferError, _>(py, "Object is not writable"))
-        }
+    fn bf_getbuffer<'a>(&'a self, py: Python<'a>, writable: bool) -> PyResult<PyBufferView<'a, u8>> {
+        Ok(PyBufferView::new(self.vec(py)?))
+    }
+}
 
-        let bytes = self.vec(py);
+#[test]
+fn test_buffer() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
 
-        unsafe {
-            (*view).buf = bytes.as_ptr() as *mut c_void;
-            (*view).len = bytes.len() as isize;
-            (*view).readonly = 1;
-            (*view).itemsize = 1;
+    let ob = TestClass::create_instance(py, vec![b' ', b'2', b'3']).unwrap();
 
-            (*view).format = ptr::null_mut();
-            if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
-                let msg = ::std::ffi::CStr::from_ptr("B\0".as_ptr() as *const _);
-                (*view).format = msg.as_ptr() as *mut _;
-            }
+    py_assert!(py, ob, "bytes(ob) == b' 23'");
+    py_assert!(py, ob, "memoryview(ob).readonly");
+    py_expect_exception!(py, ob, "memoryview(ob)[0] = 1", TypeError);
+    // read-only exports share the data
+    py_run!(py, ob, "view = memoryview(ob)\n\
+                     assert bytes(memoryview(ob)) == bytes(view)\n\
+                     view.release()");
+}
 
-            (*view).ndim = 1;
-            (*view).shape = ptr::null_mut();
-            if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
-                (*view).shape = (&((*view).len)) as *const _ as *mut _;
-            }
 
-            (*view).strides = ptr::null_mut();
-            if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
-                (*view).strides = &((*view).itemsize) as *const _ as *mut _;
-            }
+#[class]
+struct Matrix {
+    data: Vec<f64>,
+}
 
-            (*view).suboffsets = ptr::null_mut();
-            (*view).internal = ptr::null_mut();
-        }
+#[proto]
+impl PyBufferProtocol for Matrix {
+    type Element = f64;
+
+    fn bf_getbuffer<'a>(&'a self, py: Python<'a>, writable: bool) -> PyResult<PyBufferView<'a, f64>> {
+        let view = if writable {
+            PyBufferView::new_mut(self.data_mut(py)?)
+        } else {
+            PyBufferView::new(self.data(py)?)
+        };
+        view.reshape(py, &[2, 3])
+    }
+}
 
+#[methods]
+impl Matrix {
+    fn push(&mut self, py: Python, value: f64) -> PyResult<()> {
+        self.data_mut(py)?.push(value);
         Ok(())
     }
+
+    fn size(&self, py: Python) -> PyResult<usize> {
+        Ok(self.data(py)?.len())
+    }
 }
 
+#[test]
+fn test_buffer_shape() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let m = Matrix::create_instance(py, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+
+    py_run!(py, m, "view = memoryview(m)\n\
+                    assert view.format == 'd' and view.itemsize == 8\n\
+                    assert view.shape == (2, 3) and view.strides == (24, 8)\n\
+                    assert view.tolist() == [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]");
+
+    // memory is writable only on request
+    py_run!(py, m, "view = memoryview(m)\n\
+                    assert view.readonly\n\
+                    view.release()\n\
+                    import io, struct\n\
+                    io.BytesIO(struct.pack('6d', 1, 2, 3, 4, 42, 6)).readinto(m)\n\
+                    assert memoryview(m).tolist()[1][1] == 42.0");
+    assert_eq!(m.data(py).unwrap()[4], 42.0);
+}
 
 #[test]
-fn test_buffer() {
+fn test_buffer_exports() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let m = Matrix::create_instance(py, vec![0.0; 6]).unwrap();
+
+    py_run!(py, m, "view = memoryview(m)\n\
+                    try:\n    m.push(1.0)\n    assert False\n\
+                    except BufferError:\n    pass\n\
+                    view.release()\n\
+                    m.push(1.0)");
+    assert_eq!(m.data(py).unwrap().len(), 7);
+
+    // read-only export does not block shared borrows
+    py_run!(py, m, "view = memoryview(m)\n\
+                    assert m.size() == 7\n\
+                    assert bytes(memoryview(m)) == bytes(view)\n\
+                    view.release()");
+
+    // writable export blocks shared borrows too
+    let mut buf: ffi::Py_buffer = unsafe { mem::zeroed() };
+    assert_eq!(unsafe {
+        ffi::PyObject_GetBuffer(m.as_object().as_ptr(), &mut buf, ffi::PyBUF_FULL)
+    }, 0);
+    assert_eq!(buf.readonly, 0);
+    py_expect_exception!(py, m, "m.size()", BufferError);
+    let err = m.data(py).err().unwrap();
+    assert!(err.matches(py, py.get_type::<exc::BufferError>()));
+    unsafe { ffi::PyBuffer_Release(&mut buf) };
+    py_assert!(py, m, "m.size() == 7");
+}
+
+
+#[class]
+struct Transposed {
+    data: Vec<i32>,
+}
+
+#[proto]
+impl PyBufferProtocol for Transposed {
+    type Element = i32;
+
+    fn bf_getbuffer<'a>(&'a self, py: Python<'a>, writable: bool) -> PyResult<PyBufferView<'a, i32>> {
+        PyBufferView::new(self.data(py)?).with_strides(py, &[3, 2], &[1, 3])
+    }
+}
+
+#[test]
+fn test_buffer_strides() {
     let gil = Python::acquire_gil();
     let py = gil.python();
 
-    let t = TestClass::create_instance(py, vec![b' ', b'2', b'3']).unwrap();
+    let t = Transposed::create_instance(py, vec![1, 2, 3, 4, 5, 6]).unwrap();
 
-    let d = PyDict::new(py);
-    let _ = d.set_item(py, "ob", t);
+    py_assert!(py, t, "memoryview(t).tolist() == [[1, 4], [2, 5], [3, 6]]");
+    py_assert!(py, t, "memoryview(t).f_contiguous and not memoryview(t).c_contiguous");
+    // bytes() requests C-contiguous buffer
+    py_assert!(py, t, "bytes(t) == bytes(memoryview(t).tobytes())");
 
-    py.run("assert bytes(ob) == b' 23'", None, Some(&d)).unwrap();
+    let view = PyBufferView::<i32>::new(t.data(py).unwrap());
+    let err = view.with_strides(py, &[2, 2], &[1, 6]).err().unwrap();
+    assert!(err.matches(py, py.get_type::<exc::BufferError>()));
+    let err = PyBufferView::<i32>::new(t.data(py).unwrap()).reshape(py, &[3, 3]).err().unwrap();
+    assert!(err.matches(py, py.get_type::<exc::BufferError>()));
 }
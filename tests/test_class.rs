@@ -1,38 +1,18 @@
 #![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
 
 #[macro_use] extern crate pyo3;
 
+#[macro_use] mod common;
+
 use pyo3::*;
-use std::{mem, isize, iter};
+use std::{isize, iter};
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use pyo3::ffi;
 
-macro_rules! py_run {
-    ($py:expr, $val:ident, $code:expr) => {{
-        let d = PyDict::new($py);
-        d.set_item($py, stringify!($val), &$val).unwrap();
-        $py.run($code, None, Some(&d)).expect($code);
-    }}
-}
-
-macro_rules! py_assert {
-    ($py:expr, $val:ident, $assertion:expr) => { py_run!($py, $val, concat!("assert ", $assertion)) };
-}
-
-macro_rules! py_expect_exception {
-    ($py:expr, $val:ident, $code:expr, $err:ident) => {{
-        let d = PyDict::new($py);
-        d.set_item($py, stringify!($val), &$val).unwrap();
-        let res = $py.run($code, None, Some(&d));
-        let err = res.unwrap_err();
-        if !err.matches($py, $py.get_type::<exc::$err>()) {
-            panic!(format!("Expected {} but got {:?}", stringify!($err), err))
-        }
-    }}
-}
-
 
 py_class!(class EmptyClass |py| { });
 
@@ -61,11 +41,16 @@ fn empty_class_in_module() {
     assert_eq!(ty.getattr(py, "__module__").unwrap().extract::<String>(py).unwrap(), "test_module.nested");
 }
 
-py_class!(class EmptyClassWithNew |py| {
-    def __new__(_cls) -> PyResult<EmptyClassWithNew> {
+#[class]
+struct EmptyClassWithNew { }
+
+#[methods]
+impl EmptyClassWithNew {
+    #[new]
+    fn __new__(cls: &PyType, py: Python) -> PyResult<EmptyClassWithNew> {
         EmptyClassWithNew::create_instance(py)
     }
-});
+}
 
 #[test]
 fn empty_class_with_new() {
@@ -75,12 +60,18 @@ fn empty_class_with_new() {
     assert!(typeobj.call(py, NoArgs, None).unwrap().cast_into::<EmptyClassWithNew>(py).is_ok());
 }
 
-py_class!(class NewWithOneArg |py| {
-    data _data: i32;
-    def __new__(_cls, arg: i32) -> PyResult<NewWithOneArg> {
+#[class]
+struct NewWithOneArg {
+    _data: i32,
+}
+
+#[methods]
+impl NewWithOneArg {
+    #[new]
+    fn __new__(cls: &PyType, py: Python, arg: i32) -> PyResult<NewWithOneArg> {
         NewWithOneArg::create_instance(py, arg)
     }
-});
+}
 
 #[test]
 fn new_with_one_arg() {
@@ -88,16 +79,22 @@ fn new_with_one_arg() {
     let py = gil.python();
     let typeobj = py.get_type::<NewWithOneArg>();
     let obj = typeobj.call(py, (42,), None).unwrap().cast_into::<NewWithOneArg>(py).unwrap();
-    assert_eq!(*obj._data(py), 42);
+    assert_eq!(*obj._data(py).unwrap(), 42);
 }
 
-py_class!(class NewWithTwoArgs |py| {
-    data _data1: i32;
-    data _data2: i32;
-    def __new__(_cls, arg1: i32, arg2: i32) -> PyResult<NewWithTwoArgs> {
+#[class]
+struct NewWithTwoArgs {
+    _data1: i32,
+    _data2: i32,
+}
+
+#[methods]
+impl NewWithTwoArgs {
+    #[new]
+    fn __new__(cls: &PyType, py: Python, arg1: i32, arg2: i32) -> PyResult<NewWithTwoArgs> {
         NewWithTwoArgs::create_instance(py, arg1, arg2)
     }
-});
+}
 
 #[test]
 fn new_with_two_args() {
@@ -105,8 +102,8 @@ fn new_with_two_args() {
     let py = gil.python();
     let typeobj = py.get_type::<NewWithTwoArgs>();
     let obj = typeobj.call(py, (10, 20), None).unwrap().cast_into::<NewWithTwoArgs>(py).unwrap();
-    assert_eq!(*obj._data1(py), 10);
-    assert_eq!(*obj._data2(py), 20);
+    assert_eq!(*obj._data1(py).unwrap(), 10);
+    assert_eq!(*obj._data2(py).unwrap(), 20);
 }
 
 struct TestDropCall {
@@ -181,16 +178,21 @@ fn instance_method_with_args() {
     py.run("assert obj.method(multiplier=6) == 42", None, Some(&d)).unwrap();
 }
 
-py_class!(class ClassMethod |py| {
-    def __new__(cls) -> PyResult<ClassMethod> {
+#[class]
+struct ClassMethod {}
+
+#[methods]
+impl ClassMethod {
+    #[new]
+    fn __new__(cls: &PyType, py: Python) -> PyResult<ClassMethod> {
         ClassMethod::create_instance(py)
     }
 
-    @classmethod
-    def method(cls) -> PyResult<String> {
+    #[classmethod]
+    fn method(cls: &PyType, py: Python) -> PyResult<String> {
         Ok(format!("{}.method()!", cls.name(py)))
     }
-});
+}
 
 #[test]
 fn class_method() {
@@ -203,12 +205,16 @@ fn class_method() {
     py.run("assert C().method() == 'ClassMethod.method()!'", None, Some(&d)).unwrap();
 }
 
-py_class!(class ClassMethodWithArgs |py| {
-    @classmethod
-    def method(cls, input: &str) -> PyResult<String> {
+#[class]
+struct ClassMethodWithArgs {}
+
+#[methods]
+impl ClassMethodWithArgs {
+    #[classmethod]
+    fn method(cls: &PyType, py: Python, input: String) -> PyResult<String> {
         Ok(format!("{}.method({})", cls.name(py), input))
     }
-});
+}
 
 #[test]
 fn class_method_with_args() {
@@ -220,16 +226,21 @@ fn class_method_with_args() {
     py.run("assert C.method('abc') == 'ClassMethodWithArgs.method(abc)'", None, Some(&d)).unwrap();
 }
 
-py_class!(class StaticMethod |py| {
-    def __new__(cls) -> PyResult<StaticMethod> {
+#[class]
+struct StaticMethod {}
+
+#[methods]
+impl StaticMethod {
+    #[new]
+    fn __new__(cls: &PyType, py: Python) -> PyResult<StaticMethod> {
         StaticMethod::create_instance(py)
     }
 
-    @staticmethod
-    def method() -> PyResult<&'static str> {
+    #[staticmethod]
+    fn method(py: Python) -> PyResult<&'static str> {
         Ok("StaticMethod.method()!")
     }
-});
+}
 
 #[test]
 fn static_method() {
@@ -243,12 +254,16 @@ fn static_method() {
     py.run("assert C().method() == 'StaticMethod.method()!'", None, Some(&d)).unwrap();
 }
 
-py_class!(class StaticMethodWithArgs |py| {
-    @staticmethod
-    def method(input: i32) -> PyResult<String> {
+#[class]
+struct StaticMethodWithArgs {}
+
+#[methods]
+impl StaticMethodWithArgs {
+    #[staticmethod]
+    fn method(py: Python, input: i32) -> PyResult<String> {
         Ok(format!("0x{:x}", input))
     }
-});
+}
 
 #[test]
 fn static_method_with_args() {
@@ -261,6 +276,419 @@ fn static_method_with_args() {
     py.run("assert C.method(1337) == '0x539'", None, Some(&d)).unwrap();
 }
 
+#[class]
+struct MethArgs {}
+
+#[methods]
+impl MethArgs {
+    #[staticmethod]
+    #[args(step = "1", "*", verbose = "false", rest = "*", opts = "**")]
+    fn range(py: Python, stop: i32, step: i32, verbose: bool,
+             rest: &PyTuple, opts: Option<&PyDict>) -> PyResult<String> {
+        Ok(format!("{} {} {} {} {}", stop, step, verbose,
+                   rest.len(py), opts.map_or(0, |d| d.len(py))))
+    }
+
+    #[staticmethod]
+    #[args(a, "/", b = "None")]
+    fn pos_only(py: Python, a: i32, b: Option<i32>) -> PyResult<i32> {
+        Ok(a + b.unwrap_or(0))
+    }
+
+    #[staticmethod]
+    #[args("*", key)]
+    fn kw_only(py: Python, key: i32) -> PyResult<i32> {
+        Ok(key)
+    }
+}
+
+#[test]
+fn meth_args() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let cls = py.get_type::<MethArgs>();
+
+    py_assert!(py, cls, "cls.range(10) == '10 1 false 0 0'");
+    py_assert!(py, cls, "cls.range(10, 2) == '10 2 false 0 0'");
+    py_assert!(py, cls, "cls.range(10, step=3, verbose=True) == '10 3 true 0 0'");
+    py_assert!(py, cls, "cls.range(10, 2, 3, 4, verbose=True, x=1) == '10 2 true 2 1'");
+    py_expect_exception!(py, cls, "cls.range()", TypeError);
+    py_expect_exception!(py, cls, "cls.range(10, 2, False, stop=1)", TypeError);
+
+    py_assert!(py, cls, "cls.pos_only(1) == 1");
+    py_assert!(py, cls, "cls.pos_only(1, 2) == 3");
+    py_assert!(py, cls, "cls.pos_only(1, b=None) == 1");
+    py_expect_exception!(py, cls, "cls.pos_only(a=1)", TypeError);
+
+    py_assert!(py, cls, "cls.kw_only(key=5) == 5");
+    py_expect_exception!(py, cls, "cls.kw_only(5)", TypeError);
+
+    py_assert!(py, cls, "cls.range.__text_signature__ == \
+                         '(stop, step=1, *rest, verbose=False, **opts)'");
+    py_assert!(py, cls, "cls.pos_only.__text_signature__ == '(a, /, b=None)'");
+    py_assert!(py, cls, "cls.kw_only.__text_signature__ == '(*, key)'");
+}
+
+/// Class docstring
+#[class]
+struct ClassWithDocs {}
+
+#[methods]
+impl ClassWithDocs {
+    /// Property docstring
+    #[getter]
+    fn value(&self, py: Python) -> PyResult<i32> {
+        Ok(0)
+    }
+
+    /// Method docstring
+    ///
+    /// More details
+    fn method(&self, py: Python) -> PyResult<i32> {
+        Ok(0)
+    }
+}
+
+#[test]
+fn class_with_docstr() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let typeobj = py.get_type::<ClassWithDocs>();
+
+    py_assert!(py, typeobj, "typeobj.__doc__ == 'Class docstring'");
+    py_assert!(py, typeobj, "typeobj.value.__doc__ == 'Property docstring'");
+    py_assert!(py, typeobj, "typeobj.method.__doc__ == 'Method docstring\\n\\nMore details'");
+    py_assert!(py, typeobj, "typeobj.method.__text_signature__ == '($self)'");
+}
+
+#[class]
+struct FieldProps {
+    /// Number docstring
+    #[prop(get, set)]
+    num: i32,
+    #[prop(get, name = "text")]
+    data: String,
+    hidden: i32,
+}
+
+#[test]
+fn field_props() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = FieldProps::create_instance(py, 10, "data".to_owned(), 0).unwrap();
+
+    py_assert!(py, obj, "obj.num == 10");
+    py_run!(py, obj, "obj.num = 20");
+    assert_eq!(*obj.num(py).unwrap(), 20);
+    py_expect_exception!(py, obj, "obj.num = 'str'", TypeError);
+    py_expect_exception!(py, obj, "del obj.num", TypeError);
+
+    py_assert!(py, obj, "obj.text == 'data'");
+    py_expect_exception!(py, obj, "obj.text = 'other'", AttributeError);
+    py_expect_exception!(py, obj, "obj.hidden", AttributeError);
+
+    let typeobj = py.get_type::<FieldProps>();
+    py_assert!(py, typeobj, "typeobj.num.__doc__ == 'Number docstring'");
+}
+
+#[class]
+struct MultipleBlocks {
+    num: i32,
+}
+
+#[methods]
+impl MultipleBlocks {
+    #[new]
+    fn __new__(cls: &PyType, py: Python, num: i32) -> PyResult<MultipleBlocks> {
+        MultipleBlocks::create_instance(py, num)
+    }
+
+    fn method(&self, py: Python) -> PyResult<i32> {
+        Ok(*self.num(py)?)
+    }
+}
+
+#[methods(block = 1)]
+impl MultipleBlocks {
+    #[getter]
+    fn double(&self, py: Python) -> PyResult<i32> {
+        Ok(*self.num(py)? * 2)
+    }
+
+    #[staticmethod]
+    fn create(py: Python) -> PyResult<i32> {
+        Ok(1)
+    }
+}
+
+#[test]
+fn multiple_methods_blocks() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let cls = py.get_type::<MultipleBlocks>();
+
+    py_assert!(py, cls, "cls(5).method() == 5");
+    py_assert!(py, cls, "cls(5).double == 10");
+    py_assert!(py, cls, "cls.create() == 1");
+
+    // class with the same name in other module keeps its own methods
+    let other = other::multiple_blocks_type(py);
+    py_assert!(py, other, "other.other() == 2");
+    py_assert!(py, other, "not hasattr(other, 'create')");
+    py_assert!(py, cls, "not hasattr(cls, 'other')");
+}
+
+mod other {
+    use pyo3::*;
+
+    #[class]
+    struct MultipleBlocks {}
+
+    pub fn multiple_blocks_type(py: Python) -> PyType {
+        py.get_type::<MultipleBlocks>()
+    }
+
+    #[methods]
+    impl MultipleBlocks {
+        #[staticmethod]
+        fn other(py: Python) -> PyResult<i32> {
+            Ok(2)
+        }
+    }
+}
+
+#[class]
+struct ClassAttrs {}
+
+#[methods]
+impl ClassAttrs {
+    #[classattr]
+    const VERSION: u32 = 3;
+
+    #[classattr]
+    fn name() -> &'static str {
+        "class attrs"
+    }
+
+    #[classattr]
+    fn helper(py: Python) -> PyType {
+        py.get_type::<EmptyClass>()
+    }
+}
+
+#[test]
+fn class_attributes() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let cls = py.get_type::<ClassAttrs>();
+
+    py_assert!(py, cls, "cls.VERSION == 3");
+    py_assert!(py, cls, "cls.name == 'class attrs'");
+    py_assert!(py, cls, "cls.helper.__name__ == 'EmptyClass'");
+    py_assert!(py, cls, "cls().VERSION == 3");
+    py_assert!(py, cls, "'VERSION' in cls.__dict__");
+}
+
+#[class]
+struct BorrowChecked {
+    num: i32,
+}
+
+#[methods]
+impl BorrowChecked {
+    fn get(&self, py: Python) -> PyResult<i32> {
+        Ok(*self.num(py)?)
+    }
+
+    fn set(&mut self, py: Python, num: i32) -> PyResult<()> {
+        *self.num_mut(py)? = num;
+        Ok(())
+    }
+
+    fn call(&self, py: Python, callback: PyObject) -> PyResult<PyObject> {
+        callback.call(py, NoArgs, None)
+    }
+
+    fn call_mut(&mut self, py: Python, callback: PyObject) -> PyResult<PyObject> {
+        *self.num_mut(py)? += 1;
+        callback.call(py, NoArgs, None)
+    }
+}
+
+#[proto]
+impl PyObjectProtocol for BorrowChecked {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("BorrowChecked({})", *self.num(py)?))
+    }
+}
+
+#[test]
+fn borrow_checking() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = BorrowChecked::create_instance(py, 1).unwrap();
+
+    py_run!(py, obj, "obj.set(2); assert obj.get() == 2");
+
+    // shared borrows can overlap
+    py_assert!(py, obj, "obj.call(lambda obj=obj: obj.get()) == 2");
+    py_expect_exception!(py, obj, "obj.call(lambda obj=obj: obj.set(3))", RuntimeError);
+    py_expect_exception!(py, obj, "obj.call_mut(lambda obj=obj: obj.get())", RuntimeError);
+    py_expect_exception!(py, obj, "obj.call_mut(lambda obj=obj: obj.set(3))", RuntimeError);
+    // protocol methods can not re-enter `&mut self` method either
+    py_assert!(py, obj, "obj.call(lambda obj=obj: repr(obj)) == 'BorrowChecked(4)'");
+    py_expect_exception!(py, obj, "obj.call_mut(lambda obj=obj: repr(obj))", RuntimeError);
+
+    // borrow is released after the call
+    py_assert!(py, obj, "obj.get() == 5");
+
+    {
+        let data = obj.borrow(py).unwrap();
+        assert_eq!(data.num, 5);
+        assert!(obj.borrow_mut(py).is_err());
+        py_expect_exception!(py, obj, "obj.set(6)", RuntimeError);
+    }
+    obj.borrow_mut(py).unwrap().num = 6;
+    py_assert!(py, obj, "obj.get() == 6");
+
+    // handles of the same object can not alias mutable reference
+    let other = obj.clone_ref(py);
+    {
+        let mut num = obj.num_mut(py).unwrap();
+        *num = 7;
+        assert!(other.num(py).is_err());
+        assert!(other.num_mut(py).is_err());
+    }
+    assert_eq!(*other.num(py).unwrap(), 7);
+}
+
+/// First class
+#[class]
+struct HeapTypeA {}
+
+#[methods]
+impl HeapTypeA {
+    fn a(&self, py: Python) -> PyResult<i32> {
+        Ok(1)
+    }
+}
+
+/// Second class
+#[class]
+struct HeapTypeB {}
+
+#[methods]
+impl HeapTypeB {
+    fn b(&self, py: Python) -> PyResult<i32> {
+        Ok(2)
+    }
+}
+
+#[test]
+fn heap_types() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let a = py.get_type::<HeapTypeA>();
+    let b = py.get_type::<HeapTypeB>();
+
+    py_assert!(py, a, "a.__flags__ & (1 << 9)"); // Py_TPFLAGS_HEAPTYPE
+    py_assert!(py, b, "b.__flags__ & (1 << 9)");
+
+    // every class has its own slots
+    py_assert!(py, a, "a.__doc__ == 'First class'");
+    py_assert!(py, b, "b.__doc__ == 'Second class'");
+    py_assert!(py, a, "hasattr(a, 'a') and not hasattr(a, 'b')");
+    py_assert!(py, b, "hasattr(b, 'b') and not hasattr(b, 'a')");
+
+    // no #[new] method
+    py_expect_exception!(py, a, "a()", TypeError);
+}
+
+#[class(subclass)]
+struct SubclassableClass {
+    value: i32,
+}
+
+#[methods]
+impl SubclassableClass {
+    #[new]
+    fn __new__(cls: &PyType, py: Python, value: i32) -> PyResult<SubclassableClass> {
+        SubclassableClass::create_instance_of(py, cls, value)
+    }
+
+    fn get_value(&self, py: Python) -> PyResult<i32> {
+        Ok(*self.value(py)?)
+    }
+}
+
+#[test]
+fn subclass() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let base = py.get_type::<SubclassableClass>();
+
+    py_run!(py, base, "class Sub(base):\n    def double(self):\n        return self.get_value() * 2\n\ninst = Sub(5)\nassert isinstance(inst, base)\nassert type(inst) is Sub\nassert inst.double() == 10");
+
+    let other = py.get_type::<HeapTypeA>();
+    py_expect_exception!(py, other, "class Sub(other): pass", TypeError);
+}
+
+#[class(dict, weakref)]
+struct DictWeakRefClass {
+    value: i32,
+}
+
+#[test]
+fn instance_dict_and_weakref() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let inst = DictWeakRefClass::create_instance(py, 1).unwrap();
+
+    py_run!(py, inst, "inst.attr = 5\nassert inst.attr == 5\nassert inst.__dict__ == {'attr': 5}");
+    py_run!(py, inst, "import weakref\nref = weakref.ref(inst)\nassert ref() is inst");
+
+    let empty = HeapTypeA::create_instance(py).unwrap();
+    py_expect_exception!(py, empty, "empty.attr = 5", AttributeError);
+    py_expect_exception!(py, empty, "import weakref; weakref.ref(empty)", TypeError);
+}
+
+#[test]
+fn weakref_cleared_on_drop() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let d = PyDict::new(py);
+    d.set_item(py, "C", py.get_type::<DictWeakRefClass>()).unwrap();
+    let inst = DictWeakRefClass::create_instance(py, 1).unwrap();
+    d.set_item(py, "inst", inst).unwrap();
+
+    py.run("import weakref\nref = weakref.ref(inst)\ninst.attr = [1]\ndel inst\nassert ref() is None",
+           None, Some(&d)).unwrap();
+}
+
+#[class(freelist = 2)]
+struct FreeListClass {
+    drop_called: TestDropCall,
+}
+
+#[test]
+fn freelist() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let drop_called = Arc::new(AtomicBool::new(false));
+
+    let inst = FreeListClass::create_instance(
+        py, TestDropCall { drop_called: drop_called.clone() }).unwrap();
+    let ptr = inst.as_object().as_ptr();
+    inst.release_ref(py);
+    assert!(drop_called.load(Ordering::Relaxed));
+
+    // memory of the dropped object is reused
+    let inst = FreeListClass::create_instance(
+        py, TestDropCall { drop_called: Arc::new(AtomicBool::new(false)) }).unwrap();
+    assert_eq!(inst.as_object().as_ptr(), ptr);
+    inst.release_ref(py);
+}
+
 py_class!(class StaticData |py| {
     static VAL1 = 123;
     static VAL2 = py.None();
@@ -278,20 +706,11 @@ fn static_data() {
     assert!(py.run("C.VAL1 = 124", None, Some(&d)).is_err());
 }
 
-py_class!(class GCIntegration |py| {
-    data self_ref: RefCell<PyObject>;
-    data dropped: TestDropCall;
-
-    def __traverse__(&self, visit) {
-        visit.call(&*self.self_ref(py).borrow())
-    }
-
-    def __clear__(&self) {
-        let old_ref = mem::replace(&mut *self.self_ref(py).borrow_mut(), py.None());
-        // Release reference only after the mutable borrow has expired.
-        old_ref.release_ref(py);
-    }
-});
+#[class(gc)]
+struct GCIntegration {
+    self_ref: PyObject,
+    dropped: TestDropCall,
+}
 
 #[test]
 fn gc_integration() {
@@ -300,10 +719,98 @@ fn gc_integration() {
 
     let drop_called = Arc::new(AtomicBool::new(false));
     let inst = GCIntegration::create_instance(py,
-        RefCell::new(py.None()),
+        py.None(),
         TestDropCall { drop_called: drop_called.clone() }
     ).unwrap();
-    *inst.self_ref(py).borrow_mut() = inst.as_object().clone_ref(py);
+    *inst.self_ref_mut(py).unwrap() = inst.as_object().clone_ref(py);
+    inst.release_ref(py);
+
+    py.run("import gc; gc.collect()", None, None).unwrap();
+    assert!(drop_called.load(Ordering::Relaxed));
+}
+
+struct Node {
+    next: Option<PyObject>,
+}
+
+impl PyTraverse for Node {
+    fn traverse(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        self.next.traverse(visit)
+    }
+
+    fn clear(&mut self, py: Python) {
+        self.next.clear(py)
+    }
+}
+
+#[class(gc)]
+struct GCContainers {
+    items: Vec<PyObject>,
+    map: HashMap<String, PyObject>,
+    node: Node,
+    count: usize,
+    dropped: TestDropCall,
+}
+
+#[test]
+fn gc_containers() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let drop_called = Arc::new(AtomicBool::new(false));
+    let inst = GCContainers::create_instance(
+        py, Vec::new(), HashMap::new(), Node { next: None }, 0,
+        TestDropCall { drop_called: drop_called.clone() }).unwrap();
+    let obj = inst.as_object().clone_ref(py);
+    inst.items_mut(py).unwrap().push(obj.clone_ref(py));
+    inst.map_mut(py).unwrap().insert("self".to_owned(), obj.clone_ref(py));
+    inst.node_mut(py).unwrap().next = Some(obj);
+    py_assert!(py, inst, "__import__('gc').is_tracked(inst)");
+    inst.release_ref(py);
+
+    py.run("import gc; gc.collect()", None, None).unwrap();
+    assert!(drop_called.load(Ordering::Relaxed));
+}
+
+#[class(gc)]
+struct TypedCycle {
+    other: Option<TypedCycle>,
+    dropped: TestDropCall,
+}
+
+#[test]
+fn gc_typed_cycle() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let drop_called = Arc::new(AtomicBool::new(false));
+    let a = TypedCycle::create_instance(
+        py, None, TestDropCall { drop_called: drop_called.clone() }).unwrap();
+    let b = TypedCycle::create_instance(
+        py, Some(a.clone_ref(py)), TestDropCall { drop_called: Arc::new(AtomicBool::new(false)) })
+        .unwrap();
+    *a.other_mut(py).unwrap() = Some(b.clone_ref(py));
+    a.release_ref(py);
+    b.release_ref(py);
+
+    py.run("import gc; gc.collect()", None, None).unwrap();
+    assert!(drop_called.load(Ordering::Relaxed));
+}
+
+#[class(gc, dict)]
+struct GCDict {
+    dropped: TestDropCall,
+}
+
+#[test]
+fn gc_instance_dict() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let drop_called = Arc::new(AtomicBool::new(false));
+    let inst = GCDict::create_instance(
+        py, TestDropCall { drop_called: drop_called.clone() }).unwrap();
+    py_run!(py, inst, "inst.me = inst");
     inst.release_ref(py);
 
     py.run("import gc; gc.collect()", None, None).unwrap();
@@ -334,26 +841,31 @@ fn len() {
     py_expect_exception!(py, inst, "len(inst)", OverflowError);
 }
 
-py_class!(class Iterator |py| {
-    data iter: RefCell<Box<iter::Iterator<Item=i32> + Send>>;
+#[class]
+struct Iterator {
+    iter: Box<iter::Iterator<Item=i32> + Send>,
+}
 
-    def __iter__(&self) -> PyResult<Iterator> {
+#[proto]
+impl PyIterProtocol for Iterator {
+    fn __iter__(&self, py: Python) -> PyResult<Iterator> {
         Ok(self.clone_ref(py))
     }
 
-    def __next__(&self) -> PyResult<Option<i32>> {
-        Ok(self.iter(py).borrow_mut().next())
+    fn __next__(&mut self, py: Python) -> PyResult<Option<i32>> {
+        Ok(self.iter_mut(py)?.next())
     }
-});
+}
 
 #[test]
 fn iterator() {
     let gil = Python::acquire_gil();
     let py = gil.python();
 
-    let inst = Iterator::create_instance(py, RefCell::new(Box::new(5..8))).unwrap();
+    let inst = Iterator::create_instance(py, Box::new(5..8)).unwrap();
     py_assert!(py, inst, "iter(inst) is inst");
     py_assert!(py, inst, "list(inst) == [5, 6, 7]");
+    py_expect_exception!(py, inst, "next(inst)", StopIteration);
 }
 
 py_class!(class StringMethods |py| {
@@ -456,22 +968,34 @@ fn sequence() {
 }
 
 
-py_class!(class Callable |py| {
-    def __call__(&self, arg: i32) -> PyResult<i32> {
-        Ok(arg * 6)
+#[class]
+struct Callable {
+    calls: i32,
+}
+
+#[methods]
+impl Callable {
+    #[call]
+    #[args(arg, factor = "6")]
+    fn __call__(&self, py: Python, arg: i32, factor: i32) -> PyResult<i32> {
+        *self.calls_mut(py)? += 1;
+        Ok(arg * factor)
     }
-});
+}
 
 #[test]
 fn callable() {
     let gil = Python::acquire_gil();
     let py = gil.python();
 
-    let c = Callable::create_instance(py).unwrap();
+    let c = Callable::create_instance(py, 0).unwrap();
     py_assert!(py, c, "callable(c)");
     py_assert!(py, c, "c(7) == 42");
+    py_assert!(py, c, "c(7, factor=2) == 14");
+    py_expect_exception!(py, c, "c()", TypeError);
+    assert_eq!(*c.calls(py).unwrap(), 2);
 
-    let nc = Comparisons::create_instance(py, 0).unwrap();
+    let nc = EmptyClassWithNew::create_instance(py).unwrap();
     py_assert!(py, nc, "not callable(nc)");
 }
 
@@ -0,0 +1,120 @@
+#![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+#[macro_use] mod common;
+
+use pyo3::*;
+
+
+/// Primary colors
+#[class]
+#[derive(Debug, PartialEq)]
+enum Color {
+    Red,
+    Green = 5,
+    Blue,
+}
+
+#[test]
+fn unit_enum() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let color = Color::type_object(py);
+
+    py_assert!(py, color, "color.__name__ == 'Color'");
+    py_assert!(py, color, "color.__doc__ == 'Primary colors'");
+    py_assert!(py, color, "isinstance(color.Red, color)");
+    py_assert!(py, color, "color.Red == color.Red and color.Red != color.Blue");
+    py_assert!(py, color, "color.Red != 0");
+    py_assert!(py, color, "hash(color.Green) == hash(color.Green)");
+    py_assert!(py, color, "len({color.Red, color.Red, color.Blue}) == 2");
+    py_assert!(py, color, "[int(color.Red), int(color.Green), int(color.Blue)] == [0, 5, 6]");
+    py_assert!(py, color, "repr(color.Blue) == 'Color.Blue'");
+    py_expect_exception!(py, color, "color()", TypeError);
+}
+
+#[class]
+enum Sign {
+    Minus = -1,
+    Zero,
+    Plus,
+}
+
+#[test]
+fn negative_discriminant() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let sign = Sign::type_object(py);
+
+    py_assert!(py, sign, "int(sign.Minus) == -1");
+    py_assert!(py, sign, "hash(sign.Minus) == hash(-1) == -2");
+    py_assert!(py, sign, "len({sign.Minus, sign.Zero, sign.Plus, sign.Minus}) == 3");
+}
+
+#[test]
+fn unit_enum_conversion() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let green = Color::Green.to_py_object(py);
+    py_assert!(py, green, "repr(green) == 'Color.Green'");
+    assert_eq!(green.extract::<Color>(py).unwrap(), Color::Green);
+
+    let color = Color::type_object(py);
+    let blue = color.as_object().getattr(py, "Blue").unwrap();
+    assert_eq!(blue.extract::<Color>(py).unwrap(), Color::Blue);
+
+    assert!(py.None().extract::<Color>(py).is_err());
+}
+
+
+#[class]
+#[derive(Clone, Debug, PartialEq)]
+enum Shape {
+    /// Circle with radius
+    Circle { radius: f64 },
+    Point(i32, i32),
+    Empty,
+}
+
+#[test]
+fn data_enum() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let shape = Shape::type_object(py);
+
+    py_assert!(py, shape, "issubclass(shape.Circle, shape)");
+    py_assert!(py, shape, "issubclass(shape.Point, shape) and issubclass(shape.Empty, shape)");
+    py_assert!(py, shape, "shape.Circle.__doc__ == 'Circle with radius'");
+    py_expect_exception!(py, shape, "shape.Circle()", TypeError);
+
+    let circle = Shape::Circle { radius: 1.5 }.to_py_object(py);
+    py_assert!(py, circle, "type(circle).__name__ == 'Circle'");
+    py_assert!(py, circle, "circle.radius == 1.5");
+    py_assert!(py, circle, "repr(circle) == 'Shape.Circle(radius=1.5)'");
+
+    let point = Shape::Point(1, 2).to_py_object(py);
+    py_assert!(py, point, "(point._0, point._1) == (1, 2)");
+    py_assert!(py, point, "repr(point) == 'Shape.Point(1, 2)'");
+    py_assert!(py, point, "not hasattr(point, 'radius')");
+
+    let empty = Shape::Empty.to_py_object(py);
+    py_assert!(py, empty, "repr(empty) == 'Shape.Empty'");
+}
+
+#[test]
+fn data_enum_conversion() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let point = Shape::Point(3, 4).to_py_object(py);
+    assert_eq!(point.extract::<Shape>(py).unwrap(), Shape::Point(3, 4));
+
+    let circle = Shape::Circle { radius: 2.0 }.into_py_object(py);
+    assert_eq!(circle.extract::<Shape>(py).unwrap(), Shape::Circle { radius: 2.0 });
+
+    let color = Color::Red.to_py_object(py);
+    assert!(color.extract::<Shape>(py).is_err());
+}
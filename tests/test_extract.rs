@@ -0,0 +1,147 @@
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+use pyo3::*;
+
+
+fn eval(py: Python, code: &str) -> PyObject {
+    py.eval(code, None, None).expect(code)
+}
+
+fn extract_error<T>(py: Python, obj: &PyObject) -> String
+    where for<'a> T: FromPyObject<'a> + std::fmt::Debug
+{
+    let mut err = obj.extract::<T>(py).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::TypeError>()));
+    err.instance(py).str(py).unwrap().to_string_lossy(py).into_owned()
+}
+
+
+#[derive(FromPyObject, Debug, PartialEq)]
+struct Retry {
+    retries: u32,
+    delay: f64,
+}
+
+#[derive(FromPyObject, Debug, PartialEq)]
+struct Config {
+    #[extract(item)]
+    name: String,
+    #[extract(item = "retry-policy")]
+    retry: Retry,
+    #[extract(item)]
+    tags: Option<Vec<String>>,
+}
+
+#[test]
+fn extract_attributes() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let obj = eval(py, "type('Retry', (), {'retries': 3, 'delay': 0.5})()");
+    assert_eq!(obj.extract::<Retry>(py).unwrap(), Retry { retries: 3, delay: 0.5 });
+
+    let obj = eval(py, "type('Retry', (), {'retries': '3', 'delay': 0.5})()");
+    assert_eq!(extract_error::<Retry>(py, &obj), "Retry.retries: expected int, got str");
+
+    let obj = eval(py, "type('Retry', (), {'delay': 0.5})()");
+    assert_eq!(extract_error::<Retry>(py, &obj), "Retry.retries: missing attribute");
+
+    // errors other than mismatch are propagated
+    let obj = eval(py, "type('Retry', (), {'retries': 2 ** 40, 'delay': 0.5})()");
+    let err = obj.extract::<Retry>(py).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::OverflowError>()));
+
+    let obj = eval(py, "type('Retry', (), {'retries': property(lambda self: 1 // 0), \
+                                           'delay': 0.5})()");
+    let err = obj.extract::<Retry>(py).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::ZeroDivisionError>()));
+}
+
+#[test]
+fn extract_items() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let retry = "type('Retry', (), {'retries': 1, 'delay': 2.0})()";
+    let obj = eval(py, &format!("{{'name': 'a', 'retry-policy': {}, 'tags': None}}", retry));
+    assert_eq!(obj.extract::<Config>(py).unwrap(), Config {
+        name: String::from("a"), retry: Retry { retries: 1, delay: 2.0 }, tags: None });
+
+    let obj = eval(py, &format!("{{'name': 'a', 'retry-policy': {}, 'tags': ['x']}}", retry));
+    assert_eq!(obj.extract::<Config>(py).unwrap().tags, Some(vec![String::from("x")]));
+
+    let obj = eval(py, "{'name': 'a', 'retry-policy': None, 'tags': None}");
+    assert_eq!(extract_error::<Config>(py, &obj),
+               "Config[\"retry-policy\"].retries: missing attribute");
+
+    let obj = eval(py, &format!("{{'name': 'a', 'retry-policy': {}, 'tags': 1}}", retry));
+    assert_eq!(extract_error::<Config>(py, &obj),
+               "Config[\"tags\"]: expected list or None, got int");
+
+    let obj = eval(py, "{}");
+    assert_eq!(extract_error::<Config>(py, &obj), "Config[\"name\"]: missing key");
+
+    let obj = eval(py, "type('M', (dict,), {'__getitem__': lambda self, key: 1 // 0})()");
+    let err = obj.extract::<Config>(py).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::ZeroDivisionError>()));
+}
+
+
+#[derive(FromPyObject, Debug, PartialEq)]
+struct Point(i32, i32);
+
+#[derive(FromPyObject, Debug, PartialEq)]
+struct Name(String);
+
+#[test]
+fn extract_tuple_struct() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    assert_eq!(eval(py, "(1, 2)").extract::<Point>(py).unwrap(), Point(1, 2));
+    assert_eq!(eval(py, "[3, 4]").extract::<Point>(py).unwrap(), Point(3, 4));
+    assert_eq!(extract_error::<Point>(py, &eval(py, "(1, 'b')")),
+               "Point[1]: expected int, got str");
+    assert_eq!(extract_error::<Point>(py, &eval(py, "(1, 2, 3)")),
+               "Point: expected sequence of length 2, got tuple of length 3");
+    assert_eq!(extract_error::<Point>(py, &eval(py, "1")),
+               "Point: expected sequence, got int");
+
+    assert_eq!(eval(py, "'x'").extract::<Name>(py).unwrap(), Name(String::from("x")));
+    assert_eq!(extract_error::<Name>(py, &eval(py, "1")), "Name: expected str, got int");
+}
+
+
+#[derive(FromPyObject, Debug, PartialEq)]
+enum Value {
+    Int(i64),
+    Pair(Point, Point),
+    Named { name: String },
+}
+
+#[test]
+fn extract_enum() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    assert_eq!(eval(py, "5").extract::<Value>(py).unwrap(), Value::Int(5));
+    assert_eq!(eval(py, "((1, 2), (3, 4))").extract::<Value>(py).unwrap(),
+               Value::Pair(Point(1, 2), Point(3, 4)));
+    assert_eq!(eval(py, "type('N', (), {'name': 'n'})()").extract::<Value>(py).unwrap(),
+               Value::Named { name: String::from("n") });
+
+    let values: Vec<Value> = eval(py, "[1, ((0, 0), (0, 1))]").extract(py).unwrap();
+    assert_eq!(values, vec![Value::Int(1), Value::Pair(Point(0, 0), Point(0, 1))]);
+
+    assert_eq!(extract_error::<Value>(py, &eval(py, "None")),
+               "Value: expected Value, got NoneType (no variant matched: \
+                Int: Value: expected int, got NoneType; \
+                Pair: Value: expected sequence, got NoneType; \
+                Named: Value.name: missing attribute)");
+
+    // variants are not tried after error other than mismatch
+    let err = eval(py, "2 ** 70").extract::<Value>(py).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::OverflowError>()));
+}
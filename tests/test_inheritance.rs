@@ -0,0 +1,76 @@
+#![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+#[macro_use] mod common;
+
+use pyo3::*;
+
+
+#[class(subclass)]
+struct BaseClass {
+    val1: usize,
+}
+
+#[class(base=BaseClass)]
+struct SubClass {
+    val2: usize,
+}
+
+#[test]
+fn subclass_of_rust_class() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let obj = SubClass::create_instance(py, BaseClass::init_storage(10), 5).unwrap();
+    assert_eq!(*obj.val2(py).unwrap(), 5);
+
+    let base = obj.as_object().cast_as::<BaseClass>(py).unwrap();
+    assert_eq!(*base.val1(py).unwrap(), 10);
+
+    let typeobj = py.get_type::<BaseClass>();
+    assert!(typeobj.is_instance(py, obj.as_object()));
+    assert!(py.get_type::<SubClass>().is_subtype_of(py, &typeobj));
+}
+
+
+#[class(base=exc::Exception)]
+struct CustomError {
+    code: i32,
+}
+
+#[test]
+fn subclass_of_exception() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let err = CustomError::create_instance(py, (), 42).unwrap();
+    assert_eq!(*err.code(py).unwrap(), 42);
+
+    let typeobj = py.get_type::<exc::Exception>();
+    assert!(typeobj.is_instance(py, err.as_object()));
+    assert!(py.get_type::<CustomError>().is_subtype_of(py, &typeobj));
+}
+
+
+#[class(base=PyDict)]
+struct DictWithData {
+    data: String,
+}
+
+#[test]
+fn subclass_of_dict() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let obj = DictWithData::create_instance(py, (), "data".to_owned()).unwrap();
+    assert_eq!(*obj.data(py).unwrap(), "data");
+    py_run!(py, obj, "obj['key'] = 1; assert obj['key'] == 1; assert isinstance(obj, dict)");
+
+    // instances release their reference to the heap type
+    let ty = py.get_type::<DictWithData>();
+    let refcnt = ty.as_object().get_refcnt(py);
+    obj.release_ref(py);
+    assert_eq!(ty.as_object().get_refcnt(py), refcnt - 1);
+}
@@ -0,0 +1,108 @@
+#![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+#[macro_use] mod common;
+
+use pyo3::*;
+
+
+#[class(pickle, subclass)]
+struct Point {
+    #[prop(get)]
+    x: i32,
+    #[prop(get)]
+    y: i32,
+    #[prop(get)]
+    tag: PyObject,
+}
+
+impl PyPickleProtocol for Point {
+    type State = (i32, i32, PyObject);
+
+    fn to_state(&self, py: Python) -> PyResult<(i32, i32, PyObject)> {
+        Ok((*self.x(py)?, *self.y(py)?, self.tag(py)?.clone_ref(py)))
+    }
+
+    fn from_state(py: Python, cls: &PyType, state: (i32, i32, PyObject)) -> PyResult<Point> {
+        let (x, y, tag) = state;
+        Point::create_instance_of(py, cls, x, y, tag)
+    }
+}
+
+/// Registers `Point` in importable module, pickle looks the class up by its name
+fn point_module(py: Python) -> PyModule {
+    let module = PyModule::new(py, "test_pickle_module").unwrap();
+    module.add_class::<Point>(py).unwrap();
+    py_run!(py, module, "import sys; sys.modules['test_pickle_module'] = module");
+    module
+}
+
+#[test]
+fn pickle_roundtrip() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    // type object is created before the class is added to the module
+    let tag = PyList::new(py, &[py.None()]).into_object();
+    let p = Point::create_instance(py, 1, 2, tag).unwrap();
+    let module = point_module(py);
+
+    py_assert!(py, p, "type(p).__module__ == 'test_pickle_module'");
+    py_assert!(py, p, "type(p).__qualname__ == 'Point'");
+    py_run!(py, p, "import pickle\n\
+                    q = pickle.loads(pickle.dumps(p))\n\
+                    assert type(q) is type(p)\n\
+                    assert (q.x, q.y, q.tag) == (1, 2, [None])");
+}
+
+#[test]
+fn copy_and_deepcopy() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let tag = PyList::new(py, &[py.None()]).into_object();
+    let p = Point::create_instance(py, 3, 4, tag).unwrap();
+
+    py_run!(py, p, "import copy\n\
+                    q = copy.copy(p)\n\
+                    assert q is not p and (q.x, q.y) == (3, 4)\n\
+                    assert q.tag is p.tag");
+    py_run!(py, p, "import copy\n\
+                    q = copy.deepcopy(p)\n\
+                    assert q is not p and (q.x, q.y) == (3, 4)\n\
+                    assert q.tag is not p.tag and q.tag == p.tag");
+}
+
+#[test]
+fn pickle_subclass() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let module = point_module(py);
+    py_run!(py, module, "import pickle, copy\n\
+                         class Sub(module.Point): pass\n\
+                         Sub.__module__ = module.__name__\n\
+                         module.Sub = Sub\n\
+                         p = Sub._from_state((1, 2, None))\n\
+                         p.label = ['sub']\n\
+                         for q in (pickle.loads(pickle.dumps(p)), copy.copy(p), copy.deepcopy(p)):\n    \
+                             assert type(q) is Sub and (q.x, q.y) == (1, 2)\n    \
+                             assert q.label == ['sub']\n\
+                         assert copy.copy(p).label is p.label\n\
+                         assert copy.deepcopy(p).label is not p.label\n\
+                         p.me = p\n\
+                         q = copy.deepcopy(p)\n\
+                         assert q.me is q");
+}
+
+#[test]
+fn pickle_wrong_state() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let cls = py.get_type::<Point>();
+    py_run!(py, cls, "try:\n    cls._from_state((1, 2))\n    assert False\n\
+                      except ValueError:\n    pass");
+}
@@ -0,0 +1,162 @@
+#![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+#[macro_use] mod common;
+
+
+use pyo3::*;
+
+
+#[class]
+struct Basic {
+    num: i32,
+}
+
+#[proto]
+impl PyObjectProtocol for Basic {
+    fn __getattr__(&self, py: Python, name: String) -> PyResult<String> {
+        Ok(format!("attr:{}", name))
+    }
+
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("Basic({})", *self.num(py)?))
+    }
+
+    fn __hash__(&self, py: Python) -> PyResult<usize> {
+        Ok(*self.num(py)? as usize)
+    }
+
+    fn __bool__(&self, py: Python) -> PyResult<bool> {
+        Ok(*self.num(py)? != 0)
+    }
+
+    fn __richcmp__(&self, py: Python, other: i32, op: CompareOp) -> PyResult<PyObject> {
+        match op {
+            CompareOp::Eq => Ok((*self.num(py)? == other).to_py_object(py)),
+            CompareOp::Lt => Ok((*self.num(py)? < other).to_py_object(py)),
+            _ => Ok(py.NotImplemented()),
+        }
+    }
+}
+
+#[test]
+fn object_protocol() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Basic::create_instance(py, 5).unwrap();
+    let zero = Basic::create_instance(py, 0).unwrap();
+
+    py_assert!(py, obj, "obj.name == 'attr:name'");
+    py_assert!(py, obj, "str(obj) == 'Basic(5)'");
+    py_assert!(py, obj, "hash(obj) == 5");
+    py_assert!(py, obj, "bool(obj)");
+    py_assert!(py, zero, "not zero");
+    py_assert!(py, obj, "obj == 5 and obj < 6");
+    py_assert!(py, obj, "obj != 'five'");
+    py_expect_exception!(py, obj, "obj < 'five'", TypeError);
+}
+
+
+#[class]
+struct Number {
+    num: i32,
+}
+
+#[proto]
+impl PyNumberProtocol for Number {
+    fn __add__(&self, py: Python, other: i32) -> PyResult<i32> {
+        Ok(*self.num(py)? + other)
+    }
+
+    fn __pow__(&self, py: Python, other: u32, modulo: Option<i32>) -> PyResult<i32> {
+        let value = self.num(py)?.pow(other);
+        Ok(match modulo {
+            Some(modulo) => value % modulo,
+            None => value,
+        })
+    }
+
+    fn __neg__(&self, py: Python) -> PyResult<i32> {
+        Ok(-*self.num(py)?)
+    }
+
+    fn __round__(&self, py: Python, ndigits: Option<isize>) -> PyResult<i32> {
+        Ok(*self.num(py)?)
+    }
+}
+
+#[test]
+fn number_protocol() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Number::create_instance(py, 10).unwrap();
+
+    py_assert!(py, obj, "obj + 5 == 15");
+    py_assert!(py, obj, "obj ** 2 == 100");
+    py_assert!(py, obj, "pow(obj, 2, 7) == 2");
+    py_assert!(py, obj, "-obj == -10");
+    py_assert!(py, obj, "round(obj) == 10");
+    py_expect_exception!(py, obj, "obj + 'str'", TypeError);
+    py_expect_exception!(py, obj, "obj ** 'str'", TypeError);
+}
+
+
+#[class]
+struct Mapping {
+    num: i32,
+}
+
+#[proto]
+impl PyMappingProtocol for Mapping {
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        Ok(*self.num(py)? as usize)
+    }
+
+    fn __getitem__(&self, py: Python, key: String) -> PyResult<String> {
+        Ok(key.repeat(*self.num(py)? as usize))
+    }
+}
+
+#[test]
+fn mapping_protocol() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Mapping::create_instance(py, 3).unwrap();
+
+    py_assert!(py, obj, "len(obj) == 3");
+    py_assert!(py, obj, "obj['ab'] == 'ababab'");
+    py_expect_exception!(py, obj, "obj[1]", TypeError);
+}
+
+
+#[class]
+struct Context {
+    exit_called: bool,
+}
+
+#[proto]
+impl PyContextProtocol for Context {
+    fn __enter__(&self, py: Python) -> PyResult<i32> {
+        Ok(42)
+    }
+
+    fn __exit__(&self, py: Python,
+                ty: Option<PyType>, value: Option<PyObject>, traceback: Option<PyObject>)
+                -> PyResult<bool> {
+        *self.exit_called_mut(py)? = true;
+        Ok(ty.is_some())
+    }
+}
+
+#[test]
+fn context_protocol() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let obj = Context::create_instance(py, false).unwrap();
+
+    py_run!(py, obj, "with obj as value:\n    assert value == 42");
+    assert!(*obj.exit_called(py).unwrap());
+    py_run!(py, obj, "with obj:\n    raise ValueError('suppressed')");
+}
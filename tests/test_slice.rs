@@ -1,7 +1,9 @@
 #![allow(dead_code, unused_variables)]
+#![feature(proc_macro, specialization)]
 
 #[macro_use] extern crate pyo3;
 
+
 use pyo3::*;
 
 
@@ -48,3 +50,99 @@ fn test_cls_impl() {
     py.run("assert ob[1] == 'int'", None, Some(&d)).unwrap();
     py.run("assert ob[100:200:1] == 'slice'", None, Some(&d)).unwrap();
 }
+
+
+#[class]
+struct Sequence {
+    data: Vec<i32>,
+}
+
+#[proto]
+impl PySequenceProtocol for Sequence {
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        Ok(self.data(py)?.len())
+    }
+
+    fn __getitem__(&self, py: Python, key: SliceOrIndex) -> PyResult<PyObject> {
+        let data = self.data(py)?;
+        match key {
+            SliceOrIndex::Index(idx) => match data.get(idx) {
+                Some(item) => Ok(item.to_py_object(py).into_object()),
+                None => Err(PyErr::new::<exc::IndexError, _>(py, "index out of range")),
+            },
+            SliceOrIndex::Slice(indices) => {
+                let items: Vec<i32> = (0..indices.slicelength)
+                    .map(|i| data[(indices.start + i * indices.step) as usize])
+                    .collect();
+                Ok(items.to_py_object(py).into_object())
+            }
+        }
+    }
+
+    fn __setitem__(&self, py: Python, key: SliceOrIndex, value: Option<i32>) -> PyResult<()> {
+        let mut data = self.data_mut(py)?;
+        if let SliceOrIndex::Index(idx) = key {
+            if idx >= data.len() {
+                return Err(PyErr::new::<exc::IndexError, _>(py, "index out of range"))
+            }
+        }
+        match (key, value) {
+            (SliceOrIndex::Index(idx), Some(value)) => data[idx] = value,
+            (SliceOrIndex::Index(idx), None) => { data.remove(idx); },
+            (SliceOrIndex::Slice(_), _) =>
+                return Err(PyErr::new::<exc::TypeError, _>(py, "slice assignment")),
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_sequence_slice_or_index() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let ob = Sequence::create_instance(py, (0..10).collect()).unwrap();
+    let d = PyDict::new(py);
+    d.set_item(py, "ob", &ob).unwrap();
+
+    py.run("assert ob[1] == 1", None, Some(&d)).unwrap();
+    py.run("assert ob[-1] == 9", None, Some(&d)).unwrap();
+    py.run("assert ob[1:6:2] == [1, 3, 5]", None, Some(&d)).unwrap();
+    py.run("assert ob[::-3] == [9, 6, 3, 0]", None, Some(&d)).unwrap();
+    py.run("assert ob[8:100] == [8, 9]", None, Some(&d)).unwrap();
+    py.run("assert list(ob) == list(range(10))", None, Some(&d)).unwrap();
+
+    let err = py.run("ob[10]", None, Some(&d)).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::IndexError>()));
+    let err = py.run("ob[-11]", None, Some(&d)).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::IndexError>()));
+    let err = py.run("ob[2 ** 100]", None, Some(&d)).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::IndexError>()));
+    let err = py.run("ob['a']", None, Some(&d)).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::TypeError>()));
+
+    py.run("ob[-2] = 42", None, Some(&d)).unwrap();
+    py.run("del ob[0]", None, Some(&d)).unwrap();
+    assert_eq!(*ob.data(py).unwrap(), vec![1, 2, 3, 4, 5, 6, 7, 42, 9]);
+    let err = py.run("ob[1:2] = 5", None, Some(&d)).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::TypeError>()));
+}
+
+#[test]
+fn test_sequence_slots_negative_index() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let ob = Sequence::create_instance(py, (0..10).collect()).unwrap();
+    let seq = ob.as_object().cast_as::<PySequence>(py).unwrap();
+
+    // python adds length to negative index before it calls `sq_item`
+    assert_eq!(seq.get_item(py, -1).unwrap().extract::<i32>(py).unwrap(), 9);
+    let err = seq.get_item(py, -11).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::IndexError>()));
+    let err = seq.set_item(py, -11, &0.to_py_object(py).into_object()).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::IndexError>()));
+    let err = seq.del_item(py, -11).unwrap_err();
+    assert!(err.matches(py, py.get_type::<exc::IndexError>()));
+    assert_eq!(*ob.data(py).unwrap(), (0..10).collect::<Vec<i32>>());
+}
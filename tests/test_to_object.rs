@@ -0,0 +1,88 @@
+#![feature(proc_macro, specialization)]
+
+#[macro_use] extern crate pyo3;
+
+#[macro_use] mod common;
+
+use std::collections::BTreeMap;
+
+use pyo3::*;
+
+
+#[derive(ToPyObject)]
+struct Record {
+    name: String,
+    count: u32,
+    tags: Vec<&'static str>,
+}
+
+#[test]
+fn to_dict() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let record = Record { name: String::from("a"), count: 2, tags: vec!["x"] }.to_py_object(py);
+    py_assert!(py, record, "record == {'name': 'a', 'count': 2, 'tags': ['x']}");
+}
+
+
+/// Point in space
+#[derive(ToPyObject)]
+#[to_py_object(namedtuple)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[test]
+fn to_namedtuple() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let point = Point { x: 1.0, y: 2.5 }.to_py_object(py);
+    py_assert!(py, point, "point == (1.0, 2.5)");
+    py_assert!(py, point, "(point.x, point.y) == (1.0, 2.5)");
+    py_assert!(py, point, "type(point).__name__ == 'Point'");
+    py_assert!(py, point, "type(point).__doc__ == 'Point in space'");
+
+    let other = Point { x: 0.0, y: 0.0 }.to_py_object(py);
+    py_assert!(py, other, "other.x == 0.0");
+    assert!(point.get_type(py) == other.get_type(py));
+}
+
+
+#[derive(ToPyObject)]
+#[to_py_object(namespace)]
+struct Options {
+    verbose: bool,
+    level: Option<i32>,
+}
+
+#[test]
+fn to_namespace() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let options = Options { verbose: true, level: None }.to_py_object(py);
+    py_assert!(py, options, "options.verbose is True and options.level is None");
+    py_assert!(py, options, "type(options).__name__ == 'SimpleNamespace'");
+}
+
+
+#[test]
+fn into_py_dict_kwargs() {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let builtins = py.import("builtins").unwrap();
+    let dict = builtins.get(py, "dict").unwrap();
+
+    let kwargs = [("a", 1), ("b", 2)][..].into_py_dict(py);
+    let obj = dict.call(py, NoArgs, Some(&kwargs)).unwrap();
+    py_assert!(py, obj, "obj == {'a': 1, 'b': 2}");
+
+    let mut map = BTreeMap::new();
+    map.insert("c", "d");
+    let obj = dict.call(py, NoArgs, Some(&map.into_py_dict(py))).unwrap();
+    py_assert!(py, obj, "obj == {'c': 'd'}");
+}